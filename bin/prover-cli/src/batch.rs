@@ -0,0 +1,193 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Parser;
+use prover_sdk::{
+    access_key::ProverAccessKey, errors::SdkErrors, sdk::ProverSDK, BatchInput,
+    BatchStatusResponse, Cairo0ProverInput, CairoCompiledProgram, CairoProverInput, JobResponse,
+    Layout, Priority, RunMode, Uuid,
+};
+use serde::Deserialize;
+use tokio::time::sleep;
+use tracing::info;
+use url::Url;
+
+use crate::{
+    common::{validate_input, CairoVersion},
+    errors::ProveErrors,
+};
+
+#[derive(Parser, Debug, Clone)]
+#[clap(author, version, about, long_about = None)]
+pub struct BatchRunner {
+    #[arg(long, env)]
+    pub prover_url: Url,
+    #[arg(long, env)]
+    pub prover_access_key: String,
+    /// JSON file listing the programs of the batch, see the server README for its format.
+    #[arg(long, env)]
+    pub manifest: PathBuf,
+    /// Only run the programs instead of proving them.
+    #[arg(long, env, default_value = "false")]
+    pub run_only: bool,
+    #[arg(long, env, default_value = "false")]
+    pub wait: bool,
+    /// Where the result of every completed job is written as `<job_id>.json` once the batch
+    /// finished, requires `--wait`.
+    #[arg(long, env)]
+    pub output_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    jobs: Vec<ManifestJob>,
+}
+
+/// Paths are relative to the directory of the manifest.
+#[derive(Debug, Deserialize)]
+struct ManifestJob {
+    #[serde(default = "default_cairo_version")]
+    cairo_version: CairoVersion,
+    layout: Layout,
+    program_path: PathBuf,
+    program_input_path: PathBuf,
+    #[serde(default = "default_run_mode")]
+    run_mode: RunMode,
+    #[serde(default)]
+    n_queries: Option<u32>,
+    #[serde(default)]
+    pow_bits: Option<u32>,
+    #[serde(default)]
+    timeout: Option<u64>,
+    #[serde(default)]
+    priority: Option<Priority>,
+    #[serde(default)]
+    public_result: bool,
+}
+
+fn default_cairo_version() -> CairoVersion {
+    CairoVersion::V1
+}
+
+fn default_run_mode() -> RunMode {
+    RunMode::Trace
+}
+
+impl BatchRunner {
+    pub async fn run(self) {
+        let access_key = ProverAccessKey::from_hex_string(&self.prover_access_key.clone()).unwrap();
+        let sdk = ProverSDK::new(self.prover_url.clone(), access_key)
+            .await
+            .unwrap();
+        let jobs = read_manifest(&self.manifest).unwrap();
+        let batch = if self.run_only {
+            sdk.run_batch(jobs).await.unwrap()
+        } else {
+            sdk.prove_batch(jobs).await.unwrap()
+        };
+        println!(
+            "Batch {} submitted, job ids: {:?}",
+            batch.batch_id, batch.job_ids
+        );
+        if self.wait {
+            let status = wait_for_batch(&sdk, batch.batch_id).await.unwrap();
+            println!(
+                "Batch {} finished: {} completed, {} failed",
+                status.batch_id, status.completed, status.failed
+            );
+            if let Some(output_dir) = self.output_dir {
+                if let Err(e) = save_results(&sdk, &status, &output_dir).await {
+                    println!("Results could not be saved: {}", e);
+                }
+            }
+        }
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<Vec<BatchInput>, ProveErrors> {
+    let manifest: Manifest = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut jobs = Vec::with_capacity(manifest.jobs.len());
+    for job in manifest.jobs {
+        let program_path = base.join(&job.program_path);
+        let program_input_path = base.join(&job.program_input_path);
+        let input = match job.cairo_version {
+            CairoVersion::V0 => BatchInput::Cairo0(Cairo0ProverInput {
+                program: std::fs::read(program_path)?,
+                program_input: std::fs::read(program_input_path)?,
+                layout: job.layout,
+                n_queries: job.n_queries,
+                pow_bits: job.pow_bits,
+                run_mode: job.run_mode,
+                timeout: job.timeout,
+                priority: job.priority,
+                public_result: job.public_result,
+            }),
+            CairoVersion::V1 => {
+                let program: CairoCompiledProgram =
+                    serde_json::from_str(&std::fs::read_to_string(program_path)?)?;
+                let input = std::fs::read_to_string(program_input_path)?;
+                BatchInput::Cairo(CairoProverInput {
+                    program,
+                    program_input: validate_input(&input),
+                    layout: job.layout,
+                    n_queries: job.n_queries,
+                    pow_bits: job.pow_bits,
+                    run_mode: job.run_mode,
+                    timeout: job.timeout,
+                    priority: job.priority,
+                    public_result: job.public_result,
+                })
+            }
+        };
+        jobs.push(input);
+    }
+    Ok(jobs)
+}
+
+async fn wait_for_batch(
+    sdk: &ProverSDK,
+    batch_id: Uuid,
+) -> Result<BatchStatusResponse, ProveErrors> {
+    loop {
+        let status = sdk.get_batch(batch_id).await?;
+        if status.is_finished() {
+            return Ok(status);
+        }
+        info!(
+            "Batch {}: {} pending, {} running, {} completed, {} failed. Waiting for 10 seconds...",
+            batch_id, status.pending, status.running, status.completed, status.failed
+        );
+        sleep(Duration::from_secs(10)).await;
+    }
+}
+
+async fn save_results(
+    sdk: &ProverSDK,
+    status: &BatchStatusResponse,
+    output_dir: &Path,
+) -> Result<(), ProveErrors> {
+    std::fs::create_dir_all(output_dir)?;
+    // A job whose result cannot be fetched or written does not keep the others from being saved
+    for job in &status.jobs {
+        match save_result(sdk, job.job_id, output_dir).await {
+            Ok(()) => {}
+            Err(ProveErrors::SdkErrors(SdkErrors::JobFailed(error))) => {
+                println!("Job {} failed: {}", job.job_id, error);
+            }
+            Err(e) => println!("Result of job {} could not be saved: {}", job.job_id, e),
+        }
+    }
+    Ok(())
+}
+
+async fn save_result(sdk: &ProverSDK, job_id: Uuid, output_dir: &Path) -> Result<(), ProveErrors> {
+    let response = sdk.get_job(job_id).await?.text().await?;
+    if let JobResponse::Completed { result, .. } = serde_json::from_str::<JobResponse>(&response)? {
+        let path = output_dir.join(format!("{}.json", job_id));
+        std::fs::write(path, serde_json::to_string_pretty(&result)?)?;
+    }
+    Ok(())
+}
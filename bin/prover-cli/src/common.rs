@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use starknet_types_core::felt::Felt;
 
 #[derive(Debug, Serialize, Deserialize, ValueEnum, Clone)]
+#[serde(rename_all = "lowercase")]
 pub enum CairoVersion {
     V0,
     V1,
@@ -1,53 +1,83 @@
 use std::time::Duration;
 
-use prover_sdk::{sdk::ProverSDK, JobResponse, JobResult};
-use serde_json::Value;
+use prover_sdk::{
+    errors::SdkErrors, models::JobMetrics, sdk::ProverSDK, JobResponse, JobResult, Uuid,
+};
 use tokio::time::sleep;
 use tracing::info;
 
 use crate::errors::ProveErrors;
 
-pub async fn fetch_job_sse(sdk: ProverSDK, job: u64) -> Result<JobResult, ProveErrors> {
+pub async fn fetch_job_sse(sdk: ProverSDK, job: Uuid) -> Result<JobResult, ProveErrors> {
     info!("Job ID: {}", job);
     sdk.sse(job).await?;
     info!("Job completed");
     let response = sdk.get_job(job).await?;
     let response = response.text().await?;
-    let json_response: JobResponse = serde_json::from_str(&response).unwrap();
-    if let JobResponse::Completed { result, .. } = json_response {
-        return Ok(result);
+    let json_response: JobResponse = serde_json::from_str(&response)?;
+    match json_response {
+        JobResponse::Completed {
+            result, metrics, ..
+        } => {
+            log_metrics(job, &metrics);
+            Ok(result)
+        }
+        JobResponse::TimedOut { error, .. } | JobResponse::Failed { error, .. } => {
+            Err(SdkErrors::JobFailed(error).into())
+        }
+        JobResponse::InProgress { .. } => Err(ProveErrors::Custom("Job failed".to_string())),
     }
-    Err(ProveErrors::Custom("Job failed".to_string()))
 }
-pub async fn fetch_job_polling(sdk: ProverSDK, job: u64) -> Result<JobResult, ProveErrors> {
+pub async fn fetch_job_polling(sdk: ProverSDK, job: Uuid) -> Result<JobResult, ProveErrors> {
     info!("Fetching job: {}", job);
     let mut counter = 0;
     loop {
         let response = sdk.get_job(job).await?;
         let response = response.text().await?;
-        let json_response: Value = serde_json::from_str(&response)?;
-        if let Some(status) = json_response.get("status").and_then(Value::as_str) {
-            match status {
-                "Completed" => {
-                    let json_response: JobResponse = serde_json::from_str(&response).unwrap();
-
-                    if let JobResponse::Completed { result, .. } = json_response {
-                        return Ok(result);
-                    }
-                }
-                "Pending" | "Running" => {
-                    info!("Job is still in progress. Status: {}", status);
-                    info!(
-                        "Time passed: {} Waiting for 10 seconds before retrying...",
-                        counter * 10
-                    );
-                    counter += 1;
-                    sleep(Duration::from_secs(10)).await;
-                }
-                _ => {
-                    return Err(ProveErrors::Custom(json_response.to_string()));
+        let json_response: JobResponse = serde_json::from_str(&response)?;
+        match json_response {
+            JobResponse::Completed {
+                result, metrics, ..
+            } => {
+                log_metrics(job, &metrics);
+                return Ok(result);
+            }
+            JobResponse::InProgress {
+                status, retry_at, ..
+            } => {
+                info!("Job is still in progress. Status: {:?}", status);
+                if let Some(retry_at) = retry_at {
+                    info!("An attempt failed, the job is retried at {}", retry_at);
                 }
+                info!(
+                    "Time passed: {} Waiting for 10 seconds before retrying...",
+                    counter * 10
+                );
+                counter += 1;
+                sleep(Duration::from_secs(10)).await;
+            }
+            JobResponse::TimedOut { error, .. } | JobResponse::Failed { error, .. } => {
+                return Err(SdkErrors::JobFailed(error).into());
             }
         }
     }
 }
+
+fn log_metrics(job: Uuid, metrics: &JobMetrics) {
+    for stage in &metrics.stages {
+        info!(
+            "Job {} stage {} took {} ms, peak memory: {:?} KiB",
+            job, stage.stage, stage.duration_ms, stage.peak_rss_kib
+        );
+    }
+    if let (Some(queued), Some(started)) = (metrics.queued_at, metrics.started_at) {
+        info!(
+            "Job {} waited {} ms in the queue",
+            job,
+            (started - queued).num_milliseconds()
+        );
+    }
+    if let Some(n_steps) = metrics.n_steps {
+        info!("Job {} ran {} steps", job, n_steps);
+    }
+}
@@ -15,6 +15,9 @@ use layout_bridge::LayoutBridgeRunner;
 pub mod snos;
 use snos::SnosRunner;
 
+pub mod batch;
+use batch::BatchRunner;
+
 pub mod common;
 pub mod errors;
 pub mod fetch;
@@ -38,6 +41,8 @@ enum Subcommands {
     LayoutBridge(LayoutBridgeRunner),
     #[clap(about = "Generate snos pie")]
     Snos(SnosRunner),
+    #[clap(about = "Prove or run the cairo programs listed in a manifest as one batch")]
+    Batch(BatchRunner),
 }
 
 #[tokio::main]
@@ -59,5 +64,8 @@ pub async fn main() {
         Subcommands::Snos(snos) => {
             snos.run().await;
         }
+        Subcommands::Batch(batch) => {
+            batch.run().await;
+        }
     }
 }
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use clap::Parser;
 use prover_sdk::{
     access_key::ProverAccessKey, sdk::ProverSDK, Cairo0ProverInput, CairoCompiledProgram,
-    CairoProverInput, JobResult, Layout, ProverResult, RunMode,
+    CairoProverInput, JobResult, Layout, Priority, ProverResult, RunMode, Uuid,
 };
 use url::Url;
 
@@ -41,6 +41,13 @@ pub struct Prove {
     pub run_mode: RunMode,
     #[arg(long, env, default_value = "false")]
     pub full_output: bool,
+    #[arg(long, env)]
+    pub timeout: Option<u64>,
+    #[arg(long, env)]
+    pub priority: Option<Priority>,
+    /// Lets anyone read the result of the job, not only the key that submitted it.
+    #[arg(long, env, default_value = "false")]
+    pub public_result: bool,
 }
 impl Prove {
     pub async fn run(self) {
@@ -77,7 +84,7 @@ pub fn handle_completed_job_response(result: JobResult) -> ProverResult {
     }
 }
 
-pub async fn prove(args: Prove, sdk: ProverSDK) -> u64 {
+pub async fn prove(args: Prove, sdk: ProverSDK) -> Uuid {
     match args.cairo_version {
         CairoVersion::V0 => {
             let program = std::fs::read(&args.program_path).unwrap();
@@ -89,6 +96,9 @@ pub async fn prove(args: Prove, sdk: ProverSDK) -> u64 {
                 pow_bits: args.pow_bits,
                 n_queries: args.n_queries,
                 run_mode: args.run_mode,
+                timeout: args.timeout,
+                priority: args.priority,
+                public_result: args.public_result,
             };
             sdk.prove_cairo0(data).await.unwrap()
         }
@@ -104,6 +114,9 @@ pub async fn prove(args: Prove, sdk: ProverSDK) -> u64 {
                 pow_bits: args.pow_bits,
                 n_queries: args.n_queries,
                 run_mode: args.run_mode,
+                timeout: args.timeout,
+                priority: args.priority,
+                public_result: args.public_result,
             };
             sdk.prove_cairo(data).await.unwrap()
         }
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use clap::Parser;
 use prover_sdk::{
     access_key::ProverAccessKey, sdk::ProverSDK, Cairo0ProverInput, CairoCompiledProgram,
-    CairoProverInput, JobResult, Layout, RunMode, RunResult,
+    CairoProverInput, JobResult, Layout, Priority, RunMode, RunResult, Uuid,
 };
 use tokio::fs;
 use url::Url;
@@ -36,6 +36,13 @@ pub struct CairoRunner {
     pub proof_dir: PathBuf,
     #[arg(long, env, default_value = "trace")]
     pub run_mode: RunMode,
+    #[arg(long, env)]
+    pub timeout: Option<u64>,
+    #[arg(long, env)]
+    pub priority: Option<Priority>,
+    /// Lets anyone read the result of the job, not only the key that submitted it.
+    #[arg(long, env, default_value = "false")]
+    pub public_result: bool,
 }
 impl CairoRunner {
     pub async fn run(self) {
@@ -96,7 +103,7 @@ impl CairoRunner {
     }
 }
 
-pub async fn cairo_runner(args: CairoRunner, sdk: ProverSDK) -> u64 {
+pub async fn cairo_runner(args: CairoRunner, sdk: ProverSDK) -> Uuid {
     match args.cairo_version {
         CairoVersion::V0 => {
             let program = std::fs::read(&args.program_path).unwrap();
@@ -108,6 +115,9 @@ pub async fn cairo_runner(args: CairoRunner, sdk: ProverSDK) -> u64 {
                 pow_bits: None,
                 n_queries: None,
                 run_mode: args.run_mode,
+                timeout: args.timeout,
+                priority: args.priority,
+                public_result: args.public_result,
             };
             sdk.run_cairo0(data).await.unwrap()
         }
@@ -123,6 +133,9 @@ pub async fn cairo_runner(args: CairoRunner, sdk: ProverSDK) -> u64 {
                 pow_bits: None,
                 n_queries: None,
                 run_mode: args.run_mode,
+                timeout: args.timeout,
+                priority: args.priority,
+                public_result: args.public_result,
             };
             sdk.run_cairo(data).await.unwrap()
         }
@@ -1,4 +1,9 @@
-use clap::{arg, Parser};
+use chrono::{DateTime, Utc};
+use clap::{arg, Parser, Subcommand};
+use common::{
+    models::{JobKind, KeyPolicy, KeyQuota, KeyRole},
+    prover_input::Layout,
+};
 use ed25519_dalek::VerifyingKey;
 use prover_sdk::{access_key::ProverAccessKey, errors::SdkErrors, sdk::ProverSDK};
 use url::Url;
@@ -10,11 +15,84 @@ pub struct Args {
     #[arg(long, short, env)]
     pub private_key: String,
 
+    #[arg(long, short, env)]
+    pub url: Url,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Authorizes a key, or replaces the role, scopes and limits of an authorized key
+    Add(AddArgs),
+    /// Revokes a key, its requests are rejected from then on
+    Revoke {
+        #[arg(long, short = 'k', env)]
+        key: String,
+    },
+    /// Lists the authorized keys with their policies
+    List,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AddArgs {
     #[arg(long, short = 'k', env)]
     pub added_key: String,
 
-    #[arg(long, short, env)]
-    pub url: Url,
+    #[arg(long, env, value_enum, default_value = "user")]
+    pub role: KeyRole,
+
+    /// Kinds of jobs the key may submit, any kind when not given.
+    #[arg(long, env, value_enum, value_delimiter = ',')]
+    pub kinds: Vec<JobKind>,
+
+    /// Layouts the key may use, any layout when not given.
+    #[arg(long, env, value_delimiter = ',')]
+    pub layouts: Vec<Layout>,
+
+    #[arg(long, env)]
+    pub label: Option<String>,
+
+    /// RFC 3339 time after which the key is rejected, e.g. 2025-01-31T00:00:00Z.
+    #[arg(long, env)]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    #[arg(long, env)]
+    pub max_concurrent_jobs: Option<u64>,
+
+    #[arg(long, env)]
+    pub max_queued_jobs: Option<u64>,
+
+    #[arg(long, env)]
+    pub requests_per_minute: Option<u64>,
+
+    #[arg(long, env)]
+    pub daily_proving_seconds: Option<u64>,
+}
+
+impl AddArgs {
+    fn policy(self) -> KeyPolicy {
+        KeyPolicy {
+            role: self.role,
+            kinds: self.kinds,
+            layouts: self.layouts,
+            label: self.label,
+            expires_at: self.expires_at,
+            quota: KeyQuota {
+                max_concurrent_jobs: self.max_concurrent_jobs,
+                max_queued_jobs: self.max_queued_jobs,
+                requests_per_minute: self.requests_per_minute,
+                daily_proving_seconds: self.daily_proving_seconds,
+            },
+        }
+    }
+}
+
+fn parse_key(key: &str) -> Result<VerifyingKey, SdkErrors> {
+    let bytes: [u8; 32] =
+        prefix_hex::decode(key).map_err(|e| SdkErrors::PrefixError(e.to_string()))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| SdkErrors::InvalidKey)
 }
 
 #[tokio::main]
@@ -28,9 +106,21 @@ async fn main() -> Result<(), SdkErrors> {
         .await
         .expect("Failed to create SDK instance");
 
-    let bytes: [u8; 32] =
-        prefix_hex::decode(&args.added_key).map_err(|e| SdkErrors::PrefixError(e.to_string()))?;
-    let added_key = VerifyingKey::from_bytes(&bytes).map_err(|_| SdkErrors::InvalidKey)?;
-
-    sdk.register(added_key).await
+    match args.command {
+        Command::Add(add) => {
+            let added_key = parse_key(&add.added_key)?;
+            sdk.register_with_policy(added_key, add.policy()).await
+        }
+        Command::Revoke { key } => sdk.revoke_key(parse_key(&key)?).await,
+        Command::List => {
+            for authorized in sdk.list_keys().await?.keys {
+                println!(
+                    "{} {}",
+                    prefix_hex::encode(authorized.key.to_bytes()),
+                    serde_json::to_string(&authorized.policy)?
+                );
+            }
+            Ok(())
+        }
+    }
 }
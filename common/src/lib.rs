@@ -7,6 +7,7 @@ pub mod models;
 pub mod prover_input;
 pub mod requests;
 pub mod snos_input;
+pub mod ws;
 
 pub trait HttpProverData {
     fn to_json_value(&self) -> serde_json::Value;
@@ -14,6 +15,13 @@ pub trait HttpProverData {
 }
 pub trait Signable: Serialize + Debug {}
 
+/// Header with the version of the signing scheme of a request. Requests without it are signed
+/// with `sign_data`, version 1, which covers the JSON body, timestamp and nonce but not the
+/// method or path it was sent to.
+pub const SIGNATURE_VERSION_HEADER: &str = "X-Signature-Version";
+/// Version of the scheme of `sign_request`.
+pub const SIGNATURE_VERSION: u32 = 2;
+
 impl<T: Serialize + Debug> Signable for T {}
 
 pub fn sign_data<T: Signable>(
@@ -34,3 +42,65 @@ pub fn sign_data<T: Signable>(
     let signature: Signature = signing_key.sign(&hash);
     hex::encode(signature.to_bytes())
 }
+
+/// What version 2 of the signing scheme signs: the method, the path with its query relative to
+/// the root of the server, the hex SHA-256 of the raw body, the timestamp and the nonce, one per
+/// line. A request without a body hashes the empty string.
+pub fn signing_message(
+    method: &str,
+    path_and_query: &str,
+    body: &[u8],
+    timestamp: &str,
+    nonce: u64,
+) -> Vec<u8> {
+    format!(
+        "v{}\n{}\n{}\n{}\n{}\n{}",
+        SIGNATURE_VERSION,
+        method.to_uppercase(),
+        path_and_query,
+        hex::encode(Sha256::digest(body)),
+        timestamp,
+        nonce
+    )
+    .into_bytes()
+}
+
+/// Signs a request with version 2 of the signing scheme, see `signing_message`.
+pub fn sign_request(
+    method: &str,
+    path_and_query: &str,
+    body: &[u8],
+    timestamp: &str,
+    signing_key: &SigningKey,
+    nonce: u64,
+) -> String {
+    let message = signing_message(method, path_and_query, body, timestamp, nonce);
+    let hash = Sha256::digest(message);
+    let signature: Signature = signing_key.sign(&hash);
+    hex::encode(signature.to_bytes())
+}
+
+/// JSON of the value with the keys of its objects sorted, so that equal values encode to the same
+/// bytes whatever order their fields were written in and however `serde_json` is built.
+pub fn canonical_json(value: &serde_json::Value) -> Result<Vec<u8>, serde_json::Error> {
+    serde_json::to_vec(&sorted_keys(value.clone()))
+}
+
+fn sorted_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries: Vec<(String, serde_json::Value)> = object.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted_keys(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(values) => {
+            serde_json::Value::Array(values.into_iter().map(sorted_keys).collect())
+        }
+        value => value,
+    }
+}
@@ -1,7 +1,13 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use ed25519_dalek::VerifyingKey;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use starknet_types_core::felt::Felt;
+use std::{fmt, time::Duration};
+use uuid::Uuid;
+
+use crate::prover_input::Layout;
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,15 +17,29 @@ pub struct JWTResponse {
     pub expiration: u64,
     pub session_key: Option<VerifyingKey>,
 }
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum JobStatus {
     Pending,
     Running,
+    Tracing,
+    Proving,
+    Parsing,
     Completed,
     Failed,
+    Cancelled,
+    TimedOut,
     Unknown,
 }
 
+impl JobStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled | JobStatus::TimedOut
+        )
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ProverResult {
     pub proof: String,
@@ -56,18 +76,492 @@ pub enum JobResult {
     Run(RunResult),
     Snos(SnosPieOutput),
 }
+/// Wall time and resources used by one step of a job, e.g. trace generation or proving.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageMetrics {
+    pub stage: String,
+    pub duration_ms: u64,
+    /// Peak resident memory of the processes that ran the stage, in KiB. It is sampled, so short
+    /// spikes of the processes spawned by the stage's command may be missed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_rss_kib: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobMetrics {
+    pub queued_at: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub stages: Vec<StageMetrics>,
+    pub peak_rss_kib: Option<u64>,
+    pub n_steps: Option<u64>,
+    pub proof_size: Option<usize>,
+    /// Earlier attempts of the job that failed and were retried.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retries: Vec<JobAttempt>,
+    /// The result was served from the cache of the server instead of being proven again.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cache_hit: bool,
+}
+
+/// A failed attempt of a job that was retried.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobAttempt {
+    /// Number of the attempt, starting at 1.
+    pub attempt: u32,
+    pub failed_at: DateTime<Utc>,
+    /// When the next attempt was scheduled to start, missing for attempts recorded by older
+    /// servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_at: Option<DateTime<Utc>>,
+    pub error: JobError,
+}
+
+impl StageMetrics {
+    pub fn new(stage: &str, duration: Duration, peak_rss_kib: Option<u64>) -> Self {
+        Self {
+            stage: stage.to_string(),
+            duration_ms: duration.as_millis() as u64,
+            peak_rss_kib,
+        }
+    }
+}
+
+impl JobMetrics {
+    pub fn record_stage(&mut self, stage: StageMetrics) {
+        self.peak_rss_kib = self.peak_rss_kib.max(stage.peak_rss_kib);
+        self.stages.push(stage);
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JobResponse {
     InProgress {
-        id: u64,
+        id: Uuid,
         status: JobStatus,
+        /// Number of jobs scheduled ahead of this one, only set while it waits for a worker.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        queue_position: Option<usize>,
+        /// When the next attempt starts, only set while a job that failed transiently waits out
+        /// its backoff.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_at: Option<DateTime<Utc>>,
+        #[serde(default)]
+        metrics: JobMetrics,
     },
     Completed {
         result: JobResult,
         status: JobStatus,
+        #[serde(default)]
+        metrics: JobMetrics,
+    },
+    TimedOut {
+        status: JobStatus,
+        error: JobError,
+        #[serde(default)]
+        metrics: JobMetrics,
+    },
+    Failed {
+        error: JobError,
+        #[serde(default)]
+        metrics: JobMetrics,
+    },
+}
+
+impl JobResponse {
+    /// Why the job failed, `None` while it runs or once it completed.
+    pub fn error(&self) -> Option<&JobError> {
+        match self {
+            JobResponse::TimedOut { error, .. } | JobResponse::Failed { error, .. } => Some(error),
+            JobResponse::InProgress { .. } | JobResponse::Completed { .. } => None,
+        }
+    }
+}
+
+/// What went wrong with a failed job, as a machine-readable code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobErrorCode {
+    /// The program or its input was rejected by the Cairo runner.
+    InvalidProgram,
+    /// The program failed while executing one of its hints.
+    HintFailure,
+    /// The job ran out of memory or disk, e.g. it was killed by the OOM killer.
+    OutOfResources,
+    /// The prover exited abnormally.
+    ProverCrash,
+    /// A proof was produced but it does not verify, or it commits to another output than the
+    /// program wrote.
+    VerifierMismatch,
+    /// A service the job depends on failed, e.g. the RPC provider a SNOS block is fetched from.
+    RpcFailure,
+    /// The job exceeded its timeout.
+    Timeout,
+    /// The job was cancelled by its owner.
+    Cancelled,
+    /// Anything else, e.g. the server failed to store the artifacts of the job.
+    Internal,
+}
+
+impl JobErrorCode {
+    /// HTTP status a failed job with this code is served with.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            JobErrorCode::InvalidProgram | JobErrorCode::HintFailure => 422,
+            JobErrorCode::OutOfResources => 503,
+            JobErrorCode::ProverCrash | JobErrorCode::VerifierMismatch | JobErrorCode::Internal => {
+                500
+            }
+            JobErrorCode::RpcFailure => 502,
+            JobErrorCode::Timeout => 504,
+            JobErrorCode::Cancelled => 410,
+        }
+    }
+    /// Whether submitting the same job again may succeed, errors caused by the program itself
+    /// will happen again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            JobErrorCode::OutOfResources
+                | JobErrorCode::ProverCrash
+                | JobErrorCode::RpcFailure
+                | JobErrorCode::Timeout
+                | JobErrorCode::Internal
+        )
+    }
+    /// Status of a job that failed with this code.
+    pub fn job_status(&self) -> JobStatus {
+        match self {
+            JobErrorCode::Timeout => JobStatus::TimedOut,
+            JobErrorCode::Cancelled => JobStatus::Cancelled,
+            _ => JobStatus::Failed,
+        }
+    }
+}
+
+/// Error of a failed job. `details` carries the raw output behind it, e.g. the end of the stderr
+/// of `cairo-run`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobError {
+    pub code: JobErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl JobError {
+    pub fn new(code: JobErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(JobErrorCode::Internal, message)
+    }
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+    pub fn is_retryable(&self) -> bool {
+        self.code.is_retryable()
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(details) = &self.details {
+            write!(f, ": {}", details)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JobError {}
+
+/// Captured output of the processes of a job, as returned by `/job/:id/logs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobLogsResponse {
+    pub job_id: Uuid,
+    pub lines: Vec<String>,
+    /// Older lines that were dropped to keep the log bounded.
+    pub dropped: usize,
+}
+
+/// What a job does, recorded when it is submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Prove,
+    Run,
+    LayoutBridge,
+    Snos,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Prove => "prove",
+            JobKind::Run => "run",
+            JobKind::LayoutBridge => "layout_bridge",
+            JobKind::Snos => "snos",
+        }
+    }
+}
+
+/// Filters and page of a `/jobs` listing. Repeat `status` or `kind` to match any of several,
+/// `created_after` is inclusive and `created_before` exclusive.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobQuery {
+    #[serde(default)]
+    pub status: Vec<JobStatus>,
+    #[serde(default)]
+    pub kind: Vec<JobKind>,
+    #[serde(default)]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub offset: usize,
+    /// Page size, the server caps it and picks a default when omitted.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// A job as listed by `/jobs`, without its result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSummary {
+    pub job_id: Uuid,
+    /// Jobs stored before kinds were recorded have none.
+    pub kind: Option<JobKind>,
+    pub status: JobStatus,
+    pub owner: Option<VerifyingKey>,
+    pub created: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    pub error: Option<JobError>,
+}
+
+/// A page of jobs, newest first. `total` counts every job matching the filters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobListResponse {
+    pub jobs: Vec<JobSummary>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// Jobs created for a batch, in the order of its inputs. Identical proofs of one batch share a job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub batch_id: Uuid,
+    pub job_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJobStatus {
+    pub job_id: Uuid,
+    pub status: JobStatus,
+}
+
+/// Progress of a batch as returned by `/batch/:id`. Jobs that were evicted since count as failed
+/// with an `Unknown` status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchStatusResponse {
+    pub batch_id: Uuid,
+    pub created: DateTime<Utc>,
+    pub total: usize,
+    pub pending: usize,
+    pub running: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub jobs: Vec<BatchJobStatus>,
+}
+
+impl BatchStatusResponse {
+    /// Whether every job of the batch has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.completed + self.failed == self.total
+    }
+}
+
+/// Limits of a key, configured along with it in the authorized keys file. Missing limits are not
+/// enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyQuota {
+    /// Unfinished jobs, queued or running, the key may have at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_jobs: Option<u64>,
+    /// Jobs of the key that may wait for a worker at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_queued_jobs: Option<u64>,
+    /// Signed requests of any kind the key may send per minute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u64>,
+    /// Seconds the runner and prover may spend on the jobs of the key per UTC day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_proving_seconds: Option<u64>,
+}
+
+/// What a key may do besides submitting jobs. Admins may register keys, read any job and have
+/// their jobs scheduled ahead of others.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyRole {
+    #[default]
+    User,
+    Admin,
+}
+
+/// Everything the authorized keys file records about a key besides the key itself. The default
+/// policy is the one of a bare key: an unlimited user key that may submit anything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyPolicy {
+    #[serde(default)]
+    pub role: KeyRole,
+    /// Kinds of jobs the key may submit, any kind when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<JobKind>,
+    /// Layouts the key may prove and run programs with, any layout when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layouts: Vec<Layout>,
+    /// Who the key was handed out to, e.g. `ci` or the name of a partner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// After this the key is rejected as if it was never authorized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub quota: KeyQuota,
+}
+
+impl KeyPolicy {
+    pub fn is_admin(&self) -> bool {
+        self.role == KeyRole::Admin
+    }
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+    pub fn allows_kind(&self, kind: JobKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+    pub fn allows_layout(&self, layout: &Layout) -> bool {
+        self.layouts.is_empty() || self.layouts.contains(layout)
+    }
+}
+
+/// An authorized key and its policy, as listed by `/keys`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthorizedKey {
+    pub key: VerifyingKey,
+    #[serde(flatten)]
+    pub policy: KeyPolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyListResponse {
+    pub keys: Vec<AuthorizedKey>,
+}
+
+/// How much of one limit is used, `limit` and `remaining` are `None` when it is not enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub used: u64,
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+}
+
+impl QuotaUsage {
+    pub fn new(used: u64, limit: Option<u64>) -> Self {
+        Self {
+            used,
+            limit,
+            remaining: limit.map(|limit| limit.saturating_sub(used)),
+        }
+    }
+}
+
+/// Allowance left to the signing key, as returned by `/quota`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaResponse {
+    pub concurrent_jobs: QuotaUsage,
+    pub queued_jobs: QuotaUsage,
+    pub requests_per_minute: QuotaUsage,
+    pub daily_proving_seconds: QuotaUsage,
+    /// When the daily proving time starts over.
+    pub resets_at: DateTime<Utc>,
+}
+
+/// Progress of a job as streamed on `/sse`, the variant name is the SSE event type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobEvent {
+    /// The job was accepted and waits for a worker.
+    Queued {
+        job_id: Uuid,
+    },
+    /// A worker picked the job up, `status` is its first stage.
+    Started {
+        job_id: Uuid,
+        status: JobStatus,
+    },
+    /// The job moved on to another stage, e.g. from `Tracing` to `Proving`.
+    Stage {
+        job_id: Uuid,
+        status: JobStatus,
+    },
+    /// A line of output from one of the job's processes.
+    Log {
+        job_id: Uuid,
+        line: String,
+    },
+    /// An attempt of the job failed with a transient error, it is queued again after `retry_in_ms`.
+    Retrying {
+        job_id: Uuid,
+        attempt: u32,
+        error: JobError,
+        retry_in_ms: u64,
     },
+    Completed {
+        job_id: Uuid,
+    },
+    /// The job ended without a result, `status` tells whether it failed, timed out or was cancelled.
     Failed {
-        error: String,
+        job_id: Uuid,
+        status: JobStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<JobError>,
     },
 }
+
+impl JobEvent {
+    pub fn job_id(&self) -> Uuid {
+        match self {
+            JobEvent::Queued { job_id }
+            | JobEvent::Started { job_id, .. }
+            | JobEvent::Stage { job_id, .. }
+            | JobEvent::Log { job_id, .. }
+            | JobEvent::Retrying { job_id, .. }
+            | JobEvent::Completed { job_id }
+            | JobEvent::Failed { job_id, .. } => *job_id,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Queued { .. } => "queued",
+            JobEvent::Started { .. } => "started",
+            JobEvent::Stage { .. } => "stage",
+            JobEvent::Log { .. } => "log",
+            JobEvent::Retrying { .. } => "retrying",
+            JobEvent::Completed { .. } => "completed",
+            JobEvent::Failed { .. } => "failed",
+        }
+    }
+    /// No event follows a terminal one for the same job.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobEvent::Completed { .. } | JobEvent::Failed { .. })
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use starknet_types_core::felt::Felt;
 
-use super::RunMode;
+use super::{Priority, RunMode};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CairoProverInput {
@@ -11,6 +11,15 @@ pub struct CairoProverInput {
     pub n_queries: Option<u32>,
     pub pow_bits: Option<u32>,
     pub run_mode: RunMode,
+    /// Requested execution timeout in seconds, the server caps it at its own limit.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Requested scheduling priority, derived from the signing key when omitted and never above it.
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Lets anyone read the result of the job, not only the key that submitted it.
+    #[serde(default)]
+    pub public_result: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
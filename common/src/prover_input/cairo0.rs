@@ -1,4 +1,4 @@
-use super::{Layout, RunMode};
+use super::{Layout, Priority, RunMode};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,6 +9,15 @@ pub struct Cairo0ProverInput {
     pub n_queries: Option<u32>,
     pub pow_bits: Option<u32>,
     pub run_mode: RunMode,
+    /// Requested execution timeout in seconds, the server caps it at its own limit.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Requested scheduling priority, derived from the signing key when omitted and never above it.
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Lets anyone read the result of the job, not only the key that submitted it.
+    #[serde(default)]
+    pub public_result: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
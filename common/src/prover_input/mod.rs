@@ -17,6 +17,47 @@ pub enum ProverInput {
     Cairo0(Cairo0ProverInput),
     Cairo(CairoProverInput),
 }
+/// One program of a batch submitted to `/prove/batch` or `/run/batch`, tagged with its version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "version", rename_all = "snake_case")]
+pub enum BatchInput {
+    Cairo0(Cairo0ProverInput),
+    Cairo(CairoProverInput),
+}
+
+impl BatchInput {
+    pub fn layout(&self) -> &Layout {
+        match self {
+            BatchInput::Cairo0(input) => &input.layout,
+            BatchInput::Cairo(input) => &input.layout,
+        }
+    }
+    pub fn run_mode(&self) -> &RunMode {
+        match self {
+            BatchInput::Cairo0(input) => &input.run_mode,
+            BatchInput::Cairo(input) => &input.run_mode,
+        }
+    }
+    pub fn priority(&self) -> Option<Priority> {
+        match self {
+            BatchInput::Cairo0(input) => input.priority,
+            BatchInput::Cairo(input) => input.priority,
+        }
+    }
+    pub fn timeout(&self) -> Option<u64> {
+        match self {
+            BatchInput::Cairo0(input) => input.timeout,
+            BatchInput::Cairo(input) => input.timeout,
+        }
+    }
+}
+
+/// Programs submitted together under the signature of a single request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub jobs: Vec<BatchInput>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ValueEnum)]
 pub enum RunMode {
     Bootload,
@@ -39,6 +80,15 @@ impl HttpProverData for ProverInput {
     }
 }
 
+impl HttpProverData for BatchRequest {
+    fn to_json_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+    fn sign(&self, signing_key: SigningKey, timestamp: String, nonce: u64) -> String {
+        sign_data(self, &timestamp, &signing_key, nonce)
+    }
+}
+
 impl HttpProverData for LayoutBridgeInput {
     fn to_json_value(&self) -> serde_json::Value {
         serde_json::to_value(self).unwrap()
@@ -48,6 +98,17 @@ impl HttpProverData for LayoutBridgeInput {
     }
 }
 
+/// Scheduling priority of a job, more urgent jobs are picked up first by the server.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+pub enum Priority {
+    Low = 0,
+    #[default]
+    Normal = 1,
+    High = 2,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum Layout {
     Small,
@@ -1,5 +1,9 @@
+use chrono::{DateTime, Utc};
 use ed25519_dalek::{Signature, VerifyingKey};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{canonical_json, models::KeyPolicy};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ValidateSignatureRequest {
@@ -21,4 +25,54 @@ pub struct AddKeyRequest {
     pub signature: Signature,
     pub authority: VerifyingKey,
     pub new_key: VerifyingKey,
+    /// Role, scopes, expiry and limits of the new key as a [`KeyPolicy`], the default policy when
+    /// missing. Registering a key that is already authorized replaces its policy. It is kept as
+    /// sent, so the signature covers it and not what the server parses it into.
+    #[serde(default)]
+    pub policy: Value,
+    /// Signed along with the key so that the request cannot be replayed, they are checked like
+    /// the headers of any other signed request.
+    pub timestamp: DateTime<Utc>,
+    pub nonce: u64,
+}
+
+impl AddKeyRequest {
+    /// The policy sent for `policy`, `null` for the default one.
+    pub fn policy_value(policy: &KeyPolicy) -> Result<Value, serde_json::Error> {
+        if *policy == KeyPolicy::default() {
+            return Ok(Value::Null);
+        }
+        serde_json::to_value(policy)
+    }
+
+    /// The policy of the request, rejecting one that is not a valid [`KeyPolicy`].
+    pub fn key_policy(&self) -> Result<KeyPolicy, serde_json::Error> {
+        if self.policy.is_null() {
+            return Ok(KeyPolicy::default());
+        }
+        KeyPolicy::deserialize(&self.policy)
+    }
+
+    /// What the authority signs: the new key, followed by the canonical JSON of the policy as it
+    /// is sent unless it is missing, then a line with the timestamp and one with the nonce.
+    pub fn message(
+        new_key: &VerifyingKey,
+        policy: &Value,
+        timestamp: &DateTime<Utc>,
+        nonce: u64,
+    ) -> Result<Vec<u8>, serde_json::Error> {
+        let mut message = new_key.as_bytes().to_vec();
+        if !policy.is_null() {
+            message.extend(canonical_json(policy)?);
+        }
+        message.extend(format!("\n{}\n{}", timestamp.to_rfc3339(), nonce).into_bytes());
+        Ok(message)
+    }
+}
+
+/// Authorizes `new_key` with the policy of `old_key`, which is revoked at the same time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateKeyRequest {
+    pub old_key: VerifyingKey,
+    pub new_key: VerifyingKey,
 }
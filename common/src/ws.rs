@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    models::{JobEvent, JobResponse},
+    prover_input::{Cairo0ProverInput, CairoProverInput, LayoutBridgeInput},
+    snos_input::SnosPieInput,
+};
+
+/// Messages a client sends over the `/ws` endpoint. `request_id` is chosen by the client and
+/// echoed in the response, so several requests can be in flight on one connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsRequest {
+    ProveCairo {
+        request_id: u64,
+        input: CairoProverInput,
+    },
+    ProveCairo0 {
+        request_id: u64,
+        input: Cairo0ProverInput,
+    },
+    RunCairo {
+        request_id: u64,
+        input: CairoProverInput,
+    },
+    RunCairo0 {
+        request_id: u64,
+        input: Cairo0ProverInput,
+    },
+    LayoutBridge {
+        request_id: u64,
+        input: LayoutBridgeInput,
+    },
+    Snos {
+        request_id: u64,
+        input: SnosPieInput,
+    },
+    /// Follow the events of a job that was submitted earlier, submitted jobs are followed anyway.
+    /// Answered with [`WsResponse::Subscribed`], or an error if the job is unknown or may not be
+    /// read.
+    Subscribe {
+        request_id: u64,
+        job_id: Uuid,
+    },
+    GetJob {
+        request_id: u64,
+        job_id: Uuid,
+    },
+}
+
+impl WsRequest {
+    pub fn request_id(&self) -> u64 {
+        match self {
+            WsRequest::ProveCairo { request_id, .. }
+            | WsRequest::ProveCairo0 { request_id, .. }
+            | WsRequest::RunCairo { request_id, .. }
+            | WsRequest::RunCairo0 { request_id, .. }
+            | WsRequest::LayoutBridge { request_id, .. }
+            | WsRequest::Snos { request_id, .. }
+            | WsRequest::Subscribe { request_id, .. }
+            | WsRequest::GetJob { request_id, .. } => *request_id,
+        }
+    }
+}
+
+/// Messages the server sends over the `/ws` endpoint.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsResponse {
+    /// The job was created and queued.
+    Accepted {
+        request_id: u64,
+        job_id: Uuid,
+    },
+    /// The job is followed, its remembered events come next.
+    Subscribed {
+        request_id: u64,
+        job_id: Uuid,
+    },
+    /// Progress of a followed job, `id` matches the SSE event id when there is one.
+    Event {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+        event: JobEvent,
+    },
+    Job {
+        request_id: u64,
+        job: JobResponse,
+    },
+    /// The request failed, `request_id` is missing if the message could not be parsed.
+    Error {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+        error: String,
+    },
+}
@@ -1,3 +1,4 @@
+use common::models::JobError;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,8 +9,26 @@ pub enum SdkErrors {
     UrlParseError(#[from] url::ParseError),
     #[error("Prover response error: {0}")]
     ProveResponseError(String),
+    /// The server did not accept the job, the second field is the suggested delay in seconds.
+    #[error("Server busy: {0}")]
+    ServerBusy(String, Option<u64>),
     #[error("Get Job response error: {0}")]
     GetJobResponseError(String),
+    #[error("Cancel Job response error: {0}")]
+    CancelJobResponseError(String),
+    /// The job ended without a result, the code of the error tells whether to submit it again.
+    #[error("Job failed: {0}")]
+    JobFailed(JobError),
+    #[error("Get Job logs response error: {0}")]
+    GetJobLogsResponseError(String),
+    #[error("Batch response error: {0}")]
+    BatchResponseError(String),
+    #[error("List Jobs response error: {0}")]
+    ListJobsResponseError(String),
+    #[error("Quota response error: {0}")]
+    QuotaResponseError(String),
+    #[error("Keys response error: {0}")]
+    KeysResponseError(String),
     #[error("Prefix error: {0}")]
     PrefixError(String),
     #[error("Nonce Request error: {0}")]
@@ -30,6 +49,8 @@ pub enum SdkErrors {
     RegisterResponseError(String),
     #[error("SSE error: {0}")]
     SSEError(String),
+    #[error("WebSocket error: {0}")]
+    WebSocketError(String),
     #[error("Verify response error: {0}")]
     VerifyResponseError(String),
     #[error("Invalid key")]
@@ -41,3 +62,14 @@ pub enum SdkErrors {
     #[error("Invalid Option for prove/run, cairo1 does not support layout bridge option")]
     LayoutBridgeError,
 }
+
+impl SdkErrors {
+    /// Whether submitting the same request again may succeed, e.g. once the server is less busy.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SdkErrors::ServerBusy(..) => true,
+            SdkErrors::JobFailed(error) => error.is_retryable(),
+            _ => false,
+        }
+    }
+}
@@ -1,8 +1,11 @@
 pub mod access_key;
 pub mod errors;
 pub mod sdk;
+pub mod ws;
 
-pub use common::models::{JobResponse, ProverResult};
+pub use common::models::{BatchResponse, BatchStatusResponse};
+pub use common::models::{JobError, JobErrorCode, JobResponse, ProverResult};
 pub use common::models::{JobResult, RunResult};
 pub use common::prover_input::*;
 pub use common::*;
+pub use uuid::Uuid;
@@ -1,18 +1,34 @@
-use crate::{access_key::ProverAccessKey, errors::SdkErrors};
+use crate::{access_key::ProverAccessKey, errors::SdkErrors, ws::ProverWebSocket};
 use chrono::Utc;
 use common::{
-    prover_input::{Cairo0ProverInput, CairoProverInput, LayoutBridgeInput, ProverInput},
-    requests::AddKeyRequest,
+    models::{
+        AuthorizedKey, BatchResponse, BatchStatusResponse, JobListResponse, JobLogsResponse,
+        JobQuery, JobResponse, KeyListResponse, KeyPolicy, QuotaResponse,
+    },
+    prover_input::{
+        BatchInput, BatchRequest, Cairo0ProverInput, CairoProverInput, LayoutBridgeInput,
+        ProverInput,
+    },
+    requests::{AddKeyRequest, RotateKeyRequest},
+    sign_request,
     snos_input::SnosPieInput,
-    HttpProverData,
+    HttpProverData, SIGNATURE_VERSION, SIGNATURE_VERSION_HEADER,
 };
 use ed25519_dalek::{ed25519::signature::Signer, VerifyingKey};
 
 use futures::StreamExt;
 use rand::Rng;
-use reqwest::{Client, Response};
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
 use serde::Deserialize;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, http::HeaderValue},
+};
 use url::Url;
+use uuid::Uuid;
 #[derive(Debug, Clone)]
 /// ProverSDK is a struct representing a client for interacting with the Prover service.
 pub struct ProverSDK {
@@ -21,18 +37,29 @@ pub struct ProverSDK {
     pub prover_cairo: Url,
     pub run_cairo0: Url,
     pub run_cairo: Url,
+    pub prove_batch: Url,
+    pub run_batch: Url,
+    pub batch: Url,
     pub layout_bridge: Url,
     pub snos_pie_gen: Url,
     pub verify: Url,
     pub get_job: Url,
+    pub public_job: Url,
+    pub job: Url,
+    pub jobs: Url,
+    pub quota: Url,
+    pub keys: Url,
     pub register: Url,
     pub sse: Url,
+    pub ws: Url,
+    /// Root of the server, the other urls are relative to it.
+    pub url: Url,
     pub authority: ProverAccessKey,
 }
 
 #[derive(Deserialize)]
 pub struct JobId {
-    pub job_id: u64,
+    pub job_id: Uuid,
 }
 
 impl ProverSDK {
@@ -44,7 +71,15 @@ impl ProverSDK {
         } else {
             url
         };
-        let client = reqwest::Client::new();
+        // The server looks the key up by this header instead of trying every authorized key
+        let mut headers = HeaderMap::new();
+        let public_key = prefix_hex::encode(access_key.0.verifying_key().to_bytes());
+        headers.insert(
+            "X-Public-Key",
+            reqwest::header::HeaderValue::from_str(&public_key)
+                .map_err(|_| SdkErrors::InvalidKey)?,
+        );
+        let client = Client::builder().default_headers(headers).build()?;
 
         Ok(ProverSDK {
             client,
@@ -52,35 +87,91 @@ impl ProverSDK {
             prover_cairo: url.join("prove/cairo")?,
             run_cairo0: url.join("run/cairo0")?,
             run_cairo: url.join("run/cairo")?,
+            prove_batch: url.join("prove/batch")?,
+            run_batch: url.join("run/batch")?,
+            batch: url.join("batch")?,
             layout_bridge: url.join("layout-bridge")?,
             snos_pie_gen: url.join("run/snos")?,
             verify: url.join("verify")?,
             get_job: url.join("get-job")?,
+            public_job: url.join("public/job")?,
+            job: url.join("job")?,
+            jobs: url.join("jobs")?,
+            quota: url.join("quota")?,
+            keys: url.join("keys")?,
             register: url.join("register")?,
             sse: url.join("sse")?,
+            ws: ws_url(url.join("ws")?)?,
+            url,
             authority: access_key,
         })
     }
 
-    async fn send_prover_request<T: HttpProverData>(
+    /// Headers signing a request to `url` with the current version of the signing scheme.
+    fn signature_headers(
         &self,
-        data: T,
+        method: &str,
         url: &Url,
-    ) -> Result<u64, SdkErrors> {
+        body: &[u8],
+    ) -> [(&'static str, String); 4] {
         let nonce = rand::thread_rng().gen::<u64>();
         let current_time = Utc::now().to_rfc3339();
-        let signature = data.sign(self.authority.0.clone(), current_time.clone(), nonce);
+        let signature = sign_request(
+            method,
+            &self.signed_path(url),
+            body,
+            &current_time,
+            &self.authority.0,
+            nonce,
+        );
+        [
+            ("X-Signature", signature),
+            ("X-Timestamp", current_time),
+            ("X-Nonce", nonce.to_string()),
+            (SIGNATURE_VERSION_HEADER, SIGNATURE_VERSION.to_string()),
+        ]
+    }
 
+    // The path is signed from the root of the server, which is what the server sees when a proxy
+    // serves it under a prefix of the SDK url
+    fn signed_path(&self, url: &Url) -> String {
+        let path = url
+            .path()
+            .strip_prefix(self.url.path())
+            .unwrap_or(url.path());
+        let mut signed = format!("/{}", path.trim_start_matches('/'));
+        if let Some(query) = url.query() {
+            signed.push('?');
+            signed.push_str(query);
+        }
+        signed
+    }
+
+    /// Builds a signed request, a non-empty `body` is sent as JSON.
+    fn signed_request(&self, method: Method, url: Url, body: Vec<u8>) -> RequestBuilder {
+        let mut request = self.client.request(method.clone(), url.clone());
+        for (name, value) in self.signature_headers(method.as_str(), &url, &body) {
+            request = request.header(name, value);
+        }
+        if body.is_empty() {
+            request
+        } else {
+            request.header(CONTENT_TYPE, "application/json").body(body)
+        }
+    }
+
+    async fn send_prover_request<T: HttpProverData>(
+        &self,
+        data: T,
+        url: &Url,
+    ) -> Result<Uuid, SdkErrors> {
+        let body = serde_json::to_vec(&data.to_json_value())?;
         let response = self
-            .client
-            .post(url.clone())
-            .header("X-Signature", signature)
-            .header("X-Timestamp", current_time)
-            .header("X-Nonce", nonce)
-            .json(&data.to_json_value())
+            .signed_request(Method::POST, url.clone(), body)
             .send()
             .await?;
 
+        let response = reject_busy(response).await?;
         if !response.status().is_success() {
             let response_data: String = response.text().await?;
             tracing::error!("{}", response_data);
@@ -92,7 +183,7 @@ impl ProverSDK {
         Ok(job.job_id)
     }
 
-    pub async fn prove_cairo0(&self, data: Cairo0ProverInput) -> Result<u64, SdkErrors> {
+    pub async fn prove_cairo0(&self, data: Cairo0ProverInput) -> Result<Uuid, SdkErrors> {
         if !data.layout.is_bootloadable()
             && matches!(data.run_mode, common::prover_input::RunMode::Bootload)
         {
@@ -102,7 +193,7 @@ impl ProverSDK {
             .await
     }
 
-    pub async fn prove_cairo(&self, data: CairoProverInput) -> Result<u64, SdkErrors> {
+    pub async fn prove_cairo(&self, data: CairoProverInput) -> Result<Uuid, SdkErrors> {
         if !data.layout.is_bootloadable()
             && matches!(data.run_mode, common::prover_input::RunMode::Bootload)
         {
@@ -112,11 +203,11 @@ impl ProverSDK {
             .await
     }
 
-    async fn prove(&self, data: ProverInput, url: Url) -> Result<u64, SdkErrors> {
+    async fn prove(&self, data: ProverInput, url: Url) -> Result<Uuid, SdkErrors> {
         self.send_prover_request(data, &url).await
     }
 
-    pub async fn run_cairo0(&self, data: Cairo0ProverInput) -> Result<u64, SdkErrors> {
+    pub async fn run_cairo0(&self, data: Cairo0ProverInput) -> Result<Uuid, SdkErrors> {
         if !data.layout.is_bootloadable()
             && matches!(data.run_mode, common::prover_input::RunMode::Bootload)
         {
@@ -126,7 +217,7 @@ impl ProverSDK {
             .await
     }
 
-    pub async fn run_cairo(&self, data: CairoProverInput) -> Result<u64, SdkErrors> {
+    pub async fn run_cairo(&self, data: CairoProverInput) -> Result<Uuid, SdkErrors> {
         if !data.layout.is_bootloadable()
             && matches!(data.run_mode, common::prover_input::RunMode::Bootload)
         {
@@ -136,14 +227,65 @@ impl ProverSDK {
             .await
     }
 
-    async fn run(&self, data: ProverInput, url: Url) -> Result<u64, SdkErrors> {
+    async fn run(&self, data: ProverInput, url: Url) -> Result<Uuid, SdkErrors> {
         self.send_prover_request(data, &url).await
     }
 
-    pub async fn snos_pie_gen(&self, data: SnosPieInput) -> Result<u64, SdkErrors> {
+    /// Submits several proofs under a single signed request. The job ids are in the order of
+    /// `jobs`, progress of the whole batch is available through [`ProverSDK::get_batch`].
+    pub async fn prove_batch(&self, jobs: Vec<BatchInput>) -> Result<BatchResponse, SdkErrors> {
+        self.send_batch_request(jobs, &self.prove_batch).await
+    }
+
+    /// Runs several programs under a single signed request.
+    pub async fn run_batch(&self, jobs: Vec<BatchInput>) -> Result<BatchResponse, SdkErrors> {
+        self.send_batch_request(jobs, &self.run_batch).await
+    }
+
+    async fn send_batch_request(
+        &self,
+        jobs: Vec<BatchInput>,
+        url: &Url,
+    ) -> Result<BatchResponse, SdkErrors> {
+        if jobs.iter().any(|input| {
+            !input.layout().is_bootloadable()
+                && matches!(input.run_mode(), common::prover_input::RunMode::Bootload)
+        }) {
+            return Err(SdkErrors::BootloaderError);
+        }
+        let body = serde_json::to_vec(&BatchRequest { jobs })?;
+        let response = self
+            .signed_request(Method::POST, url.clone(), body)
+            .send()
+            .await?;
+        let response = reject_busy(response).await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::BatchResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Returns the status of every job of a batch. Only the key that submitted it may read it.
+    pub async fn get_batch(&self, batch_id: Uuid) -> Result<BatchStatusResponse, SdkErrors> {
+        let url = Url::parse(&format!("{}/{}", self.batch.clone().as_str(), batch_id))?;
+        let response = self
+            .signed_request(Method::GET, url, Vec::new())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::BatchResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+
+    pub async fn snos_pie_gen(&self, data: SnosPieInput) -> Result<Uuid, SdkErrors> {
         self.send_prover_request(data, &self.snos_pie_gen).await
     }
-    pub async fn layout_bridge(&self, data: LayoutBridgeInput) -> Result<u64, SdkErrors> {
+    pub async fn layout_bridge(&self, data: LayoutBridgeInput) -> Result<Uuid, SdkErrors> {
         self.send_prover_request(data, &self.layout_bridge).await
     }
     pub async fn verify(self, proof: String) -> Result<String, SdkErrors> {
@@ -157,23 +299,131 @@ impl ProverSDK {
         Ok(response_data)
     }
 
-    pub async fn get_job(&self, job_id: u64) -> Result<Response, SdkErrors> {
-        let url = format!("{}/{}", self.get_job.clone().as_str(), job_id);
+    /// Returns the status or result of a job. Only the key that submitted the job may read it,
+    /// unless the job was submitted with a public result.
+    /// Returns the job, a job that failed, timed out or was cancelled is returned as
+    /// `SdkErrors::JobFailed` with its error.
+    pub async fn get_job(&self, job_id: Uuid) -> Result<Response, SdkErrors> {
+        let url = Url::parse(&format!("{}/{}", self.get_job.clone().as_str(), job_id))?;
+        let response = self
+            .signed_request(Method::GET, url, Vec::new())
+            .send()
+            .await?;
+        job_response(response).await
+    }
+
+    /// Returns a job submitted with a public result, without signing the request.
+    pub async fn get_public_job(&self, job_id: Uuid) -> Result<Response, SdkErrors> {
+        let url = format!("{}/{}", self.public_job.clone().as_str(), job_id);
         let response = self.client.get(url).send().await?;
+        job_response(response).await
+    }
+
+    /// Cancels a pending or running job. Only the key that submitted the job may cancel it.
+    pub async fn cancel_job(&self, job_id: Uuid) -> Result<(), SdkErrors> {
+        let url = Url::parse(&format!("{}/{}", self.job.clone().as_str(), job_id))?;
+        let response = self
+            .signed_request(Method::DELETE, url, Vec::new())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::CancelJobResponseError(response_data));
+        }
+        Ok(())
+    }
+
+    /// Returns the captured output of the Cairo runner and prover processes of a job.
+    /// Only the key that submitted the job may read it.
+    pub async fn get_job_logs(&self, job_id: Uuid) -> Result<JobLogsResponse, SdkErrors> {
+        let url = Url::parse(&format!("{}/{}/logs", self.job.clone().as_str(), job_id))?;
+        let response = self
+            .signed_request(Method::GET, url, Vec::new())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::GetJobLogsResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Lists the jobs submitted with this key matching `query`, newest first. Admin keys list
+    /// the jobs of every key.
+    pub async fn list_jobs(&self, query: &JobQuery) -> Result<JobListResponse, SdkErrors> {
+        let mut url = self.jobs.clone();
+        {
+            let mut pairs = url.query_pairs_mut();
+            for status in &query.status {
+                pairs.append_pair("status", &query_value(status)?);
+            }
+            for kind in &query.kind {
+                pairs.append_pair("kind", &query_value(kind)?);
+            }
+            if let Some(after) = query.created_after {
+                pairs.append_pair("created_after", &after.to_rfc3339());
+            }
+            if let Some(before) = query.created_before {
+                pairs.append_pair("created_before", &before.to_rfc3339());
+            }
+            pairs.append_pair("offset", &query.offset.to_string());
+            if let Some(limit) = query.limit {
+                pairs.append_pair("limit", &limit.to_string());
+            }
+        }
+        let response = self
+            .signed_request(Method::GET, url, Vec::new())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::ListJobsResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Returns how much of its quota this key has left. Limits the key does not have are `None`.
+    pub async fn get_quota(&self) -> Result<QuotaResponse, SdkErrors> {
+        let response = self
+            .signed_request(Method::GET, self.quota.clone(), Vec::new())
+            .send()
+            .await?;
         if !response.status().is_success() {
             let response_data: String = response.text().await?;
             tracing::error!("{}", response_data);
-            return Err(SdkErrors::GetJobResponseError(response_data));
+            return Err(SdkErrors::QuotaResponseError(response_data));
         }
-        Ok(response)
+        Ok(response.json().await?)
     }
 
     pub async fn register(&mut self, key: VerifyingKey) -> Result<(), SdkErrors> {
-        let signature = self.authority.0.sign(key.as_bytes());
+        self.register_with_policy(key, KeyPolicy::default()).await
+    }
+
+    /// Authorizes `key` with a role, scopes, expiry and limits, or replaces those of a key that
+    /// is already authorized. The SDK key must be an admin.
+    pub async fn register_with_policy(
+        &mut self,
+        key: VerifyingKey,
+        policy: KeyPolicy,
+    ) -> Result<(), SdkErrors> {
+        let timestamp = Utc::now();
+        let nonce = rand::thread_rng().gen::<u64>();
+        let policy = AddKeyRequest::policy_value(&policy)?;
+        let signature = self
+            .authority
+            .0
+            .sign(&AddKeyRequest::message(&key, &policy, &timestamp, nonce)?);
         let request = AddKeyRequest {
             signature,
             new_key: key,
             authority: self.authority.0.verifying_key(),
+            policy,
+            timestamp,
+            nonce,
         };
         let response = self
             .client
@@ -190,9 +440,63 @@ impl ProverSDK {
         Ok(())
     }
 
-    pub async fn sse(&self, job_id: u64) -> Result<(), SdkErrors> {
-        let url = format!("{}?job_id={}", self.sse.clone().as_str(), job_id);
-        let response = self.client.get(url).send().await?;
+    /// Lists every authorized key with its policy. The SDK key must be an admin.
+    pub async fn list_keys(&self) -> Result<KeyListResponse, SdkErrors> {
+        let response = self
+            .signed_request(Method::GET, self.keys.clone(), Vec::new())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::KeysResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Revokes `key`, its later requests are rejected. The SDK key must be an admin.
+    pub async fn revoke_key(&self, key: VerifyingKey) -> Result<(), SdkErrors> {
+        let url = Url::parse(&format!(
+            "{}/{}",
+            self.keys.clone().as_str(),
+            prefix_hex::encode(key.to_bytes())
+        ))?;
+        let response = self
+            .signed_request(Method::DELETE, url, Vec::new())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::KeysResponseError(response_data));
+        }
+        Ok(())
+    }
+
+    /// Replaces `old_key` with `new_key`, which keeps the role, scopes and limits of the old one.
+    /// The SDK key must be an admin.
+    pub async fn rotate_key(
+        &self,
+        old_key: VerifyingKey,
+        new_key: VerifyingKey,
+    ) -> Result<AuthorizedKey, SdkErrors> {
+        let url = Url::parse(&format!("{}/rotate", self.keys.clone().as_str()))?;
+        let body = serde_json::to_vec(&RotateKeyRequest { old_key, new_key })?;
+        let response = self.signed_request(Method::POST, url, body).send().await?;
+        if !response.status().is_success() {
+            let response_data: String = response.text().await?;
+            tracing::error!("{}", response_data);
+            return Err(SdkErrors::KeysResponseError(response_data));
+        }
+        Ok(response.json().await?)
+    }
+
+    pub async fn sse(&self, job_id: Uuid) -> Result<(), SdkErrors> {
+        let url = Url::parse(&format!("{}?job_id={}", self.sse.clone().as_str(), job_id))?;
+        let response = self
+            .signed_request(Method::GET, url, Vec::new())
+            .send()
+            .await?;
         if !response.status().is_success() {
             return Err(SdkErrors::SSEError(format!(
                 "Failed to get SSE with status code: {}",
@@ -204,4 +508,77 @@ impl ProverSDK {
         while let Some(_item) = stream.next().await {}
         Ok(())
     }
+
+    /// Opens a WebSocket connection to submit jobs and follow their events over one connection.
+    pub async fn connect_ws(&self) -> Result<ProverWebSocket, SdkErrors> {
+        let mut request = self
+            .ws
+            .as_str()
+            .into_client_request()
+            .map_err(|e| SdkErrors::WebSocketError(e.to_string()))?;
+        // The upgrade request is a signed GET without a body
+        let headers = self.signature_headers("GET", &self.ws, &[]);
+        let public_key = (
+            "X-Public-Key",
+            prefix_hex::encode(self.authority.0.verifying_key().to_bytes()),
+        );
+        for (name, value) in headers.into_iter().chain([public_key]) {
+            let value = HeaderValue::from_str(&value)
+                .map_err(|e| SdkErrors::WebSocketError(e.to_string()))?;
+            request.headers_mut().insert(name, value);
+        }
+        let (stream, _) = connect_async(request)
+            .await
+            .map_err(|e| SdkErrors::WebSocketError(e.to_string()))?;
+        Ok(ProverWebSocket::new(stream))
+    }
+}
+
+/// Failed jobs are served with an error status and their `JobResponse`, which is turned into
+/// `SdkErrors::JobFailed`.
+async fn job_response(response: Response) -> Result<Response, SdkErrors> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let response_data: String = response.text().await?;
+    tracing::error!("{}", response_data);
+    match serde_json::from_str(&response_data) {
+        Ok(JobResponse::Failed { error, .. } | JobResponse::TimedOut { error, .. }) => {
+            Err(SdkErrors::JobFailed(error))
+        }
+        _ => Err(SdkErrors::GetJobResponseError(response_data)),
+    }
+}
+
+/// Turns the answer of a server that could not take the submission right now, because it is
+/// overloaded or the key is over its quota, into `SdkErrors::ServerBusy`.
+async fn reject_busy(response: Response) -> Result<Response, SdkErrors> {
+    if !matches!(
+        response.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    let response_data: String = response.text().await?;
+    Err(SdkErrors::ServerBusy(response_data, retry_after))
+}
+
+// Query parameters take the names enums serialize to, e.g. `Failed` or `layout_bridge`
+fn query_value<T: serde::Serialize>(value: &T) -> Result<String, SdkErrors> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(value) => Ok(value),
+        value => Ok(value.to_string()),
+    }
+}
+
+fn ws_url(mut url: Url) -> Result<Url, SdkErrors> {
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme)
+        .map_err(|_| SdkErrors::WebSocketError(format!("Invalid WebSocket url: {}", url)))?;
+    Ok(url)
 }
@@ -0,0 +1,45 @@
+use common::ws::{WsRequest, WsResponse};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::errors::SdkErrors;
+
+/// A connection to the `/ws` endpoint of the prover, opened with `ProverSDK::connect_ws`.
+/// Requests are answered in order, events of the followed jobs arrive in between.
+pub struct ProverWebSocket {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl ProverWebSocket {
+    pub(crate) fn new(stream: WebSocketStream<MaybeTlsStream<TcpStream>>) -> Self {
+        Self { stream }
+    }
+
+    pub async fn send(&mut self, request: &WsRequest) -> Result<(), SdkErrors> {
+        let text = serde_json::to_string(request)?;
+        self.stream
+            .send(Message::Text(text))
+            .await
+            .map_err(|e| SdkErrors::WebSocketError(e.to_string()))
+    }
+
+    /// Waits for the next message from the server, `None` once the connection is closed.
+    pub async fn recv(&mut self) -> Result<Option<WsResponse>, SdkErrors> {
+        while let Some(message) = self.stream.next().await {
+            match message.map_err(|e| SdkErrors::WebSocketError(e.to_string()))? {
+                Message::Text(text) => return Ok(Some(serde_json::from_str(&text)?)),
+                Message::Close(_) => return Ok(None),
+                _ => continue,
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn close(mut self) -> Result<(), SdkErrors> {
+        self.stream
+            .close(None)
+            .await
+            .map_err(|e| SdkErrors::WebSocketError(e.to_string()))
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
     use chrono::Utc;
-    use common::HttpProverData;
+    use common::{sign_request, HttpProverData, SIGNATURE_VERSION, SIGNATURE_VERSION_HEADER};
     use prover_sdk::errors::SdkErrors::ProveResponseError;
     use prover_sdk::{
         access_key::ProverAccessKey, errors::SdkErrors, sdk::ProverSDK, CairoCompiledProgram,
@@ -10,6 +10,29 @@ mod tests {
     use reqwest::Client;
     use starknet_types_core::felt::Felt;
     use url::Url;
+
+    // Posts the body to `/prove/cairo` signed with version 2 of the scheme by `PRIVATE_KEY`
+    async fn post_signed(
+        body: &[u8],
+        timestamp: &str,
+        nonce: u64,
+    ) -> Result<reqwest::Response, SdkErrors> {
+        let private_key = std::env::var("PRIVATE_KEY").unwrap();
+        let signing_key = ProverAccessKey::from_hex_string(&private_key).unwrap().0;
+        let url = std::env::var("PROVER_URL").unwrap();
+        let signature = sign_request("POST", "/prove/cairo", body, timestamp, &signing_key, nonce);
+        Ok(Client::new()
+            .post(format!("{}/prove/cairo", url))
+            .header("X-Signature", signature)
+            .header("X-Timestamp", timestamp)
+            .header("X-Nonce", nonce.to_string())
+            .header(SIGNATURE_VERSION_HEADER, SIGNATURE_VERSION.to_string())
+            .header("Content-Type", "application/json")
+            .body(body.to_vec())
+            .send()
+            .await?)
+    }
+
     #[tokio::test]
     async fn test_authorized_access() {
         let private_key = std::env::var("PRIVATE_KEY").unwrap();
@@ -32,6 +55,9 @@ mod tests {
             n_queries: Some(16),
             pow_bits: Some(20),
             run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
         };
         let job = sdk.prove_cairo(data).await;
         assert!(job.is_ok());
@@ -58,6 +84,9 @@ mod tests {
             n_queries: Some(16),
             pow_bits: Some(20),
             run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
         };
         let job = sdk.prove_cairo(data).await;
 
@@ -85,33 +114,18 @@ mod tests {
             n_queries: Some(16),
             pow_bits: Some(20),
             run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
         };
-        let data = ProverInput::Cairo(data);
-
-        let private_key = std::env::var("PRIVATE_KEY").unwrap();
-        let access_key = ProverAccessKey::from_hex_string(&private_key).unwrap();
-        let signing_key = access_key.0;
-        let current_time = Utc::now();
-        let current_time_str = current_time.to_rfc3339();
-        let url = std::env::var("PROVER_URL").unwrap();
-
-        let client = Client::new();
+        let body = serde_json::to_vec(&ProverInput::Cairo(data).to_json_value())?;
+        let current_time_str = Utc::now().to_rfc3339();
 
         // Generate a unique nonce
         let nonce: u64 = rand::random(); // Ensures a fresh nonce
 
-        let signature = data.sign(signing_key.clone(), current_time_str.clone(), nonce);
-
         // First request (Expected: 202 Accepted)
-        let first_response = client
-            .post(format!("{}/prove/cairo", url.clone()))
-            .header("X-Signature", signature.clone())
-            .header("X-Timestamp", current_time_str.clone())
-            .header("X-Nonce", nonce.to_string()) // Include nonce
-            .json(&data.to_json_value())
-            .send()
-            .await?;
-
+        let first_response = post_signed(&body, &current_time_str, nonce).await?;
         assert_eq!(
             first_response.status(),
             202,
@@ -119,15 +133,7 @@ mod tests {
         );
 
         // Second request with the same nonce (Expected: 401 Unauthorized)
-        let second_response = client
-            .post(format!("{}/prove/cairo", url.clone()))
-            .header("X-Signature", signature.clone())
-            .header("X-Timestamp", current_time_str.clone()) // Same timestamp
-            .header("X-Nonce", nonce.to_string()) // Same nonce (should be rejected)
-            .json(&data.to_json_value())
-            .send()
-            .await?;
-
+        let second_response = post_signed(&body, &current_time_str, nonce).await?;
         assert_eq!(
             second_response.status(),
             401,
@@ -136,17 +142,7 @@ mod tests {
 
         // Third request with a new nonce (Expected: 202 Accepted)
         let new_nonce: u64 = rand::random(); // Generate a new nonce
-        let new_signature = data.sign(signing_key, current_time_str.clone(), new_nonce);
-
-        let third_response = client
-            .post(format!("{}/prove/cairo", url.clone()))
-            .header("X-Signature", new_signature)
-            .header("X-Timestamp", current_time_str.clone())
-            .header("X-Nonce", new_nonce.to_string()) // New nonce (should be accepted)
-            .json(&data.to_json_value())
-            .send()
-            .await?;
-
+        let third_response = post_signed(&body, &current_time_str, new_nonce).await?;
         assert_eq!(
             third_response.status(),
             202,
@@ -175,52 +171,25 @@ mod tests {
             n_queries: Some(16),
             pow_bits: Some(20),
             run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
         };
-        let data = ProverInput::Cairo(data);
-
-        let private_key = std::env::var("PRIVATE_KEY").unwrap();
-        let access_key = ProverAccessKey::from_hex_string(&private_key).unwrap();
-        let signing_key = access_key.0;
-        let current_time = Utc::now();
-        let current_time_str = current_time.to_rfc3339();
-        let url = std::env::var("PROVER_URL").unwrap();
-
-        let client = Client::new();
-
-        // Generate first nonce
-        let nonce_a: u64 = rand::random();
-        let signature_a = data.sign(signing_key.clone(), current_time_str.clone(), nonce_a);
+        let body = serde_json::to_vec(&ProverInput::Cairo(data).to_json_value())?;
+        let current_time_str = Utc::now().to_rfc3339();
 
         // First request (Expected: 202 Accepted)
-        let first_response = client
-            .post(format!("{}/prove/cairo", url.clone()))
-            .header("X-Signature", signature_a.clone())
-            .header("X-Timestamp", current_time_str.clone())
-            .header("X-Nonce", nonce_a.to_string()) // Unique nonce
-            .json(&data.to_json_value())
-            .send()
-            .await?;
-
+        let nonce_a: u64 = rand::random();
+        let first_response = post_signed(&body, &current_time_str, nonce_a).await?;
         assert_eq!(
             first_response.status(),
             202,
             "First request should be accepted"
         );
 
-        // Generate second nonce (different, but timestamp stays the same)
+        // Second request with a different nonce but the same timestamp (Expected: 202 Accepted)
         let nonce_b: u64 = rand::random();
-        let signature_b = data.sign(signing_key, current_time_str.clone(), nonce_b);
-
-        // Second request (Expected: 202 Accepted)
-        let second_response = client
-            .post(format!("{}/prove/cairo", url.clone()))
-            .header("X-Signature", signature_b.clone())
-            .header("X-Timestamp", current_time_str.clone()) // Same timestamp
-            .header("X-Nonce", nonce_b.to_string()) // Different nonce
-            .json(&data.to_json_value())
-            .send()
-            .await?;
-
+        let second_response = post_signed(&body, &current_time_str, nonce_b).await?;
         assert_eq!(
             second_response.status(),
             202,
@@ -248,12 +217,11 @@ mod tests {
             n_queries: Some(16),
             pow_bits: Some(20),
             run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
         };
-        let data = ProverInput::Cairo(data);
-
-        let private_key = std::env::var("PRIVATE_KEY").unwrap();
-        let access_key = ProverAccessKey::from_hex_string(&private_key).unwrap();
-        let signing_key = access_key.0;
+        let body = serde_json::to_vec(&ProverInput::Cairo(data).to_json_value())?;
         let current_time = Utc::now();
         let current_time_str = current_time.to_rfc3339();
 
@@ -261,47 +229,183 @@ mod tests {
         let outdated_time = current_time - chrono::Duration::seconds(31);
         let outdated_time_str = outdated_time.to_rfc3339();
 
+        // First request (Expected: 202 Accepted)
+        let nonce_a: u64 = rand::random();
+        let first_response = post_signed(&body, &current_time_str, nonce_a).await?;
+        assert_eq!(
+            first_response.status(),
+            202,
+            "First request should be accepted"
+        );
+
+        // Second request with an outdated timestamp (Expected: 401 Unauthorized)
+        let nonce_b: u64 = rand::random();
+        let second_response = post_signed(&body, &outdated_time_str, nonce_b).await?;
+        assert_eq!(
+            second_response.status(),
+            401,
+            "Second request with an outdated timestamp should be rejected"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_signature_is_bound_to_method_and_path() -> Result<(), SdkErrors> {
+        let program = std::fs::read_to_string("../examples/cairo/fibonacci_compiled.json").unwrap();
+        let program: CairoCompiledProgram = serde_json::from_str(&program)?;
+        let program_input_string = std::fs::read_to_string("../examples/cairo/input.json").unwrap();
+        let mut program_input: Vec<Felt> = Vec::new();
+        for part in program_input_string.split(',') {
+            let felt = Felt::from_dec_str(part).unwrap();
+            program_input.push(felt);
+        }
+
+        let data = CairoProverInput {
+            program,
+            layout: Layout::Recursive,
+            program_input,
+            n_queries: Some(16),
+            pow_bits: Some(20),
+            run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
+        };
+        let body = serde_json::to_vec(&ProverInput::Cairo(data).to_json_value())?;
+
+        let private_key = std::env::var("PRIVATE_KEY").unwrap();
+        let signing_key = ProverAccessKey::from_hex_string(&private_key).unwrap().0;
+        let current_time_str = Utc::now().to_rfc3339();
         let url = std::env::var("PROVER_URL").unwrap();
         let client = Client::new();
 
-        // Generate first nonce
-        let nonce_a: u64 = rand::random();
-        let signature_a = data.sign(signing_key.clone(), current_time_str.clone(), nonce_a);
-
-        // First request (Expected: 202 Accepted)
-        let first_response = client
+        // Signed for /run/cairo but sent to /prove/cairo (Expected: 401 Unauthorized)
+        let nonce: u64 = rand::random();
+        let signature = sign_request(
+            "POST",
+            "/run/cairo",
+            &body,
+            &current_time_str,
+            &signing_key,
+            nonce,
+        );
+        let response = client
             .post(format!("{}/prove/cairo", url.clone()))
-            .header("X-Signature", signature_a.clone())
-            .header("X-Timestamp", current_time_str.clone()) // Valid timestamp
-            .header("X-Nonce", nonce_a.to_string()) // Unique nonce
-            .json(&data.to_json_value())
+            .header("X-Signature", signature)
+            .header("X-Timestamp", current_time_str.clone())
+            .header("X-Nonce", nonce.to_string())
+            .header(SIGNATURE_VERSION_HEADER, SIGNATURE_VERSION.to_string())
+            .header("Content-Type", "application/json")
+            .body(body.clone())
             .send()
             .await?;
+        assert_eq!(
+            response.status(),
+            401,
+            "A signature for another path should be rejected"
+        );
 
+        // Signed for the path it is sent to (Expected: 202 Accepted)
+        let nonce: u64 = rand::random();
+        let signature = sign_request(
+            "POST",
+            "/prove/cairo",
+            &body,
+            &current_time_str,
+            &signing_key,
+            nonce,
+        );
+        let response = client
+            .post(format!("{}/prove/cairo", url.clone()))
+            .header("X-Signature", signature)
+            .header("X-Timestamp", current_time_str.clone())
+            .header("X-Nonce", nonce.to_string())
+            .header(SIGNATURE_VERSION_HEADER, SIGNATURE_VERSION.to_string())
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .send()
+            .await?;
         assert_eq!(
-            first_response.status(),
+            response.status(),
             202,
-            "First request should be accepted"
+            "The signed request should be accepted"
         );
 
-        // Generate second nonce (different, but with outdated timestamp)
-        let nonce_b: u64 = rand::random();
-        let signature_b = data.sign(signing_key, outdated_time_str.clone(), nonce_b);
-
-        // Second request (Expected: 401 Unauthorized due to outdated timestamp)
-        let second_response = client
+        // Dated ahead of the server clock (Expected: 401 Unauthorized)
+        let future_time_str = (Utc::now() + chrono::Duration::seconds(60)).to_rfc3339();
+        let nonce: u64 = rand::random();
+        let signature = sign_request(
+            "POST",
+            "/prove/cairo",
+            &body,
+            &future_time_str,
+            &signing_key,
+            nonce,
+        );
+        let response = client
             .post(format!("{}/prove/cairo", url.clone()))
-            .header("X-Signature", signature_b.clone())
-            .header("X-Timestamp", outdated_time_str.clone()) // Outdated timestamp
-            .header("X-Nonce", nonce_b.to_string()) // Different nonce
-            .json(&data.to_json_value())
+            .header("X-Signature", signature)
+            .header("X-Timestamp", future_time_str)
+            .header("X-Nonce", nonce.to_string())
+            .header(SIGNATURE_VERSION_HEADER, SIGNATURE_VERSION.to_string())
+            .header("Content-Type", "application/json")
+            .body(body)
             .send()
             .await?;
-
         assert_eq!(
-            second_response.status(),
+            response.status(),
             401,
-            "Second request with an outdated timestamp should be rejected"
+            "A timestamp in the future should be rejected"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_signature_is_rejected() -> Result<(), SdkErrors> {
+        let program = std::fs::read_to_string("../examples/cairo/fibonacci_compiled.json").unwrap();
+        let program: CairoCompiledProgram = serde_json::from_str(&program)?;
+        let program_input_string = std::fs::read_to_string("../examples/cairo/input.json").unwrap();
+        let mut program_input: Vec<Felt> = Vec::new();
+        for part in program_input_string.split(',') {
+            let felt = Felt::from_dec_str(part).unwrap();
+            program_input.push(felt);
+        }
+
+        let data = CairoProverInput {
+            program,
+            layout: Layout::Recursive,
+            program_input,
+            n_queries: Some(16),
+            pow_bits: Some(20),
+            run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
+        };
+        let data = ProverInput::Cairo(data);
+
+        let private_key = std::env::var("PRIVATE_KEY").unwrap();
+        let signing_key = ProverAccessKey::from_hex_string(&private_key).unwrap().0;
+        let current_time_str = Utc::now().to_rfc3339();
+        let url = std::env::var("PROVER_URL").unwrap();
+
+        // Version 1 signature without X-Signature-Version (Expected: 400 Bad Request)
+        let nonce: u64 = rand::random();
+        let signature = data.sign(signing_key, current_time_str.clone(), nonce);
+        let response = Client::new()
+            .post(format!("{}/prove/cairo", url))
+            .header("X-Signature", signature)
+            .header("X-Timestamp", current_time_str)
+            .header("X-Nonce", nonce.to_string())
+            .json(&data.to_json_value())
+            .send()
+            .await?;
+        assert_eq!(
+            response.status(),
+            400,
+            "A request without a signature version should be rejected by default"
         );
 
         Ok(())
@@ -1,7 +1,7 @@
 use common::models::{JobResponse, JobResult};
-use prover_sdk::{sdk::ProverSDK, ProverResult};
+use prover_sdk::{sdk::ProverSDK, ProverResult, Uuid};
 
-pub async fn fetch_job(sdk: ProverSDK, job: u64) -> Option<JobResult> {
+pub async fn fetch_job(sdk: ProverSDK, job: Uuid) -> Option<JobResult> {
     println!("Job ID: {}", job);
     sdk.sse(job).await.unwrap();
     let response = sdk.get_job(job).await.unwrap();
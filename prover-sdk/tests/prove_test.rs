@@ -29,6 +29,9 @@ async fn test_cairo_prove_bootloader() {
         n_queries: Some(16),
         pow_bits: Some(20),
         run_mode: RunMode::Bootload,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let job = sdk.prove_cairo(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -75,6 +78,9 @@ async fn test_cairo_prove() {
         n_queries: Some(16),
         pow_bits: Some(20),
         run_mode: RunMode::Trace,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let job = sdk.prove_cairo(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -115,6 +121,9 @@ async fn test_cairo0_prove_bootloader() {
         n_queries: Some(16),
         pow_bits: Some(20),
         run_mode: RunMode::Bootload,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let job = sdk.prove_cairo0(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -155,6 +164,9 @@ async fn test_cairo0_prove() {
         n_queries: Some(16),
         pow_bits: Some(20),
         run_mode: RunMode::Trace,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let job = sdk.prove_cairo0(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -201,6 +213,9 @@ async fn test_cairo_multi_prove() {
         n_queries: Some(16),
         pow_bits: Some(20),
         run_mode: RunMode::Trace,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let job1 = sdk.prove_cairo(data.clone()).await.unwrap();
     let job2 = sdk.prove_cairo(data.clone()).await.unwrap();
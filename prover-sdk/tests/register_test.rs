@@ -34,3 +34,33 @@ async fn test_register_unauthorized() {
     let response = sdk.register(random_key.0.verifying_key()).await;
     assert!(response.is_err());
 }
+
+#[tokio::test]
+async fn test_list_rotate_and_revoke_keys() {
+    let url = std::env::var("PROVER_URL").unwrap();
+    let admin_key = std::env::var("ADMIN_PRIVATE_KEY_1").unwrap();
+    let admin_key = ProverAccessKey::from_hex_string(&admin_key).unwrap();
+    let url = Url::parse(&url).unwrap();
+    let mut sdk = ProverSDK::new(url.clone(), admin_key).await.unwrap();
+
+    let old_key = ProverAccessKey::generate().0.verifying_key();
+    let new_key = ProverAccessKey::generate();
+    sdk.register(old_key).await.unwrap();
+    let keys = sdk.list_keys().await.unwrap().keys;
+    assert!(keys.iter().any(|authorized| authorized.key == old_key));
+
+    let rotated = sdk
+        .rotate_key(old_key, new_key.0.verifying_key())
+        .await
+        .unwrap();
+    assert_eq!(rotated.key, new_key.0.verifying_key());
+    let keys = sdk.list_keys().await.unwrap().keys;
+    assert!(!keys.iter().any(|authorized| authorized.key == old_key));
+
+    // Only admins may manage keys
+    let new_sdk = ProverSDK::new(url, new_key.clone()).await.unwrap();
+    assert!(new_sdk.list_keys().await.is_err());
+
+    sdk.revoke_key(new_key.0.verifying_key()).await.unwrap();
+    assert!(sdk.revoke_key(new_key.0.verifying_key()).await.is_err());
+}
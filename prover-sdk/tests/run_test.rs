@@ -31,6 +31,9 @@ async fn test_cairo_run() {
         n_queries: Some(16),
         pow_bits: Some(20),
         run_mode: prover_sdk::RunMode::Trace,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let job = sdk.run_cairo(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
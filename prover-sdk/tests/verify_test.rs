@@ -40,6 +40,9 @@ async fn test_verify_valid_proof() {
         n_queries: Some(16),
         pow_bits: Some(20),
         run_mode: RunMode::Trace,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let job = sdk.clone().prove_cairo(data).await.unwrap();
     let result = fetch_job(sdk.clone(), job).await;
@@ -0,0 +1,123 @@
+use common::{
+    models::{JobEvent, JobResponse, JobResult},
+    ws::{WsRequest, WsResponse},
+};
+use prover_sdk::{
+    access_key::ProverAccessKey, sdk::ProverSDK, CairoCompiledProgram, CairoProverInput, Layout,
+};
+use starknet_types_core::felt::Felt;
+use url::Url;
+
+#[tokio::test]
+async fn test_cairo_run_over_websocket() {
+    let private_key = std::env::var("PRIVATE_KEY").unwrap();
+    let url = std::env::var("PROVER_URL").unwrap();
+    let access_key = ProverAccessKey::from_hex_string(&private_key).unwrap();
+    let url = Url::parse(&url).unwrap();
+    let sdk = ProverSDK::new(url, access_key).await.unwrap();
+    let program = std::fs::read_to_string("../examples/cairo/fibonacci_compiled.json").unwrap();
+    let program: CairoCompiledProgram = serde_json::from_str(&program).unwrap();
+    let program_input_string = std::fs::read_to_string("../examples/cairo/input.json").unwrap();
+    let program_input: Vec<Felt> = program_input_string
+        .split(',')
+        .map(|part| Felt::from_dec_str(part).unwrap())
+        .collect();
+    let input = CairoProverInput {
+        program,
+        layout: Layout::Recursive,
+        program_input,
+        n_queries: Some(16),
+        pow_bits: Some(20),
+        run_mode: prover_sdk::RunMode::Trace,
+        timeout: None,
+        priority: None,
+        public_result: false,
+    };
+
+    let mut ws = sdk.connect_ws().await.unwrap();
+    ws.send(&WsRequest::RunCairo {
+        request_id: 1,
+        input,
+    })
+    .await
+    .unwrap();
+
+    let mut job_id = None;
+    let mut events = Vec::new();
+    while let Some(response) = ws.recv().await.unwrap() {
+        match response {
+            WsResponse::Accepted {
+                request_id,
+                job_id: id,
+            } => {
+                assert_eq!(request_id, 1);
+                job_id = Some(id);
+            }
+            WsResponse::Event { event, .. } => {
+                let terminal = event.is_terminal();
+                events.push(event);
+                if terminal {
+                    break;
+                }
+            }
+            WsResponse::Subscribed { .. } | WsResponse::Job { .. } => {
+                panic!("No job was requested")
+            }
+            WsResponse::Error { error, .. } => panic!("Request failed: {}", error),
+        }
+    }
+    let job_id = job_id.expect("Job was not accepted");
+    assert!(matches!(events.first(), Some(JobEvent::Queued { .. })));
+    assert!(matches!(events.last(), Some(JobEvent::Completed { .. })));
+
+    ws.send(&WsRequest::GetJob {
+        request_id: 2,
+        job_id,
+    })
+    .await
+    .unwrap();
+    match ws.recv().await.unwrap() {
+        Some(WsResponse::Job {
+            request_id: 2,
+            job: JobResponse::Completed { result, .. },
+        }) => assert!(matches!(result, JobResult::Run(_))),
+        _ => panic!("Expected the completed job"),
+    }
+
+    ws.send(&WsRequest::Subscribe {
+        request_id: 3,
+        job_id,
+    })
+    .await
+    .unwrap();
+    match ws.recv().await.unwrap() {
+        Some(WsResponse::Subscribed {
+            request_id: 3,
+            job_id: id,
+        }) => assert_eq!(id, job_id),
+        _ => panic!("Expected the subscription to be confirmed"),
+    }
+    // The finished job replays its events
+    loop {
+        match ws.recv().await.unwrap() {
+            Some(WsResponse::Event { event, .. }) if event.is_terminal() => break,
+            Some(WsResponse::Event { .. }) => continue,
+            _ => panic!("Expected the events of the job"),
+        }
+    }
+
+    ws.send(&WsRequest::Subscribe {
+        request_id: 4,
+        job_id: uuid::Uuid::new_v4(),
+    })
+    .await
+    .unwrap();
+    match ws.recv().await.unwrap() {
+        Some(WsResponse::Error {
+            request_id: Some(4),
+            ..
+        }) => {}
+        _ => panic!("Expected unknown jobs to be rejected"),
+    }
+    ws.close().await.unwrap();
+}
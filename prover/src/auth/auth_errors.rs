@@ -31,4 +31,10 @@ pub enum AuthError {
 
     #[error("Unauthorized")]
     Unauthorized,
+
+    #[error("Forbidden")]
+    Forbidden,
+
+    #[error("{0}")]
+    NotPermitted(String),
 }
@@ -1,16 +1,64 @@
 use super::auth_errors::AuthorizerError;
+use common::models::KeyPolicy;
 use ed25519_dalek::Verifier;
 use ed25519_dalek::{Signature, VerifyingKey};
-use std::path::PathBuf;
-use tokio::{fs::File, io::AsyncReadExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    fs::File,
+    io::AsyncWriteExt,
+    sync::{RwLock, RwLockWriteGuard},
+};
+use tracing::{info, warn};
 
 pub(crate) trait AuthorizationProvider {
     async fn is_authorized(
         &self,
         signature: Signature,
         data_hash: &[u8],
-    ) -> Result<bool, AuthorizerError>;
+    ) -> Result<bool, AuthorizerError> {
+        Ok(self
+            .authorized_key(None, signature, data_hash)
+            .await?
+            .is_some())
+    }
+    /// Returns the authorized key that produced `signature` along with its policy, if any. Only
+    /// `public_key` is tried when the signer names its key, otherwise every authorized key is.
+    async fn authorized_key(
+        &self,
+        public_key: Option<&VerifyingKey>,
+        signature: Signature,
+        data_hash: &[u8],
+    ) -> Result<Option<(VerifyingKey, KeyPolicy)>, AuthorizerError>;
+    /// Authorizes the key with the default policy, keys that are already authorized are kept as
+    /// they are.
     async fn authorize(&self, public_key: VerifyingKey) -> Result<(), AuthorizerError>;
+    /// Authorizes the key with `policy`, replacing the policy it had if it was already authorized.
+    async fn set_policy(
+        &self,
+        public_key: VerifyingKey,
+        policy: KeyPolicy,
+    ) -> Result<(), AuthorizerError>;
+    /// Policy of the key, `None` when it is not authorized.
+    async fn policy(&self, public_key: &VerifyingKey)
+        -> Result<Option<KeyPolicy>, AuthorizerError>;
+    /// Every authorized key with its policy, in the order of the file.
+    async fn keys(&self) -> Result<Vec<(VerifyingKey, KeyPolicy)>, AuthorizerError>;
+    /// Removes the key, returns whether it was authorized.
+    async fn revoke(&self, public_key: &VerifyingKey) -> Result<bool, AuthorizerError>;
+    /// Puts `new_key` in place of `old_key`, keeping its policy. Returns that policy, or `None`
+    /// when `old_key` is not authorized.
+    async fn rotate(
+        &self,
+        old_key: &VerifyingKey,
+        new_key: VerifyingKey,
+    ) -> Result<Option<KeyPolicy>, AuthorizerError>;
 
     #[cfg(test)]
     async fn is_key_authorized(&self, public_key: VerifyingKey) -> Result<bool, AuthorizerError>;
@@ -35,12 +83,69 @@ impl AuthorizationProvider for Authorizer {
             }
         })
     }
+    async fn authorized_key(
+        &self,
+        public_key: Option<&VerifyingKey>,
+        signature: Signature,
+        data_hash: &[u8],
+    ) -> Result<Option<(VerifyingKey, KeyPolicy)>, AuthorizerError> {
+        match self {
+            // The open authorizer accepts any signature, so it cannot tell who signed it
+            Authorizer::Open => Ok(None),
+            Authorizer::Persistent(authorizer) => {
+                authorizer
+                    .authorized_key(public_key, signature, data_hash)
+                    .await
+            }
+        }
+    }
     async fn authorize(&self, public_key: VerifyingKey) -> Result<(), AuthorizerError> {
         match self {
             Authorizer::Open => Ok(()),
             Authorizer::Persistent(authorizer) => authorizer.authorize(public_key).await,
         }
     }
+    async fn set_policy(
+        &self,
+        public_key: VerifyingKey,
+        policy: KeyPolicy,
+    ) -> Result<(), AuthorizerError> {
+        match self {
+            Authorizer::Open => Ok(()),
+            Authorizer::Persistent(authorizer) => authorizer.set_policy(public_key, policy).await,
+        }
+    }
+    async fn policy(
+        &self,
+        public_key: &VerifyingKey,
+    ) -> Result<Option<KeyPolicy>, AuthorizerError> {
+        match self {
+            Authorizer::Open => Ok(None),
+            Authorizer::Persistent(authorizer) => authorizer.policy(public_key).await,
+        }
+    }
+    async fn keys(&self) -> Result<Vec<(VerifyingKey, KeyPolicy)>, AuthorizerError> {
+        match self {
+            Authorizer::Open => Ok(Vec::new()),
+            Authorizer::Persistent(authorizer) => authorizer.keys().await,
+        }
+    }
+    async fn revoke(&self, public_key: &VerifyingKey) -> Result<bool, AuthorizerError> {
+        match self {
+            Authorizer::Open => Ok(false),
+            Authorizer::Persistent(authorizer) => authorizer.revoke(public_key).await,
+        }
+    }
+    async fn rotate(
+        &self,
+        old_key: &VerifyingKey,
+        new_key: VerifyingKey,
+    ) -> Result<Option<KeyPolicy>, AuthorizerError> {
+        match self {
+            Authorizer::Open => Ok(None),
+            Authorizer::Persistent(authorizer) => authorizer.rotate(old_key, new_key).await,
+        }
+    }
 
     #[cfg(test)]
     async fn is_key_authorized(&self, public_key: VerifyingKey) -> Result<bool, AuthorizerError> {
@@ -51,8 +156,95 @@ impl AuthorizationProvider for Authorizer {
     }
 }
 
+/// An entry of the authorized keys file, either the bare key or the key with its policy:
+/// `["0x..", {"key": "0x..", "role": "user", "kinds": ["run"], "quota": {"max_concurrent_jobs": 2}}]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum KeyEntry {
+    Key(String),
+    WithPolicy {
+        key: String,
+        #[serde(flatten)]
+        policy: KeyPolicy,
+    },
+}
+
+impl KeyEntry {
+    fn new(public_key: &VerifyingKey, policy: KeyPolicy) -> Self {
+        let key = prefix_hex::encode(public_key.to_bytes());
+        if policy == KeyPolicy::default() {
+            KeyEntry::Key(key)
+        } else {
+            KeyEntry::WithPolicy { key, policy }
+        }
+    }
+    fn verifying_key(&self) -> Result<VerifyingKey, AuthorizerError> {
+        let key = match self {
+            KeyEntry::Key(key) | KeyEntry::WithPolicy { key, .. } => key,
+        };
+        let verifying_key_bytes = prefix_hex::decode::<Vec<u8>>(key)
+            .map_err(|e| AuthorizerError::PrefixHexConversionError(e.to_string()))?;
+        Ok(VerifyingKey::from_bytes(&verifying_key_bytes.try_into()?)?)
+    }
+    fn policy(&self) -> KeyPolicy {
+        match self {
+            KeyEntry::Key(_) => KeyPolicy::default(),
+            KeyEntry::WithPolicy { policy, .. } => policy.clone(),
+        }
+    }
+}
+
+/// The authorized keys file as last read or written, with the keys looked up by their bytes.
+#[derive(Debug, Default)]
+struct KeySet {
+    keys: Vec<(VerifyingKey, KeyPolicy)>,
+    index: HashMap<[u8; 32], usize>,
+    /// Hash of the contents of the file, edits are detected by it rather than by modification
+    /// times that are too coarse to tell quick edits apart.
+    digest: Option<[u8; 32]>,
+}
+
+impl KeySet {
+    fn new(keys: Vec<(VerifyingKey, KeyPolicy)>, contents: &[u8]) -> Self {
+        let mut index = HashMap::with_capacity(keys.len());
+        for (position, (key, _)) in keys.iter().enumerate() {
+            // A key listed twice keeps its first policy
+            index.entry(key.to_bytes()).or_insert(position);
+        }
+        Self {
+            keys,
+            index,
+            digest: Some(digest(contents)),
+        }
+    }
+    fn parse(contents: &str) -> Result<Self, AuthorizerError> {
+        if contents.trim().is_empty() {
+            return Ok(Self::new(Vec::new(), contents.as_bytes()));
+        }
+        let entries: Vec<KeyEntry> =
+            serde_json::from_str(contents).map_err(AuthorizerError::FormatError)?;
+        let keys = entries
+            .iter()
+            .map(|entry| Ok((entry.verifying_key()?, entry.policy())))
+            .collect::<Result<_, AuthorizerError>>()?;
+        Ok(Self::new(keys, contents.as_bytes()))
+    }
+    fn position(&self, public_key: &VerifyingKey) -> Option<usize> {
+        self.index.get(&public_key.to_bytes()).copied()
+    }
+    fn get(&self, public_key: &VerifyingKey) -> Option<&KeyPolicy> {
+        self.position(public_key)
+            .map(|position| &self.keys[position].1)
+    }
+}
+
+/// Authorizes the keys of a JSON file. The keys are kept in memory, changes made through the
+/// server are written to the file and changes made to the file are picked up by `watch`.
 #[derive(Debug, Clone)]
-pub struct FileAuthorizer(PathBuf);
+pub struct FileAuthorizer {
+    path: PathBuf,
+    keys: Arc<RwLock<KeySet>>,
+}
 
 impl FileAuthorizer {
     pub async fn new(path: PathBuf) -> Result<Self, AuthorizerError> {
@@ -60,116 +252,204 @@ impl FileAuthorizer {
             tokio::fs::write(&path, "[]")
                 .await
                 .map_err(AuthorizerError::FileAccessError)?;
-        } else {
-            File::open(&path)
-                .await
-                .map_err(AuthorizerError::FileAccessError)?;
         }
-        Ok(Self(path))
+        let authorizer = Self {
+            path,
+            keys: Default::default(),
+        };
+        authorizer
+            .refresh(&mut authorizer.keys.write().await)
+            .await?;
+        Ok(authorizer)
     }
-}
-impl AuthorizationProvider for FileAuthorizer {
-    async fn is_authorized(
-        &self,
-        signature: Signature,
-        data_hash: &[u8],
-    ) -> Result<bool, AuthorizerError> {
-        let mut file = File::open(&self.0)
-            .await
-            .map_err(AuthorizerError::FileAccessError)?;
 
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .await
-            .map_err(AuthorizerError::FileAccessError)?;
+    /// Reloads the file every `interval` when its contents changed since it was last read. A file
+    /// that cannot be parsed is reported and the keys loaded before are kept.
+    pub fn watch(&self, interval: Duration) {
+        let authorizer = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match authorizer.reload().await {
+                    Ok(true) => info!("Reloaded authorized keys from {:?}", authorizer.path),
+                    Ok(false) => {}
+                    Err(e) => warn!(
+                        "Keeping the authorized keys loaded before, {:?} could not be read: {}",
+                        authorizer.path, e
+                    ),
+                }
+            }
+        });
+    }
 
-        if contents.trim().is_empty() {
+    /// Reads the file again if its contents changed, returns whether they did.
+    pub async fn reload(&self) -> Result<bool, AuthorizerError> {
+        let contents = read(&self.path).await?;
+        if Some(digest(contents.as_bytes())) == self.keys.read().await.digest {
             return Ok(false);
         }
+        self.refresh(&mut self.keys.write().await).await
+    }
 
-        let serialized_keys: Vec<String> =
-            serde_json::from_str(&contents).map_err(AuthorizerError::FormatError)?;
-
-        for key in serialized_keys.iter() {
-            let verifying_key_bytes = prefix_hex::decode::<Vec<u8>>(key)
-                .map_err(|e| AuthorizerError::PrefixHexConversionError(e.to_string()))?;
-            let verifying_key = VerifyingKey::from_bytes(&verifying_key_bytes.try_into()?)?;
-            if verifying_key.verify(data_hash, &signature).is_ok() {
-                return Ok(true);
-            }
+    // Changes are made with the lock held and start from the file as it is on disk, so that edits
+    // the watcher has not picked up yet are not overwritten
+    async fn refresh(
+        &self,
+        keys: &mut RwLockWriteGuard<'_, KeySet>,
+    ) -> Result<bool, AuthorizerError> {
+        let contents = read(&self.path).await?;
+        if Some(digest(contents.as_bytes())) == keys.digest {
+            return Ok(false);
         }
-        Ok(false)
+        **keys = KeySet::parse(&contents)?;
+        Ok(true)
     }
-    #[cfg(test)]
-    async fn is_key_authorized(&self, public_key: VerifyingKey) -> Result<bool, AuthorizerError> {
-        let mut file = File::open(&self.0)
+
+    /// Writes the keys to a temporary file that then replaces the authorized keys file, so that a
+    /// crash never leaves it half written.
+    async fn write(
+        &self,
+        keys: &mut RwLockWriteGuard<'_, KeySet>,
+        updated: Vec<(VerifyingKey, KeyPolicy)>,
+    ) -> Result<(), AuthorizerError> {
+        let entries: Vec<KeyEntry> = updated
+            .iter()
+            .map(|(key, policy)| KeyEntry::new(key, policy.clone()))
+            .collect();
+        let contents = serde_json::to_vec(&entries).map_err(AuthorizerError::FormatError)?;
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let mut file = File::create(&temporary)
             .await
             .map_err(AuthorizerError::FileAccessError)?;
-
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
+        file.write_all(&contents)
+            .await
+            .map_err(AuthorizerError::FileAccessError)?;
+        file.sync_all()
+            .await
+            .map_err(AuthorizerError::FileAccessError)?;
+        tokio::fs::rename(&temporary, &self.path)
             .await
             .map_err(AuthorizerError::FileAccessError)?;
 
-        if contents.trim().is_empty() {
-            return Ok(false);
-        }
+        **keys = KeySet::new(updated, &contents);
+        Ok(())
+    }
+}
 
-        let serialized_keys: Vec<String> =
-            serde_json::from_str(&contents).map_err(AuthorizerError::FormatError)?;
+async fn read(path: &Path) -> Result<String, AuthorizerError> {
+    tokio::fs::read_to_string(path)
+        .await
+        .map_err(AuthorizerError::FileAccessError)
+}
 
-        for key in serialized_keys.iter() {
-            let verifying_key_bytes = prefix_hex::decode::<Vec<u8>>(key)
-                .map_err(|e| AuthorizerError::PrefixHexConversionError(e.to_string()))?;
-            let verifying_key = VerifyingKey::from_bytes(&verifying_key_bytes.try_into()?)?;
-            if verifying_key == public_key {
-                return Ok(true);
-            }
-        }
-        Ok(false)
+fn digest(contents: &[u8]) -> [u8; 32] {
+    Sha256::digest(contents).into()
+}
+
+impl AuthorizationProvider for FileAuthorizer {
+    async fn authorized_key(
+        &self,
+        public_key: Option<&VerifyingKey>,
+        signature: Signature,
+        data_hash: &[u8],
+    ) -> Result<Option<(VerifyingKey, KeyPolicy)>, AuthorizerError> {
+        let keys = self.keys.read().await;
+        let signer = match public_key {
+            Some(public_key) => keys
+                .get(public_key)
+                .filter(|_| public_key.verify(data_hash, &signature).is_ok())
+                .map(|policy| (*public_key, policy.clone())),
+            None => keys
+                .keys
+                .iter()
+                .find(|(key, _)| key.verify(data_hash, &signature).is_ok())
+                .cloned(),
+        };
+        Ok(signer)
+    }
+    #[cfg(test)]
+    async fn is_key_authorized(&self, public_key: VerifyingKey) -> Result<bool, AuthorizerError> {
+        Ok(self.keys.read().await.get(&public_key).is_some())
     }
 
     async fn authorize(&self, public_key: VerifyingKey) -> Result<(), AuthorizerError> {
-        let mut contents = String::new();
+        let mut keys = self.keys.write().await;
+        self.refresh(&mut keys).await?;
+        if keys.get(&public_key).is_some() {
+            return Ok(());
+        }
+        let mut updated = keys.keys.clone();
+        updated.push((public_key, KeyPolicy::default()));
+        self.write(&mut keys, updated).await
+    }
 
-        let mut file = File::open(&self.0)
-            .await
-            .map_err(AuthorizerError::FileAccessError)?;
+    async fn set_policy(
+        &self,
+        public_key: VerifyingKey,
+        policy: KeyPolicy,
+    ) -> Result<(), AuthorizerError> {
+        let mut keys = self.keys.write().await;
+        self.refresh(&mut keys).await?;
+        let mut updated = keys.keys.clone();
+        match keys.position(&public_key) {
+            Some(position) => updated[position].1 = policy,
+            None => updated.push((public_key, policy)),
+        }
+        self.write(&mut keys, updated).await
+    }
 
-        file.read_to_string(&mut contents)
-            .await
-            .map_err(AuthorizerError::FileAccessError)?;
+    async fn policy(
+        &self,
+        public_key: &VerifyingKey,
+    ) -> Result<Option<KeyPolicy>, AuthorizerError> {
+        Ok(self.keys.read().await.get(public_key).cloned())
+    }
 
-        let mut serialized_keys: Vec<String> =
-            serde_json::from_str(&contents).unwrap_or_else(|_| Vec::new());
+    async fn keys(&self) -> Result<Vec<(VerifyingKey, KeyPolicy)>, AuthorizerError> {
+        Ok(self.keys.read().await.keys.clone())
+    }
 
-        for key in serialized_keys.iter() {
-            let verifying_key_bytes = prefix_hex::decode::<Vec<u8>>(key)
-                .map_err(|e| AuthorizerError::PrefixHexConversionError(e.to_string()))?;
-            let verifying_key = VerifyingKey::from_bytes(&verifying_key_bytes.try_into()?)?;
-            if verifying_key == public_key {
-                return Ok(());
-            }
+    async fn revoke(&self, public_key: &VerifyingKey) -> Result<bool, AuthorizerError> {
+        let mut keys = self.keys.write().await;
+        self.refresh(&mut keys).await?;
+        if keys.position(public_key).is_none() {
+            return Ok(false);
         }
-        serialized_keys.push(prefix_hex::encode(public_key.to_bytes()));
-        let new_contents = serde_json::to_string(&serialized_keys)
-            .map_err(AuthorizerError::FormatError)?
-            .as_bytes()
-            .to_vec();
-
-        tokio::fs::write(&self.0, new_contents)
-            .await
-            .map_err(AuthorizerError::FileAccessError)?;
+        let mut updated = keys.keys.clone();
+        updated.retain(|(key, _)| key != public_key);
+        self.write(&mut keys, updated).await?;
+        Ok(true)
+    }
 
-        Ok(())
+    async fn rotate(
+        &self,
+        old_key: &VerifyingKey,
+        new_key: VerifyingKey,
+    ) -> Result<Option<KeyPolicy>, AuthorizerError> {
+        let mut keys = self.keys.write().await;
+        self.refresh(&mut keys).await?;
+        let Some(position) = keys.position(old_key) else {
+            return Ok(None);
+        };
+        let mut updated = keys.keys.clone();
+        let policy = updated[position].1.clone();
+        updated[position] = (new_key, policy.clone());
+        self.write(&mut keys, updated).await?;
+        Ok(Some(policy))
     }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::{
+        models::{JobKind, KeyRole},
+        prover_input::Layout,
+    };
     use ed25519_dalek::{ed25519::signature::Signer, SigningKey, VerifyingKey};
     use rand::rngs::OsRng;
-    use sha2::Digest;
     use tempfile::tempdir;
     use tokio::fs;
 
@@ -429,4 +709,179 @@ mod tests {
 
         temp_dir.close().unwrap();
     }
+
+    #[tokio::test]
+    async fn test_quota_is_read_and_kept_when_authorizing() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("authorized_keys.json");
+
+        let limited = generate_verifying_key(&generate_signing_key());
+        let contents = serde_json::json!([{
+            "key": prefix_hex::encode(limited.to_bytes()),
+            "quota": { "max_concurrent_jobs": 2, "requests_per_minute": 60 }
+        }]);
+        fs::write(&file_path, contents.to_string()).await.unwrap();
+        let authorizer = FileAuthorizer::new(file_path.clone()).await.unwrap();
+
+        let other = generate_verifying_key(&generate_signing_key());
+        authorizer.authorize(other).await.unwrap();
+
+        let quota = authorizer.policy(&limited).await.unwrap().unwrap().quota;
+        assert_eq!(quota.max_concurrent_jobs, Some(2));
+        assert_eq!(quota.requests_per_minute, Some(60));
+        assert_eq!(quota.max_queued_jobs, None);
+        assert_eq!(
+            authorizer.policy(&other).await.unwrap(),
+            Some(KeyPolicy::default())
+        );
+        assert!(authorizer.is_key_authorized(limited).await.unwrap());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_set_policy_replaces_the_policy_of_a_key() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("authorized_keys.json");
+
+        let authorizer = FileAuthorizer::new(file_path.clone()).await.unwrap();
+        let signing_key = generate_signing_key();
+        let public_key = generate_verifying_key(&signing_key);
+        let other = generate_verifying_key(&generate_signing_key());
+        authorizer.authorize(public_key).await.unwrap();
+        authorizer.authorize(other).await.unwrap();
+
+        let policy = KeyPolicy {
+            kinds: vec![JobKind::Run],
+            layouts: vec![Layout::Recursive],
+            label: Some("ci".to_string()),
+            ..Default::default()
+        };
+        authorizer
+            .set_policy(public_key, policy.clone())
+            .await
+            .unwrap();
+        // Authorizing the key again keeps its policy
+        authorizer.authorize(public_key).await.unwrap();
+
+        let hash = sha2::Sha256::digest(b"test data");
+        let (key, signed_policy) = authorizer
+            .authorized_key(None, signing_key.sign(&hash), &hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(key, public_key);
+        assert_eq!(signed_policy, policy);
+        assert_eq!(
+            authorizer.policy(&other).await.unwrap(),
+            Some(KeyPolicy::default())
+        );
+
+        let admin = KeyPolicy {
+            role: KeyRole::Admin,
+            ..Default::default()
+        };
+        authorizer
+            .set_policy(public_key, admin.clone())
+            .await
+            .unwrap();
+        assert_eq!(authorizer.policy(&public_key).await.unwrap(), Some(admin));
+        assert_eq!(authorizer.keys().await.unwrap().len(), 2);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_revoke_and_rotate_keys() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("authorized_keys.json");
+
+        let authorizer = FileAuthorizer::new(file_path.clone()).await.unwrap();
+        let old_key = generate_verifying_key(&generate_signing_key());
+        let new_key = generate_verifying_key(&generate_signing_key());
+        let other = generate_verifying_key(&generate_signing_key());
+        let policy = KeyPolicy {
+            label: Some("partner".to_string()),
+            ..Default::default()
+        };
+        authorizer
+            .set_policy(old_key, policy.clone())
+            .await
+            .unwrap();
+        authorizer.authorize(other).await.unwrap();
+
+        assert_eq!(
+            authorizer.rotate(&old_key, new_key).await.unwrap(),
+            Some(policy.clone())
+        );
+        assert!(!authorizer.is_key_authorized(old_key).await.unwrap());
+        assert_eq!(authorizer.policy(&new_key).await.unwrap(), Some(policy));
+        assert_eq!(authorizer.rotate(&old_key, old_key).await.unwrap(), None);
+
+        assert!(authorizer.revoke(&other).await.unwrap());
+        assert!(!authorizer.revoke(&other).await.unwrap());
+        let keys = authorizer.keys().await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].0, new_key);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_picks_up_changes_to_the_file() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("authorized_keys.json");
+
+        let authorizer = FileAuthorizer::new(file_path.clone()).await.unwrap();
+        let removed = generate_verifying_key(&generate_signing_key());
+        let added = generate_verifying_key(&generate_signing_key());
+        authorizer.authorize(removed).await.unwrap();
+        assert!(!authorizer.reload().await.unwrap());
+        assert!(!temp_dir.path().join("authorized_keys.json.tmp").exists());
+
+        // An edit right after the write is picked up even if the file keeps its modification time
+        let contents = serde_json::to_string(&[prefix_hex::encode(added.to_bytes())]).unwrap();
+        fs::write(&file_path, &contents).await.unwrap();
+        assert!(authorizer.reload().await.unwrap());
+        assert!(authorizer.is_key_authorized(added).await.unwrap());
+        assert!(!authorizer.is_key_authorized(removed).await.unwrap());
+        fs::write(&file_path, &contents).await.unwrap();
+        assert!(!authorizer.reload().await.unwrap());
+
+        // A broken file keeps the keys loaded before
+        fs::write(&file_path, "[\"0x").await.unwrap();
+        assert!(authorizer.reload().await.is_err());
+        assert!(authorizer.is_key_authorized(added).await.unwrap());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_only_the_named_key_is_tried() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("authorized_keys.json");
+
+        let authorizer = FileAuthorizer::new(file_path.clone()).await.unwrap();
+        let signing_key = generate_signing_key();
+        let public_key = generate_verifying_key(&signing_key);
+        let other = generate_verifying_key(&generate_signing_key());
+        authorizer.authorize(public_key).await.unwrap();
+        authorizer.authorize(other).await.unwrap();
+
+        let hash = sha2::Sha256::digest(b"test data");
+        let signature = signing_key.sign(&hash);
+        let (key, _) = authorizer
+            .authorized_key(Some(&public_key), signature, &hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(key, public_key);
+        assert!(authorizer
+            .authorized_key(Some(&other), signature, &hash)
+            .await
+            .unwrap()
+            .is_none());
+
+        temp_dir.close().unwrap();
+    }
 }
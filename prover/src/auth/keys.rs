@@ -0,0 +1,105 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use common::{
+    models::{AuthorizedKey, KeyListResponse},
+    requests::RotateKeyRequest,
+};
+use ed25519_dalek::VerifyingKey;
+
+use crate::{errors::ProverError, server::AppState};
+
+use super::{
+    auth_errors::{AuthError, AuthorizerError},
+    authorizer::AuthorizationProvider,
+    Signer,
+};
+
+fn require_admin(app_state: &AppState, signer: &Signer) -> Result<(), ProverError> {
+    if app_state.is_admin(signer) {
+        Ok(())
+    } else {
+        Err(ProverError::Auth(AuthError::Forbidden))
+    }
+}
+
+// Keys given with `--admin-keys` are authorized again on every start, removing them from the file
+// would not last
+fn require_removable(app_state: &AppState, key: &VerifyingKey) -> Result<(), ProverError> {
+    if app_state.admin_keys.contains(key) {
+        return Err(ProverError::CustomError(
+            "Keys given with --admin-keys cannot be revoked or rotated".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Decodes a key given in prefixed hex, e.g. in a path or header.
+pub fn parse_key(key: &str) -> Result<VerifyingKey, ProverError> {
+    let bytes = prefix_hex::decode::<Vec<u8>>(key)
+        .map_err(|e| AuthorizerError::PrefixHexConversionError(e.to_string()))?;
+    Ok(VerifyingKey::from_bytes(&bytes.try_into()?)?)
+}
+
+pub async fn list_keys(
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+) -> Result<impl IntoResponse, ProverError> {
+    require_admin(&app_state, &signer)?;
+    let keys = app_state
+        .authorizer
+        .keys()
+        .await?
+        .into_iter()
+        .map(|(key, policy)| AuthorizedKey { key, policy })
+        .collect();
+    Ok(Json(KeyListResponse { keys }))
+}
+
+/// Revokes the key given in hex, e.g. `DELETE /keys/0x..`. Jobs of the key are kept.
+pub async fn revoke_key(
+    Path(key): Path<String>,
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+) -> Result<impl IntoResponse, ProverError> {
+    require_admin(&app_state, &signer)?;
+    let key = parse_key(&key)?;
+    require_removable(&app_state, &key)?;
+    if !app_state.authorizer.revoke(&key).await? {
+        return Err(ProverError::CustomError("Key not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn rotate_key(
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+    Json(request): Json<RotateKeyRequest>,
+) -> Result<impl IntoResponse, ProverError> {
+    require_admin(&app_state, &signer)?;
+    require_removable(&app_state, &request.old_key)?;
+    if app_state
+        .authorizer
+        .policy(&request.new_key)
+        .await?
+        .is_some()
+    {
+        return Err(ProverError::CustomError(
+            "New key is already authorized".to_string(),
+        ));
+    }
+    let Some(policy) = app_state
+        .authorizer
+        .rotate(&request.old_key, request.new_key)
+        .await?
+    else {
+        return Err(ProverError::CustomError("Key not found".to_string()));
+    };
+    Ok(Json(AuthorizedKey {
+        key: request.new_key,
+        policy,
+    }))
+}
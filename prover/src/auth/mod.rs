@@ -1,23 +1,91 @@
 pub mod auth_errors;
 pub mod authorizer;
+pub mod keys;
+pub mod quota;
 pub mod register;
 
-use authorizer::AuthorizationProvider;
+use auth_errors::AuthError;
+use authorizer::{AuthorizationProvider, Authorizer};
 use axum::body::Body;
 use axum::extract::{Request, State};
 use axum::http::StatusCode;
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 use chrono::{DateTime, Duration, Utc};
-use ed25519_dalek::Signature;
+use common::{
+    models::{JobKind, KeyPolicy},
+    prover_input::Layout,
+    signing_message, SIGNATURE_VERSION, SIGNATURE_VERSION_HEADER,
+};
+use ed25519_dalek::{Signature, VerifyingKey};
 use http_body_util::BodyExt;
 use serde_json::{json, Value};
 use sha2::Digest;
 use sha2::Sha256;
-use tokio::time::Instant;
-use tracing::trace;
+use std::collections::HashMap;
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{trace, warn};
 
-use crate::server::AppState;
+use crate::{errors::ProverError, metrics::METRICS, server::AppState};
+
+/// The key that signed the current request and its policy, inserted by
+/// `signature_verification_middleware`. The key is `None` when the server runs with an open
+/// authorizer, which lets anyone do anything.
+#[derive(Debug, Clone, Default)]
+pub struct Signer(pub Option<VerifyingKey>, pub KeyPolicy);
+
+impl Signer {
+    /// Rejects submissions of a kind or layout that the policy of the key does not allow.
+    pub fn check_allowed(&self, kind: JobKind, layout: Option<&Layout>) -> Result<(), ProverError> {
+        if !self.1.allows_kind(kind) {
+            return Err(AuthError::NotPermitted(format!(
+                "Key may not submit {} jobs",
+                kind.as_str()
+            ))
+            .into());
+        }
+        match layout {
+            Some(layout) if !self.1.allows_layout(layout) => Err(AuthError::NotPermitted(format!(
+                "Key may not use the {} layout",
+                layout
+            ))
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How often a connection that outlives its request checks that its key is still authorized.
+const KEY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Resolves once the key is revoked or its policy expires, so that the streams opened with it can
+/// be closed. Never resolves without a key.
+pub async fn key_revoked(authorizer: Authorizer, key: Option<VerifyingKey>) {
+    let Some(key) = key else {
+        return std::future::pending().await;
+    };
+    let mut interval = tokio::time::interval(KEY_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        match authorizer.policy(&key).await {
+            Ok(Some(policy)) if !policy.is_expired(Utc::now()) => {}
+            Ok(_) => return,
+            Err(e) => warn!(
+                "Failed to check the policy of key {}: {}",
+                hex::encode(key.to_bytes()),
+                e
+            ),
+        }
+    }
+}
+
+/// How often the requests of a key signed with the deprecated version 1 are logged.
+const LEGACY_WARNING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// How long after its timestamp a signed request is accepted.
+const MAX_TIMESTAMP_AGE: Duration = Duration::seconds(30);
+/// How far ahead of the server clock the timestamp of a signed request may be.
+const MAX_CLOCK_SKEW: Duration = Duration::seconds(5);
 
 //TODO: split into smaller functions
 pub async fn signature_verification_middleware(
@@ -33,7 +101,7 @@ pub async fn signature_verification_middleware(
     };
     let signature_hex = match signature_header {
         Ok(hex) => hex,
-        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        Err(err) => return reject(StatusCode::BAD_REQUEST, err),
     };
     trace!("Received signature_hex: {}", signature_hex);
 
@@ -43,23 +111,32 @@ pub async fn signature_verification_middleware(
     };
     let nonce = match nonce_header {
         Ok(nonce) => nonce.parse::<u64>().map_err(|_| "Invalid nonce format"),
-        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        Err(err) => return reject(StatusCode::BAD_REQUEST, err),
     };
     let nonce = match nonce {
         Ok(nonce) => nonce,
-        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        Err(err) => return reject(StatusCode::BAD_REQUEST, err),
     };
 
     if !verify_nonce(&app_state, nonce).await {
-        return (StatusCode::UNAUTHORIZED, "Invalid nonce").into_response();
+        return reject(StatusCode::UNAUTHORIZED, "Invalid nonce");
     };
 
     let mut signature_bytes = [0u8; 64];
     if hex::decode_to_slice(signature_hex, &mut signature_bytes).is_err() {
-        return (StatusCode::BAD_REQUEST, "Invalid signature format").into_response();
+        return reject(StatusCode::BAD_REQUEST, "Invalid signature format");
     }
     let signature = Signature::from_bytes(&signature_bytes);
 
+    // Clients that name their key are checked against it alone, others against every key
+    let public_key = match headers.get("X-Public-Key") {
+        Some(header) => match header.to_str().ok().map(keys::parse_key) {
+            Some(Ok(key)) => Some(key),
+            _ => return reject(StatusCode::BAD_REQUEST, "Invalid public key"),
+        },
+        None => None,
+    };
+
     let timestamp_header = match headers.get("X-Timestamp") {
         Some(header) => header.to_str().map_err(|_| "Invalid header format"),
         None => Err("Missing X-Timestamp header"),
@@ -67,68 +144,144 @@ pub async fn signature_verification_middleware(
 
     let timestamp_str = match timestamp_header {
         Ok(ts) => ts,
-        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        Err(err) => return reject(StatusCode::BAD_REQUEST, err),
     };
 
     // Parse and validate timestamp
     let timestamp: DateTime<Utc> = match timestamp_str.parse() {
         Ok(ts) => ts,
-        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid timestamp format").into_response(),
+        Err(_) => return reject(StatusCode::BAD_REQUEST, "Invalid timestamp format"),
     };
 
-    let now = Utc::now();
-    if now.signed_duration_since(timestamp) > Duration::seconds(30) {
-        return (StatusCode::UNAUTHORIZED, "Timestamp too old").into_response();
+    if let Err(err) = check_timestamp(&timestamp) {
+        return reject(StatusCode::UNAUTHORIZED, err);
+    }
+
+    let version = match headers.get(SIGNATURE_VERSION_HEADER) {
+        Some(header) => match header.to_str().ok().and_then(|v| v.parse::<u32>().ok()) {
+            Some(version @ 1..=SIGNATURE_VERSION) => version,
+            _ => return reject(StatusCode::BAD_REQUEST, "Unsupported signature version"),
+        },
+        // Only servers that still accept version 1 take a request without the header for one
+        None if app_state.min_signature_version <= 1 => 1,
+        None => {
+            return reject(
+                StatusCode::BAD_REQUEST,
+                "Missing X-Signature-Version header",
+            )
+        }
+    };
+    if version < app_state.min_signature_version {
+        return reject(StatusCode::UNAUTHORIZED, "Signature version too old");
     }
     let (parts, body) = request.into_parts();
 
     let bytes = match body.collect().await {
         Ok(bytes) => bytes.to_bytes(),
-        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid body format").into_response(),
+        Err(_) => return reject(StatusCode::BAD_REQUEST, "Invalid body format"),
     };
-
-    let value: Value = match serde_json::from_slice(&bytes) {
-        Ok(v) => v,
-        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid JSON body").into_response(),
-    };
-    let signed_data = json!(
-        {
-            "data": value,
-            "timestamp": timestamp_str,
-            "nonce": nonce
-        }
-    );
     cleanup_nonces(&app_state).await;
 
-    let val_bytes = match serde_json::to_vec(&signed_data) {
-        Ok(v) => v,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to serialize JSON",
-            )
-                .into_response()
+    let data_hash = if version == 1 {
+        match legacy_data_hash(&bytes, timestamp_str, nonce) {
+            Ok(hash) => hash,
+            Err(response) => return response,
         }
+    } else {
+        let path_and_query = parts
+            .uri
+            .path_and_query()
+            .map_or(parts.uri.path(), |path| path.as_str());
+        Sha256::digest(signing_message(
+            parts.method.as_str(),
+            path_and_query,
+            &bytes,
+            timestamp_str,
+            nonce,
+        ))
+        .to_vec()
     };
-
-    let data_hash = Sha256::digest(val_bytes);
     match app_state
         .authorizer
-        .is_authorized(signature, &data_hash)
+        .authorized_key(public_key.as_ref(), signature, &data_hash)
         .await
     {
-        Ok(true) => {
+        Ok(Some((_, policy))) if policy.is_expired(Utc::now()) => {
+            reject(StatusCode::UNAUTHORIZED, "Key expired")
+        }
+        Ok(Some((key, policy))) => {
             trace!("Signature verified");
-            let request = Request::from_parts(parts, Body::from(bytes));
+            if version == 1
+                && legacy_warning_due(&app_state.legacy_signers, &key, Instant::now()).await
+            {
+                warn!(
+                    "Accepted a request of key {} signed with the deprecated signature version 1",
+                    hex::encode(key.to_bytes())
+                );
+            }
+            if let Err(e) = app_state.request_limiter.check(&key, &policy.quota).await {
+                return e.into_response();
+            }
+            let mut request = Request::from_parts(parts, Body::from(bytes));
+            request.extensions_mut().insert(Signer(Some(key), policy));
+            next.run(request).await.into_response()
+        }
+        Ok(None) if matches!(app_state.authorizer, Authorizer::Open) => {
+            let mut request = Request::from_parts(parts, Body::from(bytes));
+            request
+                .extensions_mut()
+                .insert(Signer(None, KeyPolicy::default()));
             next.run(request).await.into_response()
         }
-        Ok(false) => (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
-        Err(_) => (
+        Ok(None) => reject(StatusCode::UNAUTHORIZED, "Unauthorized"),
+        Err(_) => reject(
             StatusCode::INTERNAL_SERVER_ERROR,
             "Authorization check failed",
+        ),
+    }
+}
+
+// Version 1 signs the JSON body as `{data, timestamp, nonce}`, whatever the route
+fn legacy_data_hash(bytes: &[u8], timestamp: &str, nonce: u64) -> Result<Vec<u8>, Response> {
+    // Requests without a body (e.g. DELETE) sign `null` as their data
+    let value: Value = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(bytes)
+            .map_err(|_| reject(StatusCode::BAD_REQUEST, "Invalid JSON body"))?
+    };
+    let signed_data = json!(
+        {
+            "data": value,
+            "timestamp": timestamp,
+            "nonce": nonce
+        }
+    );
+    let val_bytes = serde_json::to_vec(&signed_data).map_err(|_| {
+        reject(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to serialize JSON",
         )
-            .into_response(),
+    })?;
+    Ok(Sha256::digest(val_bytes).to_vec())
+}
+
+// Every rejected request is counted by its reason, the messages are fixed so the label stays bounded
+fn reject(status: StatusCode, reason: &'static str) -> Response {
+    METRICS.auth_failure(reason);
+    (status, reason).into_response()
+}
+
+// Rejects timestamps outside of the window in which a signed message is accepted
+fn check_timestamp(timestamp: &DateTime<Utc>) -> Result<(), &'static str> {
+    let now = Utc::now();
+    if now.signed_duration_since(timestamp) > MAX_TIMESTAMP_AGE {
+        return Err("Timestamp too old");
+    }
+    if timestamp.signed_duration_since(now) > MAX_CLOCK_SKEW {
+        return Err("Timestamp in the future");
     }
+    Ok(())
 }
 
 async fn verify_nonce(app_state: &AppState, nonce: u64) -> bool {
@@ -136,12 +289,126 @@ async fn verify_nonce(app_state: &AppState, nonce: u64) -> bool {
     if nonces.contains_key(&nonce) {
         return false; // Nonce already used
     }
-    nonces.insert(nonce, Instant::now() + tokio::time::Duration::from_secs(30));
+    // A nonce is remembered for as long as a timestamp signed along with it may be accepted
+    let window = (MAX_TIMESTAMP_AGE + MAX_CLOCK_SKEW)
+        .to_std()
+        .unwrap_or_default();
+    nonces.insert(nonce, Instant::now() + window);
     true
 }
 
+// Whether a request of the key signed with version 1 should be logged, each key is warned about
+// at most once per `LEGACY_WARNING_INTERVAL`
+async fn legacy_warning_due(
+    legacy_signers: &Mutex<HashMap<[u8; 32], Instant>>,
+    key: &VerifyingKey,
+    now: Instant,
+) -> bool {
+    let mut warned = legacy_signers.lock().await;
+    match warned.get(key.as_bytes()) {
+        Some(&last) if now.duration_since(last) < LEGACY_WARNING_INTERVAL => false,
+        _ => {
+            warned.insert(key.to_bytes(), now);
+            true
+        }
+    }
+}
+
 async fn cleanup_nonces(app_state: &AppState) {
     let mut nonces = app_state.nonces.lock().await;
     let now = Instant::now();
     nonces.retain(|_, &mut expiry| expiry > now);
 }
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::auth::authorizer::FileAuthorizer;
+
+    #[tokio::test]
+    async fn test_legacy_signatures_are_logged_once_per_interval() {
+        let legacy_signers = Mutex::new(HashMap::new());
+        let (alice, bob) = (
+            SigningKey::from_bytes(&[5; 32]).verifying_key(),
+            SigningKey::from_bytes(&[6; 32]).verifying_key(),
+        );
+        let now = Instant::now();
+
+        assert!(legacy_warning_due(&legacy_signers, &alice, now).await);
+        assert!(
+            !legacy_warning_due(&legacy_signers, &alice, now + LEGACY_WARNING_INTERVAL / 2).await
+        );
+        assert!(legacy_warning_due(&legacy_signers, &bob, now).await);
+        assert!(legacy_warning_due(&legacy_signers, &alice, now + LEGACY_WARNING_INTERVAL).await);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_keys_are_noticed() {
+        let temp_dir = tempdir().unwrap();
+        let authorizer = Authorizer::Persistent(
+            FileAuthorizer::new(temp_dir.path().join("authorized_keys.json"))
+                .await
+                .unwrap(),
+        );
+        let key = SigningKey::from_bytes(&[4; 32]).verifying_key();
+        authorizer.authorize(key).await.unwrap();
+        let wait = std::time::Duration::from_millis(50);
+
+        assert!(
+            tokio::time::timeout(wait, key_revoked(authorizer.clone(), Some(key)))
+                .await
+                .is_err()
+        );
+        assert!(
+            tokio::time::timeout(wait, key_revoked(authorizer.clone(), None))
+                .await
+                .is_err()
+        );
+        authorizer.revoke(&key).await.unwrap();
+        assert!(
+            tokio::time::timeout(wait, key_revoked(authorizer.clone(), Some(key)))
+                .await
+                .is_ok()
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_scopes_limit_kinds_and_layouts() {
+        let key = SigningKey::from_bytes(&[3; 32]).verifying_key();
+        let signer = Signer(
+            Some(key),
+            KeyPolicy {
+                kinds: vec![JobKind::Run, JobKind::Prove],
+                layouts: vec![Layout::Recursive],
+                ..Default::default()
+            },
+        );
+        assert!(signer
+            .check_allowed(JobKind::Run, Some(&Layout::Recursive))
+            .is_ok());
+        assert!(matches!(
+            signer.check_allowed(JobKind::Prove, Some(&Layout::Starknet)),
+            Err(ProverError::Auth(AuthError::NotPermitted(_)))
+        ));
+        assert!(signer.check_allowed(JobKind::Snos, None).is_err());
+
+        let unrestricted = Signer(Some(key), KeyPolicy::default());
+        assert!(unrestricted
+            .check_allowed(JobKind::LayoutBridge, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_timestamps_outside_the_window_are_rejected() {
+        let now = Utc::now();
+        assert!(check_timestamp(&now).is_ok());
+        assert!(check_timestamp(&(now - Duration::seconds(10))).is_ok());
+        assert!(check_timestamp(&(now - Duration::seconds(60))).is_err());
+        assert!(check_timestamp(&(now + Duration::seconds(60))).is_err());
+    }
+}
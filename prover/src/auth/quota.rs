@@ -0,0 +1,268 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use axum::{extract::State, response::IntoResponse, Extension, Json};
+use chrono::{DateTime, NaiveDate, Utc};
+use common::models::{KeyQuota, QuotaResponse, QuotaUsage};
+use ed25519_dalek::VerifyingKey;
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{errors::ProverError, server::AppState};
+
+use super::Signer;
+
+/// Window over which `requests_per_minute` is counted.
+const MINUTE: Duration = Duration::from_secs(60);
+/// Suggested wait before submitting again once a limit on the jobs of a key is reached.
+const JOB_QUOTA_RETRY_AFTER: u64 = 30;
+
+/// Recent requests of the keys that have a request rate limit.
+#[derive(Clone, Default)]
+pub struct RequestLimiter {
+    requests: Arc<Mutex<HashMap<[u8; 32], VecDeque<Instant>>>>,
+}
+
+impl RequestLimiter {
+    /// Counts the request against the key, unless it already sent all of its requests for the
+    /// last minute.
+    pub async fn check(&self, key: &VerifyingKey, quota: &KeyQuota) -> Result<(), ProverError> {
+        let Some(limit) = quota.requests_per_minute else {
+            return Ok(());
+        };
+        let mut requests = self.requests.lock().await;
+        let window = requests.entry(key.to_bytes()).or_default();
+        let now = Instant::now();
+        while window
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= MINUTE)
+        {
+            window.pop_front();
+        }
+        if window.len() as u64 >= limit {
+            let retry_after = window
+                .front()
+                .map_or(MINUTE, |oldest| MINUTE - now.duration_since(*oldest));
+            return Err(ProverError::QuotaExceeded(
+                format!("{} requests per minute", limit),
+                retry_after.as_secs() + 1,
+            ));
+        }
+        window.push_back(now);
+        Ok(())
+    }
+    /// Requests the key sent within the last minute, only counted for keys with a rate limit.
+    pub async fn recent(&self, key: &VerifyingKey) -> u64 {
+        let now = Instant::now();
+        self.requests
+            .lock()
+            .await
+            .get(&key.to_bytes())
+            .map_or(0, |window| {
+                window
+                    .iter()
+                    .filter(|sent| now.duration_since(**sent) < MINUTE)
+                    .count() as u64
+            })
+    }
+}
+
+/// Runner and prover time spent on the jobs of each key during the current UTC day. It is only
+/// kept in memory, the day starts over when the server restarts.
+#[derive(Default)]
+pub struct ProvingUsage {
+    days: HashMap<[u8; 32], (NaiveDate, u64)>,
+}
+
+impl ProvingUsage {
+    pub fn record(&mut self, key: &VerifyingKey, duration_ms: u64) {
+        let today = Utc::now().date_naive();
+        let (day, used_ms) = self.days.entry(key.to_bytes()).or_insert((today, 0));
+        if *day != today {
+            *day = today;
+            *used_ms = 0;
+        }
+        *used_ms += duration_ms;
+    }
+    pub fn seconds_today(&self, key: &VerifyingKey) -> u64 {
+        match self.days.get(&key.to_bytes()) {
+            Some((day, used_ms)) if *day == Utc::now().date_naive() => used_ms / 1000,
+            _ => 0,
+        }
+    }
+}
+
+/// What a key currently uses of the limits on its jobs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyUsage {
+    pub queued_jobs: u64,
+    pub running_jobs: u64,
+    pub proving_seconds: u64,
+}
+
+/// Start of the next UTC day, when the daily proving time starts over.
+pub fn next_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.date_naive()
+        .succ_opt()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .map_or(now, |midnight| midnight.and_utc())
+}
+
+/// Rejects submitting `jobs` more jobs when they would go over a limit of the key.
+pub fn check_jobs(quota: &KeyQuota, usage: &KeyUsage, jobs: u64) -> Result<(), ProverError> {
+    if let Some(limit) = quota.max_concurrent_jobs {
+        if usage.queued_jobs + usage.running_jobs + jobs > limit {
+            return Err(ProverError::QuotaExceeded(
+                format!("{} concurrent jobs", limit),
+                JOB_QUOTA_RETRY_AFTER,
+            ));
+        }
+    }
+    if let Some(limit) = quota.max_queued_jobs {
+        if usage.queued_jobs + jobs > limit {
+            return Err(ProverError::QuotaExceeded(
+                format!("{} queued jobs", limit),
+                JOB_QUOTA_RETRY_AFTER,
+            ));
+        }
+    }
+    if let Some(limit) = quota.daily_proving_seconds {
+        if usage.proving_seconds >= limit {
+            let now = Utc::now();
+            let retry_after = (next_reset(now) - now).num_seconds().max(1) as u64;
+            return Err(ProverError::QuotaExceeded(
+                format!("{} proving seconds per day", limit),
+                retry_after,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Applies the job limits of the signer to a submission of `jobs` jobs at once, e.g. a batch that
+/// must fit as a whole. Every job is still checked again when it is created.
+pub async fn check_submission(
+    app_state: &AppState,
+    signer: &Signer,
+    jobs: u64,
+) -> Result<(), ProverError> {
+    let Some(key) = &signer.0 else {
+        return Ok(());
+    };
+    let quota = &signer.1.quota;
+    if *quota == KeyQuota::default() {
+        return Ok(());
+    }
+    let usage = app_state.job_store.usage(key).await;
+    check_jobs(quota, &usage, jobs)
+}
+
+pub async fn get_quota(
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+) -> Result<impl IntoResponse, ProverError> {
+    // Without authorized keys nothing is limited
+    let (quota, usage, requests) = match &signer.0 {
+        Some(key) => (
+            signer.1.quota,
+            app_state.job_store.usage(key).await,
+            app_state.request_limiter.recent(key).await,
+        ),
+        None => Default::default(),
+    };
+    Ok(Json(QuotaResponse {
+        concurrent_jobs: QuotaUsage::new(
+            usage.queued_jobs + usage.running_jobs,
+            quota.max_concurrent_jobs,
+        ),
+        queued_jobs: QuotaUsage::new(usage.queued_jobs, quota.max_queued_jobs),
+        requests_per_minute: QuotaUsage::new(requests, quota.requests_per_minute),
+        daily_proving_seconds: QuotaUsage::new(usage.proving_seconds, quota.daily_proving_seconds),
+        resets_at: next_reset(Utc::now()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+
+    fn key() -> VerifyingKey {
+        SigningKey::from_bytes(&[7; 32]).verifying_key()
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_the_rate_are_rejected() {
+        let limiter = RequestLimiter::default();
+        let quota = KeyQuota {
+            requests_per_minute: Some(2),
+            ..Default::default()
+        };
+        limiter.check(&key(), &quota).await.unwrap();
+        limiter.check(&key(), &quota).await.unwrap();
+        assert!(matches!(
+            limiter.check(&key(), &quota).await,
+            Err(ProverError::QuotaExceeded(_, retry_after)) if retry_after <= 60
+        ));
+        assert_eq!(limiter.recent(&key()).await, 2);
+
+        // Keys without a rate limit are never counted
+        limiter.check(&key(), &KeyQuota::default()).await.unwrap();
+        assert_eq!(limiter.recent(&key()).await, 2);
+    }
+
+    #[test]
+    fn test_job_limits_count_the_submitted_jobs() {
+        let quota = KeyQuota {
+            max_concurrent_jobs: Some(4),
+            max_queued_jobs: Some(2),
+            ..Default::default()
+        };
+        let usage = KeyUsage {
+            queued_jobs: 1,
+            running_jobs: 2,
+            proving_seconds: 0,
+        };
+        assert!(check_jobs(&quota, &usage, 1).is_ok());
+        // A batch must fit as a whole
+        assert!(check_jobs(&quota, &usage, 2).is_err());
+        let usage = KeyUsage {
+            running_jobs: 0,
+            ..usage
+        };
+        assert!(check_jobs(&quota, &usage, 2).is_err());
+        assert!(check_jobs(&KeyQuota::default(), &usage, 100).is_ok());
+    }
+
+    #[test]
+    fn test_daily_budget_resets_at_midnight() {
+        let quota = KeyQuota {
+            daily_proving_seconds: Some(60),
+            ..Default::default()
+        };
+        let mut usage = ProvingUsage::default();
+        usage.record(&key(), 59_500);
+        let spent = KeyUsage {
+            proving_seconds: usage.seconds_today(&key()),
+            ..Default::default()
+        };
+        assert!(check_jobs(&quota, &spent, 1).is_ok());
+        usage.record(&key(), 1_000);
+        let spent = KeyUsage {
+            proving_seconds: usage.seconds_today(&key()),
+            ..Default::default()
+        };
+        assert!(check_jobs(&quota, &spent, 1).is_err());
+
+        let now: DateTime<Utc> = "2024-05-01T13:45:00Z".parse().unwrap();
+        assert_eq!(
+            next_reset(now),
+            "2024-05-02T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+}
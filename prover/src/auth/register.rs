@@ -1,20 +1,53 @@
 use axum::{extract::State, response::IntoResponse, Json};
+use chrono::Utc;
 use common::requests::AddKeyRequest;
+use ed25519_dalek::VerifyingKey;
 
 use crate::{errors::ProverError, server::AppState};
 
-use super::{auth_errors::AuthError, authorizer::AuthorizationProvider};
+use super::{
+    auth_errors::AuthError, authorizer::AuthorizationProvider, check_timestamp, cleanup_nonces,
+    verify_nonce,
+};
 
+/// Authorizes a key with the policy signed by an admin. Registering a key that is already
+/// authorized replaces its policy, which is how the scopes of a key are changed. The timestamp and
+/// nonce of the request are checked like those of other signed requests, so it cannot be replayed.
 pub async fn register(
     State(state): State<AppState>,
     Json(payload): Json<AddKeyRequest>,
 ) -> Result<impl IntoResponse, ProverError> {
-    if !state.admin_keys.contains(&payload.authority) {
+    if !is_authority(&state, &payload.authority).await? {
         return Err(ProverError::Auth(AuthError::Unauthorized));
     }
+    let message = AddKeyRequest::message(
+        &payload.new_key,
+        &payload.policy,
+        &payload.timestamp,
+        payload.nonce,
+    )?;
     payload
         .authority
-        .verify_strict(payload.new_key.as_bytes(), &payload.signature)?;
-    state.authorizer.authorize(payload.new_key).await?;
+        .verify_strict(&message, &payload.signature)?;
+    cleanup_nonces(&state).await;
+    if check_timestamp(&payload.timestamp).is_err() || !verify_nonce(&state, payload.nonce).await {
+        return Err(ProverError::Auth(AuthError::Unauthorized));
+    }
+    state
+        .authorizer
+        .set_policy(payload.new_key, payload.key_policy()?)
+        .await?;
     Ok(())
 }
+
+/// Keys given with `--admin-keys` and unexpired keys with the admin role may register keys.
+async fn is_authority(state: &AppState, key: &VerifyingKey) -> Result<bool, ProverError> {
+    if state.admin_keys.contains(key) {
+        return Ok(true);
+    }
+    Ok(state
+        .authorizer
+        .policy(key)
+        .await?
+        .is_some_and(|policy| policy.is_admin() && !policy.is_expired(Utc::now())))
+}
@@ -1,13 +1,15 @@
 use anyhow::Error as AnyhowError;
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use common::models::{JobError, JobErrorCode};
 use serde_json::json;
 use std::{convert::Infallible, net::AddrParseError};
 use thiserror::Error;
 use tokio::sync::mpsc::error::SendError;
+use uuid::Uuid;
 
 use crate::auth::auth_errors::{AuthError, AuthorizerError};
 
@@ -41,6 +43,45 @@ pub enum ProverError {
     InvalidRunMode(String),
     #[error("{0}")]
     TraceGenerationError(String),
+    #[error("Job {0} has already finished")]
+    JobAlreadyFinished(Uuid),
+    #[error("Job cancelled")]
+    Cancelled,
+    #[error("Job timed out after {0} seconds")]
+    TimedOut(u64),
+    #[error("Job queue is full, retry after {0} seconds")]
+    QueueFull(u64),
+    #[error("Server is shutting down, retry after {0} seconds")]
+    ShuttingDown(u64),
+    #[error("{0}")]
+    Job(#[from] JobError),
+    #[error("Batch of {0} jobs exceeds the limit of {1}")]
+    BatchTooLarge(usize, usize),
+    #[error("Quota of {0} exceeded, retry after {1} seconds")]
+    QuotaExceeded(String, u64),
+}
+
+impl ProverError {
+    /// Error recorded on a job whose task failed with this error.
+    pub fn into_job_error(self) -> JobError {
+        match self {
+            ProverError::Job(error) => error,
+            ProverError::TimedOut(_) => JobError::new(JobErrorCode::Timeout, self.to_string()),
+            ProverError::Cancelled => JobError::new(JobErrorCode::Cancelled, self.to_string()),
+            ProverError::InvalidRunMode(_) | ProverError::TraceGenerationError(_) => {
+                JobError::new(JobErrorCode::InvalidProgram, self.to_string())
+            }
+            ProverError::StdIoError(ref e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::OutOfMemory | std::io::ErrorKind::StorageFull
+                ) =>
+            {
+                JobError::new(JobErrorCode::OutOfResources, self.to_string())
+            }
+            _ => JobError::internal(self.to_string()),
+        }
+    }
 }
 impl<T> From<SendError<T>> for ProverError {
     fn from(err: SendError<T>) -> ProverError {
@@ -66,6 +107,8 @@ impl IntoResponse for ProverError {
                 AuthError::InvalidToken => (StatusCode::BAD_REQUEST, e.to_string()),
                 AuthError::MissingAuthorizationHeader => (StatusCode::BAD_REQUEST, e.to_string()),
                 AuthError::Unauthorized => (StatusCode::UNAUTHORIZED, e.to_string()),
+                AuthError::Forbidden => (StatusCode::FORBIDDEN, e.to_string()),
+                AuthError::NotPermitted(_) => (StatusCode::FORBIDDEN, e.to_string()),
             },
             ProverError::InternalServerError(e) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
@@ -91,9 +134,29 @@ impl IntoResponse for ProverError {
             ProverError::KeyError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             ProverError::SseError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             ProverError::ParserError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            ProverError::JobAlreadyFinished(_) => (StatusCode::CONFLICT, self.to_string()),
+            ProverError::Cancelled => (StatusCode::GONE, self.to_string()),
+            ProverError::TimedOut(_) => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
+            ProverError::QueueFull(_) | ProverError::QuotaExceeded(..) => {
+                (StatusCode::TOO_MANY_REQUESTS, self.to_string())
+            }
+            ProverError::ShuttingDown(_) => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
+            ProverError::BatchTooLarge(..) => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            ProverError::Job(e) => (
+                StatusCode::from_u16(e.code.http_status())
+                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+                e.to_string(),
+            ),
         };
 
         let body = Json(json!({ "error": error_message }));
-        (status, body).into_response()
+        match self {
+            ProverError::QueueFull(retry_after)
+            | ProverError::ShuttingDown(retry_after)
+            | ProverError::QuotaExceeded(_, retry_after) => {
+                (status, [(RETRY_AFTER, retry_after.to_string())], body).into_response()
+            }
+            _ => (status, body).into_response(),
+        }
     }
 }
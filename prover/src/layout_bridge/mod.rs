@@ -1,37 +1,59 @@
+use crate::auth::Signer;
+use crate::errors::ProverError;
 use crate::server::AppState;
 use crate::threadpool::task::LayoutBridgeParams;
 use crate::threadpool::task::{Task, TaskCommon};
-use axum::Json;
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum::{Extension, Json};
+use common::models::JobKind;
 use common::prover_input::LayoutBridgeInput;
 use serde_json::json;
+use uuid::Uuid;
 
 pub async fn root(
     State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
     Json(program_input): Json<LayoutBridgeInput>,
-) -> impl IntoResponse {
-    let thread_pool = app_state.proving_thread_pool.clone();
+) -> Result<impl IntoResponse, ProverError> {
+    let job_id = submit(&app_state, signer, program_input).await?;
+    let body = json!({
+        "job_id": job_id
+    });
+    Ok((StatusCode::ACCEPTED, body.to_string()))
+}
+
+/// Creates the job and queues it, shared by the HTTP and WebSocket APIs.
+pub async fn submit(
+    app_state: &AppState,
+    signer: Signer,
+    program_input: LayoutBridgeInput,
+) -> Result<Uuid, ProverError> {
+    signer.check_allowed(JobKind::LayoutBridge, None)?;
+    // Proofs start with their trace on the running pool, which hands them to the proving pool
+    let thread_pool = app_state.running_thread_pool.clone();
+    let proving_pool = app_state.proving_thread_pool.lock().await.handle();
     let job_store = app_state.job_store.clone();
-    let job_id = job_store.create_job().await;
+    let priority = app_state.default_priority(&signer);
+    let job_id = job_store
+        .create_job(&signer, JobKind::LayoutBridge, false)
+        .await?;
     let thread = thread_pool.lock().await;
     let task_base = TaskCommon {
         job_id,
         job_store,
-        sse_tx: app_state.sse_tx.clone(),
+        timeout: app_state.timeouts.layout_bridge,
+        priority,
     };
 
     let layout_bridge_params = LayoutBridgeParams {
         common: task_base,
         proof: program_input.proof,
+        memory_budget: app_state.memory_budget.clone(),
+        proving_pool,
     };
 
-    let _ = thread
+    thread
         .execute(Task::LayoutBridge(layout_bridge_params))
-        .await
-        .into_response();
-
-    let body = json!({
-        "job_id": job_id
-    });
-    (StatusCode::ACCEPTED, body.to_string())
+        .await?;
+    Ok(job_id)
 }
@@ -1,6 +1,7 @@
 pub mod auth;
 pub mod errors;
 pub mod layout_bridge;
+pub mod metrics;
 pub mod prove;
 pub mod run;
 pub mod server;
@@ -8,9 +9,14 @@ pub mod sse;
 pub mod threadpool;
 pub mod utils;
 pub mod verifier;
+pub mod ws;
 use std::path::PathBuf;
 
-use clap::{arg, Parser};
+use clap::{arg, builder::RangedU64ValueParser, Parser};
+use common::SIGNATURE_VERSION;
+
+/// Longest job TTL in seconds, a hundred years, well within what a `chrono::Duration` can hold.
+pub const MAX_JOB_TTL: u64 = 100 * 365 * 24 * 3600;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -21,6 +27,10 @@ pub struct Args {
     pub port: u16,
     #[arg(long, env, default_value = "authorized_keys.json")]
     pub authorized_keys_path: PathBuf,
+    #[arg(long, env, default_value = "5")]
+    pub authorized_keys_reload_interval: u64,
+    #[arg(long, env, default_value = "2", value_parser = clap::value_parser!(u32).range(1..=SIGNATURE_VERSION as i64))]
+    pub min_signature_version: u32,
     #[arg(long, env, value_delimiter = ',')]
     pub authorized_keys: Vec<String>,
     #[arg(long, env, default_value = "4")]
@@ -29,4 +39,42 @@ pub struct Args {
     pub run_workers: usize,
     #[arg(long, env, value_delimiter = ',')]
     pub admin_keys: Vec<String>,
+    #[arg(long, env)]
+    pub job_store_path: Option<PathBuf>,
+    #[arg(long, env, default_value = "3600", value_parser = clap::value_parser!(u64).range(..=MAX_JOB_TTL))]
+    pub completed_job_ttl: u64,
+    #[arg(long, env, default_value = "86400", value_parser = clap::value_parser!(u64).range(..=MAX_JOB_TTL))]
+    pub failed_job_ttl: u64,
+    #[arg(long, env)]
+    pub max_result_bytes: Option<usize>,
+    #[arg(long, env, default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    pub retention_sweep_interval: u64,
+    #[arg(long, env, default_value = "3600")]
+    pub run_timeout: u64,
+    #[arg(long, env, default_value = "7200")]
+    pub prove_timeout: u64,
+    #[arg(long, env, default_value = "7200")]
+    pub layout_bridge_timeout: u64,
+    #[arg(long, env, default_value = "3600")]
+    pub snos_timeout: u64,
+    #[arg(long, env, default_value = "60")]
+    pub queue_aging_interval: u64,
+    #[arg(long, env, default_value = "100", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_queue_depth: usize,
+    #[arg(long, env, default_value = "30")]
+    pub queue_retry_after: u64,
+    #[arg(long, env, value_parser = clap::value_parser!(u32).range(1..))]
+    pub prove_memory_budget_mib: Option<u32>,
+    #[arg(long, env, default_value = "3")]
+    pub max_attempts: u32,
+    #[arg(long, env, default_value = "10")]
+    pub retry_initial_backoff: u64,
+    #[arg(long, env, default_value = "300")]
+    pub retry_max_backoff: u64,
+    #[arg(long, env)]
+    pub result_cache_path: Option<PathBuf>,
+    #[arg(long, env, default_value = "1000")]
+    pub result_cache_max_entries: usize,
+    #[arg(long, env, default_value = "100")]
+    pub max_batch_size: usize,
 }
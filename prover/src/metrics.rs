@@ -0,0 +1,177 @@
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+};
+use common::models::{JobStatus, StageMetrics};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::{errors::ProverError, server::AppState};
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+/// Collectors exported on `/metrics`. Values that can be read from the server state at any time,
+/// like queue depths, are refreshed when the endpoint is scraped instead of tracked on every change.
+pub struct Metrics {
+    registry: Registry,
+    pub queue_depth: IntGaugeVec,
+    pub active_workers: IntGaugeVec,
+    pub jobs_finished: IntCounterVec,
+    pub jobs_retried: IntCounterVec,
+    pub stage_duration: HistogramVec,
+    pub auth_failures: IntCounterVec,
+    pub nonce_cache_size: IntGauge,
+    pub sse_subscribers: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("prover".to_string()), None)
+            .expect("metrics prefix is valid");
+        let metrics = Self {
+            queue_depth: IntGaugeVec::new(
+                Opts::new("queue_depth", "Tasks waiting for a worker, per pool"),
+                &["pool"],
+            )
+            .unwrap(),
+            active_workers: IntGaugeVec::new(
+                Opts::new(
+                    "active_workers",
+                    "Workers currently executing a task, per pool",
+                ),
+                &["pool"],
+            )
+            .unwrap(),
+            jobs_finished: IntCounterVec::new(
+                Opts::new(
+                    "jobs_finished_total",
+                    "Jobs that reached a terminal status, by kind and status",
+                ),
+                &["kind", "status"],
+            )
+            .unwrap(),
+            jobs_retried: IntCounterVec::new(
+                Opts::new(
+                    "jobs_retried_total",
+                    "Failed job attempts that were scheduled again, by kind",
+                ),
+                &["kind"],
+            )
+            .unwrap(),
+            stage_duration: HistogramVec::new(
+                HistogramOpts::new("stage_duration_seconds", "Duration of each job stage").buckets(
+                    vec![
+                        0.1, 0.5, 1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+                    ],
+                ),
+                &["stage"],
+            )
+            .unwrap(),
+            auth_failures: IntCounterVec::new(
+                Opts::new(
+                    "auth_failures_total",
+                    "Requests rejected by the signature check, by reason",
+                ),
+                &["reason"],
+            )
+            .unwrap(),
+            nonce_cache_size: IntGauge::new(
+                "nonce_cache_size",
+                "Nonces remembered for replay protection",
+            )
+            .unwrap(),
+            sse_subscribers: IntGauge::new("sse_subscribers", "Open SSE connections").unwrap(),
+            registry,
+        };
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: [Box<dyn prometheus::core::Collector>; 8] = [
+            Box::new(self.queue_depth.clone()),
+            Box::new(self.active_workers.clone()),
+            Box::new(self.jobs_finished.clone()),
+            Box::new(self.jobs_retried.clone()),
+            Box::new(self.stage_duration.clone()),
+            Box::new(self.auth_failures.clone()),
+            Box::new(self.nonce_cache_size.clone()),
+            Box::new(self.sse_subscribers.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("metrics are registered once");
+        }
+    }
+
+    pub fn job_finished(&self, kind: &str, status: &JobStatus) {
+        self.jobs_finished
+            .with_label_values(&[kind, format!("{:?}", status).as_str()])
+            .inc();
+    }
+
+    pub fn job_retried(&self, kind: &str) {
+        self.jobs_retried.with_label_values(&[kind]).inc();
+    }
+
+    pub fn observe_stage(&self, stage: &StageMetrics) {
+        self.stage_duration
+            .with_label_values(&[stage.stage.as_str()])
+            .observe(stage.duration_ms as f64 / 1000.0);
+    }
+
+    pub fn auth_failure(&self, reason: &str) {
+        self.auth_failures.with_label_values(&[reason]).inc();
+    }
+
+    /// Counts an SSE connection until the returned guard is dropped.
+    pub fn sse_subscriber(&self) -> SseSubscriber {
+        self.sse_subscribers.inc();
+        SseSubscriber
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, ProverError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| ProverError::InternalServerError(e.to_string()))?;
+        Ok(buffer)
+    }
+}
+
+pub struct SseSubscriber;
+
+impl Drop for SseSubscriber {
+    fn drop(&mut self) {
+        METRICS.sse_subscribers.dec();
+    }
+}
+
+pub async fn metrics_handler(
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ProverError> {
+    for (pool, thread_pool) in [
+        ("run", &app_state.running_thread_pool),
+        ("prove", &app_state.proving_thread_pool),
+    ] {
+        let depth = thread_pool.lock().await.queue_depth().await;
+        METRICS
+            .queue_depth
+            .with_label_values(&[pool])
+            .set(depth as i64);
+    }
+    METRICS
+        .nonce_cache_size
+        .set(app_state.nonces.lock().await.len() as i64);
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, TextEncoder::new().format_type().to_string())],
+        METRICS.encode()?,
+    ))
+}
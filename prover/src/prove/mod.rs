@@ -1,12 +1,133 @@
-use axum::{routing::post, Router};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::post, Router};
+use axum::{Extension, Json};
+use common::models::JobKind;
+use common::prover_input::{BatchRequest, Priority};
+use uuid::Uuid;
 
+use crate::auth::Signer;
+use crate::errors::ProverError;
 use crate::server::AppState;
-mod cairo;
-mod cairo0;
+use crate::threadpool::{
+    task::{Task, TaskCommon, TraceParams},
+    CairoVersionedInput,
+};
+use crate::utils::{batch::PendingBatch, cache::submit_cached, timeout::TaskTimeouts};
+pub mod cairo;
+pub mod cairo0;
 
 pub fn router(app_state: AppState) -> Router {
     Router::new()
         .route("/cairo0", post(cairo0::root))
         .route("/cairo", post(cairo::root))
+        .route("/batch", post(batch))
         .with_state(app_state)
 }
+
+/// Submits a proof of either Cairo version. A request that was proven before is answered from the
+/// result cache, and an identical request still being proven for the same key shares its job.
+pub async fn submit_proof(
+    app_state: &AppState,
+    signer: Signer,
+    input: CairoVersionedInput,
+    priority: Option<Priority>,
+    timeout: Option<u64>,
+) -> Result<Uuid, ProverError> {
+    signer.check_allowed(JobKind::Prove, Some(input.layout()))?;
+    submit_cached(app_state, &signer, &input, || {
+        queue_proof(app_state, &signer, input.clone(), priority, timeout)
+    })
+    .await
+}
+
+async fn queue_proof(
+    app_state: &AppState,
+    signer: &Signer,
+    input: CairoVersionedInput,
+    priority: Option<Priority>,
+    timeout: Option<u64>,
+) -> Result<Uuid, ProverError> {
+    let job_id = app_state
+        .job_store
+        .create_job(signer, JobKind::Prove, input.public_result())
+        .await?;
+    let task = proof_task(app_state, signer, job_id, input, priority, timeout).await;
+    let thread = app_state.running_thread_pool.lock().await;
+    thread.execute(task).await?;
+    Ok(job_id)
+}
+
+// Same as `queue_proof`, except the task is left to the batch to queue
+async fn batch_proof(
+    app_state: &AppState,
+    signer: &Signer,
+    input: CairoVersionedInput,
+    priority: Option<Priority>,
+    timeout: Option<u64>,
+    tasks: &mut Vec<Task>,
+) -> Result<Uuid, ProverError> {
+    let job_id = app_state
+        .job_store
+        .create_job(signer, JobKind::Prove, input.public_result())
+        .await?;
+    tasks.push(proof_task(app_state, signer, job_id, input, priority, timeout).await);
+    Ok(job_id)
+}
+
+async fn proof_task(
+    app_state: &AppState,
+    signer: &Signer,
+    job_id: Uuid,
+    input: CairoVersionedInput,
+    priority: Option<Priority>,
+    timeout: Option<u64>,
+) -> Task {
+    // Proofs start with their trace on the running pool, which hands them to the proving pool
+    let proving_pool = app_state.proving_thread_pool.lock().await.handle();
+    let task_base = TaskCommon {
+        job_id,
+        job_store: app_state.job_store.clone(),
+        timeout: TaskTimeouts::capped(app_state.timeouts.prove, timeout),
+        priority: app_state.priority(signer, priority),
+    };
+    Task::Trace(TraceParams {
+        common: task_base,
+        program_input: input,
+        memory_budget: app_state.memory_budget.clone(),
+        proving_pool,
+    })
+}
+
+/// Proves every program of the batch under the one signature of the request. Programs go through
+/// the result cache like single submissions.
+pub async fn batch(
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+    Json(request): Json<BatchRequest>,
+) -> Result<impl IntoResponse, ProverError> {
+    let mut pending = PendingBatch::new(&app_state, &signer, JobKind::Prove, &request.jobs).await?;
+    for input in request.jobs {
+        let (priority, timeout) = (input.priority(), input.timeout());
+        let input = CairoVersionedInput::from(input);
+        let submitted = submit_cached(&app_state, &signer, &input, || {
+            batch_proof(
+                &app_state,
+                &signer,
+                input.clone(),
+                priority,
+                timeout,
+                &mut pending.tasks,
+            )
+        })
+        .await;
+        match submitted {
+            Ok(job_id) => pending.job_ids.push(job_id),
+            Err(e) => {
+                pending.abandon().await;
+                return Err(e);
+            }
+        }
+    }
+    let pool = app_state.running_thread_pool.lock().await.handle();
+    let response = pending.submit(&app_state, &signer, pool).await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
@@ -1,36 +1,33 @@
+use crate::auth::Signer;
+use crate::errors::ProverError;
 use crate::server::AppState;
-use crate::threadpool::{
-    task::{RunParams, Task, TaskCommon},
-    CairoVersionedInput,
-};
-use axum::Json;
+use crate::threadpool::CairoVersionedInput;
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum::{Extension, Json};
 use common::prover_input::CairoProverInput;
 use serde_json::json;
+use uuid::Uuid;
 
 pub async fn root(
     State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
     Json(program_input): Json<CairoProverInput>,
-) -> impl IntoResponse {
-    let thread_pool = app_state.running_thread_pool.clone();
-    let job_store = app_state.job_store.clone();
-    let job_id = job_store.create_job().await;
-    let thread = thread_pool.lock().await;
-    let common = TaskCommon {
-        job_id,
-        job_store,
-        sse_tx: app_state.sse_tx.clone(),
-    };
-    let execution_params = RunParams {
-        common,
-        program_input: CairoVersionedInput::Cairo(program_input.clone()),
-    };
-    let _ = thread
-        .execute(Task::Run(execution_params))
-        .await
-        .into_response();
+) -> Result<impl IntoResponse, ProverError> {
+    let job_id = submit(&app_state, signer, program_input).await?;
     let body = json!({
         "job_id": job_id
     });
-    (StatusCode::ACCEPTED, body.to_string())
+    Ok((StatusCode::ACCEPTED, body.to_string()))
+}
+
+/// Creates the job and queues it, shared by the HTTP and WebSocket APIs.
+pub async fn submit(
+    app_state: &AppState,
+    signer: Signer,
+    program_input: CairoProverInput,
+) -> Result<Uuid, ProverError> {
+    let priority = program_input.priority;
+    let timeout = program_input.timeout;
+    let input = CairoVersionedInput::Cairo(program_input);
+    super::submit_run(app_state, &signer, input, priority, timeout).await
 }
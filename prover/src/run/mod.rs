@@ -1,14 +1,95 @@
-use axum::{routing::post, Router};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::post, Router};
+use axum::{Extension, Json};
+use common::models::JobKind;
+use common::prover_input::{BatchRequest, Priority};
+use uuid::Uuid;
 
+use crate::auth::Signer;
+use crate::errors::ProverError;
 use crate::server::AppState;
-mod cairo;
-mod cairo0;
-mod snos;
+use crate::threadpool::{
+    task::{RunParams, Task, TaskCommon},
+    CairoVersionedInput,
+};
+use crate::utils::{batch::PendingBatch, timeout::TaskTimeouts};
+pub mod cairo;
+pub mod cairo0;
+pub mod snos;
 
 pub fn router(app_state: AppState) -> Router {
     Router::new()
         .route("/cairo0", post(cairo0::root))
         .route("/cairo", post(cairo::root))
         .route("/snos", post(snos::root))
+        .route("/batch", post(batch))
         .with_state(app_state)
 }
+
+/// Creates a run job of either Cairo version and queues it.
+pub async fn submit_run(
+    app_state: &AppState,
+    signer: &Signer,
+    input: CairoVersionedInput,
+    priority: Option<Priority>,
+    timeout: Option<u64>,
+) -> Result<Uuid, ProverError> {
+    signer.check_allowed(JobKind::Run, Some(input.layout()))?;
+    let job_id = app_state
+        .job_store
+        .create_job(signer, JobKind::Run, input.public_result())
+        .await?;
+    let task = run_task(app_state, signer, job_id, input, priority, timeout);
+    let thread = app_state.running_thread_pool.lock().await;
+    thread.execute(task).await?;
+    Ok(job_id)
+}
+
+fn run_task(
+    app_state: &AppState,
+    signer: &Signer,
+    job_id: Uuid,
+    input: CairoVersionedInput,
+    priority: Option<Priority>,
+    timeout: Option<u64>,
+) -> Task {
+    let common = TaskCommon {
+        job_id,
+        job_store: app_state.job_store.clone(),
+        timeout: TaskTimeouts::capped(app_state.timeouts.run, timeout),
+        priority: app_state.priority(signer, priority),
+    };
+    Task::Run(RunParams {
+        common,
+        program_input: input,
+    })
+}
+
+/// Runs every program of the batch under the one signature of the request.
+pub async fn batch(
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+    Json(request): Json<BatchRequest>,
+) -> Result<impl IntoResponse, ProverError> {
+    let mut pending = PendingBatch::new(&app_state, &signer, JobKind::Run, &request.jobs).await?;
+    for input in request.jobs {
+        let (priority, timeout) = (input.priority(), input.timeout());
+        let input = CairoVersionedInput::from(input);
+        let created = app_state
+            .job_store
+            .create_job(&signer, JobKind::Run, input.public_result())
+            .await;
+        let job_id = match created {
+            Ok(job_id) => job_id,
+            Err(e) => {
+                pending.abandon().await;
+                return Err(e);
+            }
+        };
+        let task = run_task(&app_state, &signer, job_id, input, priority, timeout);
+        pending.job_ids.push(job_id);
+        pending.tasks.push(task);
+    }
+    let pool = app_state.running_thread_pool.lock().await.handle();
+    let response = pending.submit(&app_state, &signer, pool).await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
@@ -1,34 +1,49 @@
+use crate::auth::Signer;
+use crate::errors::ProverError;
 use crate::server::AppState;
 use crate::threadpool::task::SnosParams;
 use crate::threadpool::task::{Task, TaskCommon};
-use axum::Json;
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum::{Extension, Json};
+use common::models::JobKind;
 use common::snos_input::SnosPieInput;
 use serde_json::json;
+use uuid::Uuid;
 
 pub async fn root(
     State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
     Json(program_input): Json<SnosPieInput>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, ProverError> {
+    let job_id = submit(&app_state, signer, program_input).await?;
+    let body = json!({
+        "job_id": job_id
+    });
+    Ok((StatusCode::ACCEPTED, body.to_string()))
+}
+
+/// Creates the job and queues it, shared by the HTTP and WebSocket APIs.
+pub async fn submit(
+    app_state: &AppState,
+    signer: Signer,
+    program_input: SnosPieInput,
+) -> Result<Uuid, ProverError> {
+    signer.check_allowed(JobKind::Snos, None)?;
     let thread_pool = app_state.running_thread_pool.clone();
     let job_store = app_state.job_store.clone();
-    let job_id = job_store.create_job().await;
+    let priority = app_state.default_priority(&signer);
+    let job_id = job_store.create_job(&signer, JobKind::Snos, false).await?;
     let thread = thread_pool.lock().await;
     let common = TaskCommon {
         job_id,
         job_store,
-        sse_tx: app_state.sse_tx.clone(),
+        timeout: app_state.timeouts.snos,
+        priority,
     };
     let snos_params = SnosParams {
         common,
         input: program_input.clone(),
     };
-    let _ = thread
-        .execute(Task::Snos(snos_params))
-        .await
-        .into_response();
-    let body = json!({
-        "job_id": job_id
-    });
-    (StatusCode::ACCEPTED, body.to_string())
+    thread.execute(Task::Snos(snos_params)).await?;
+    Ok(job_id)
 }
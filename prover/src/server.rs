@@ -1,29 +1,40 @@
 use crate::auth::auth_errors::AuthorizerError;
 use crate::auth::authorizer::{AuthorizationProvider, Authorizer, FileAuthorizer};
+use crate::auth::keys::{list_keys, revoke_key, rotate_key};
+use crate::auth::quota::{get_quota, RequestLimiter};
 use crate::auth::register::register;
-use crate::auth::signature_verification_middleware;
+use crate::auth::{signature_verification_middleware, Signer};
 use crate::errors::ProverError;
 use crate::layout_bridge::root;
+use crate::metrics::metrics_handler;
 use crate::sse::sse_handler;
-use crate::threadpool::ThreadPool;
-use crate::utils::job::{get_job, JobStore};
+use crate::threadpool::{
+    admission::MemoryBudget, queue::QueueConfig, retry::RetryPolicy, ThreadPool,
+};
+use crate::utils::batch::{get_batch, BatchStore};
+use crate::utils::cache::{CacheConfig, ResultCache};
+use crate::utils::job::{cancel_job, get_job, get_job_logs, get_public_job, list_jobs, JobStore};
+use crate::utils::retention::{spawn_sweeper, RetentionPolicy};
 use crate::utils::shutdown::shutdown_signal;
+use crate::utils::storage::{FileJobStorage, JobBackend};
+use crate::utils::timeout::TaskTimeouts;
 use crate::verifier::verify_proof;
+use crate::ws::ws_handler;
 use crate::{prove, run, Args};
 use axum::extract::DefaultBodyLimit;
 use axum::middleware::{self};
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     serve, Router,
 };
+use common::prover_input::Priority;
 use core::net::SocketAddr;
 use ed25519_dalek::VerifyingKey;
 use std::collections::HashMap;
-use tokio::time::Instant;
+use tokio::time::{Duration, Instant};
 
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tokio::sync::broadcast::{self, Sender};
 use tokio::sync::Mutex;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -34,9 +45,40 @@ pub struct AppState {
     pub proving_thread_pool: Arc<Mutex<ThreadPool>>,
     pub running_thread_pool: Arc<Mutex<ThreadPool>>,
     pub nonces: Arc<Mutex<HashMap<u64, Instant>>>,
+    /// When each key was last warned about for signing with version 1.
+    pub legacy_signers: Arc<Mutex<HashMap<[u8; 32], Instant>>>,
     pub authorizer: Authorizer,
     pub admin_keys: Vec<VerifyingKey>,
-    pub sse_tx: Arc<Mutex<Sender<String>>>,
+    pub timeouts: TaskTimeouts,
+    pub memory_budget: MemoryBudget,
+    pub result_cache: ResultCache,
+    pub batches: BatchStore,
+    pub max_batch_size: usize,
+    pub request_limiter: RequestLimiter,
+    pub min_signature_version: u32,
+}
+
+impl AppState {
+    /// Keys given with `--admin-keys` are admins whatever role the authorized keys file gives them.
+    pub fn is_admin(&self, signer: &Signer) -> bool {
+        signer
+            .0
+            .as_ref()
+            .is_some_and(|key| signer.1.is_admin() || self.admin_keys.contains(key))
+    }
+    /// Priority of submissions that don't request one, admins are scheduled ahead of other keys.
+    pub fn default_priority(&self, signer: &Signer) -> Priority {
+        if self.is_admin(signer) {
+            Priority::High
+        } else {
+            Priority::Normal
+        }
+    }
+    /// Priority of a submission, a requested priority cannot go above the default of its key.
+    pub fn priority(&self, signer: &Signer, requested: Option<Priority>) -> Priority {
+        let default = self.default_priority(signer);
+        requested.map_or(default, |requested| requested.min(default))
+    }
 }
 
 pub async fn start(args: Args) -> Result<(), ProverError> {
@@ -48,8 +90,22 @@ pub async fn start(args: Args) -> Result<(), ProverError> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let authorizer =
-        Authorizer::Persistent(FileAuthorizer::new(args.authorized_keys_path.clone()).await?);
+    // A batch is queued as a whole, so one larger than the queue could never be accepted
+    if args.max_batch_size > args.max_queue_depth {
+        return Err(ProverError::CustomError(format!(
+            "--max-batch-size {} exceeds --max-queue-depth {}",
+            args.max_batch_size, args.max_queue_depth
+        )));
+    }
+    let retention_policy = RetentionPolicy::from(&args);
+    let timeouts = TaskTimeouts::from(&args);
+    let queue_config = QueueConfig::from(&args);
+    let retry_policy = RetryPolicy::from(&args);
+    let file_authorizer = FileAuthorizer::new(args.authorized_keys_path.clone()).await?;
+    if args.authorized_keys_reload_interval > 0 {
+        file_authorizer.watch(Duration::from_secs(args.authorized_keys_reload_interval));
+    }
+    let authorizer = Authorizer::Persistent(file_authorizer);
     let mut admin_keys = Vec::new();
     for key in args.admin_keys {
         let verifying_key_bytes = prefix_hex::decode::<Vec<u8>>(key)
@@ -65,30 +121,67 @@ pub async fn start(args: Args) -> Result<(), ProverError> {
         let verifying_key = VerifyingKey::from_bytes(&verifying_key_bytes.try_into()?)?;
         authorizer.authorize(verifying_key).await?;
     }
-    let (sse_tx, _) = broadcast::channel(200);
+    let job_backend = match args.job_store_path.clone() {
+        Some(path) => JobBackend::Persistent(FileJobStorage::new(path).await?),
+        None => JobBackend::Memory,
+    };
     let app_state = AppState {
         authorizer,
-        job_store: JobStore::default(),
-        proving_thread_pool: Arc::new(Mutex::new(ThreadPool::new(args.prove_workers))),
-        running_thread_pool: Arc::new(Mutex::new(ThreadPool::new(args.run_workers))),
+        job_store: JobStore::new(job_backend).await?,
+        proving_thread_pool: Arc::new(Mutex::new(ThreadPool::new(
+            "prove",
+            args.prove_workers,
+            queue_config,
+            retry_policy,
+        ))),
+        running_thread_pool: Arc::new(Mutex::new(ThreadPool::new(
+            "run",
+            args.run_workers,
+            queue_config,
+            retry_policy,
+        ))),
         nonces: Arc::new(Mutex::new(HashMap::new())),
+        legacy_signers: Arc::new(Mutex::new(HashMap::new())),
         admin_keys,
-        sse_tx: Arc::new(Mutex::new(sse_tx)),
+        timeouts,
+        memory_budget: MemoryBudget::new(args.prove_memory_budget_mib),
+        result_cache: ResultCache::new(CacheConfig::from(&args)).await?,
+        batches: BatchStore::default(),
+        max_batch_size: args.max_batch_size,
+        request_limiter: RequestLimiter::default(),
+        min_signature_version: args.min_signature_version,
     };
 
+    spawn_sweeper(
+        app_state.job_store.clone(),
+        app_state.batches.clone(),
+        retention_policy,
+    );
+
     async fn ok_handler() -> &'static str {
         "OK"
     }
     let open_routes = Router::new()
         .route("/", get(ok_handler))
         .route("/verify", post(verify_proof))
-        .route("/get-job/:id", get(get_job))
-        .route("/sse", get(sse_handler))
+        .route("/public/job/:id", get(get_public_job))
+        .route("/metrics", get(metrics_handler))
         .route("/register", post(register))
         .with_state(app_state.clone());
 
     let auth_routes = Router::new()
         .route("/layout-bridge", post(root))
+        .route("/get-job/:id", get(get_job))
+        .route("/sse", get(sse_handler))
+        .route("/jobs", get(list_jobs))
+        .route("/quota", get(get_quota))
+        .route("/keys", get(list_keys))
+        .route("/keys/:key", delete(revoke_key))
+        .route("/keys/rotate", post(rotate_key))
+        .route("/job/:id", delete(cancel_job))
+        .route("/job/:id/logs", get(get_job_logs))
+        .route("/batch/:id", get(get_batch))
+        .route("/ws", get(ws_handler))
         .with_state(app_state.clone())
         .nest("/prove", prove::router(app_state.clone()))
         .nest("/run", run::router(app_state.clone()))
@@ -1,56 +1,215 @@
-use crate::server::AppState;
+use crate::{
+    auth::{auth_errors::AuthError, key_revoked, Signer},
+    errors::ProverError,
+    metrics::METRICS,
+    server::AppState,
+    utils::{
+        events::{terminal_event, PublishedEvent},
+        job::{may_read, JobStore},
+    },
+};
 use async_stream::stream;
 use axum::{
-    extract::{Query, State},
-    response::{sse::KeepAlive, Sse},
+    extract::State,
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive},
+        Sse,
+    },
+    Extension,
+};
+use axum_extra::extract::Query;
+use common::models::{JobError, JobEvent, JobStatus};
+use futures::{
+    stream::{select_all, BoxStream},
+    Stream, StreamExt,
 };
-use common::models::JobStatus;
-use futures::Stream;
 use serde::{Deserialize, Serialize};
-use std::convert::Infallible;
-use tracing::info;
+use std::{convert::Infallible, pin::pin};
+use tokio::{
+    select,
+    sync::broadcast::{error::RecvError, Receiver},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
 
+/// Most jobs a single stream follows, each of them holds a subscription for the connection.
+const MAX_STREAM_JOBS: usize = 100;
+
+/// Jobs to follow, repeat the parameter to follow several: `/sse?job_id=1&job_id=2`. At least one
+/// and at most [`MAX_STREAM_JOBS`] jobs.
 #[derive(Deserialize, Serialize)]
 pub struct JobParams {
-    job_id: u64,
+    #[serde(default)]
+    job_id: Vec<Uuid>,
+}
+
+fn sse_event(event: &JobEvent, id: Option<u64>) -> Event {
+    let sse_event = Event::default()
+        .event(event.name())
+        .json_data(event)
+        .unwrap_or_default();
+    match id {
+        Some(id) => sse_event.id(id.to_string()),
+        None => sse_event,
+    }
 }
+
+/// Streams the events of the requested jobs until all of them finish. Recent events are replayed
+/// first, a client that reconnects with `Last-Event-ID` only gets the ones it has not seen yet.
+/// The request is rejected if the signer may not read one of the jobs, and the stream ends early
+/// once its key is revoked or expires.
 pub async fn sse_handler(
     State(state): State<AppState>,
+    Extension(signer): Extension<Signer>,
     Query(params): Query<JobParams>,
-) -> Sse<impl Stream<Item = Result<axum::response::sse::Event, Infallible>>> {
-    info!("SSE handler connected");
-    let mut rx = state.sse_tx.lock().await.subscribe();
-    let job_id = params.job_id;
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ProverError> {
+    info!("SSE handler connected for jobs {:?}", params.job_id);
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
 
-    let job_status = state
-        .job_store
-        .get_job(job_id)
-        .await
-        .map_or(JobStatus::Unknown, |j| j.status);
+    let mut job_ids = params.job_id;
+    job_ids.sort_unstable();
+    job_ids.dedup();
+    if job_ids.is_empty() {
+        return Err(ProverError::CustomError(
+            "At least one job_id is required".to_string(),
+        ));
+    }
+    if job_ids.len() > MAX_STREAM_JOBS {
+        return Err(ProverError::CustomError(format!(
+            "At most {MAX_STREAM_JOBS} jobs can be followed by a stream"
+        )));
+    }
+    for job_id in job_ids.iter() {
+        check_followable(&state, &signer, *job_id).await?;
+    }
+    let mut replay = Vec::new();
+    let mut live = Vec::new();
+    for job_id in job_ids {
+        let (events, job_live) = follow_job(&state.job_store, job_id, last_event_id).await;
+        replay.extend(events);
+        live.extend(job_live);
+    }
+    // Ids grow across jobs, so replaying in id order keeps `Last-Event-ID` meaningful
+    replay.sort_by_key(|(id, _)| id.unwrap_or(u64::MAX));
 
+    let subscriber = METRICS.sse_subscriber();
+    let revoked = key_revoked(state.authorizer.clone(), signer.0);
     let stream = stream! {
-        if matches!(job_status.clone(), JobStatus::Completed | JobStatus::Failed) {
-            yield Ok(axum::response::sse::Event::default().data(serde_json::to_string(&(job_status, job_id)).unwrap()));
-            return;
+        // Moved into the stream so the connection is counted until the client goes away
+        let _subscriber = subscriber;
+        for (id, event) in replay.iter() {
+            yield Ok(sse_event(event, *id));
+        }
+        let mut live = select_all(live);
+        let mut revoked = pin!(revoked);
+        loop {
+            let next = select! {
+                next = live.next() => next,
+                () = &mut revoked => {
+                    info!("Closing SSE stream, its key was revoked or expired");
+                    None
+                }
+            };
+            let Some((id, event)) = next else {
+                break;
+            };
+            yield Ok(sse_event(&event, id));
         }
-        while let Ok(message) = rx.recv().await {
-            match serde_json::from_str::<(JobStatus, u64)>(&message) {
-                Ok((status, received_job_id)) => {
-                    if job_id == received_job_id {
-                        info!("Sending message: {}", message);
-                        yield Ok(axum::response::sse::Event::default().data(message));
-                        // If the job is completed or failed, break the loop to stop sending events
-                        if matches!(status, JobStatus::Completed | JobStatus::Failed) {
-                            info!("Job {} completed or failed, stopping SSE.", received_job_id);
+    };
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Rejects following a job the signer may not read. Unknown jobs are let through, following them
+/// reports that they were not found.
+pub async fn check_followable(
+    state: &AppState,
+    signer: &Signer,
+    job_id: Uuid,
+) -> Result<(), ProverError> {
+    match state.job_store.get_job(job_id).await {
+        Some(job) if !may_read(state, signer, &job) => Err(ProverError::Auth(AuthError::Forbidden)),
+        _ => Ok(()),
+    }
+}
+
+/// An event and its id, events rebuilt from the state of a job have no id.
+pub type JobEventItem = (Option<u64>, JobEvent);
+
+/// Remembered events of the job newer than `last_event_id`, and the stream of its live events if
+/// it has not finished yet. Shared by the SSE and WebSocket APIs.
+pub async fn follow_job(
+    job_store: &JobStore,
+    job_id: Uuid,
+    last_event_id: Option<u64>,
+) -> (Vec<JobEventItem>, Option<BoxStream<'static, JobEventItem>>) {
+    let Some((job, events, rx)) = job_store.subscribe(job_id, last_event_id).await else {
+        let event = JobEvent::Failed {
+            job_id,
+            status: JobStatus::Unknown,
+            error: Some(JobError::internal("Job not found")),
+        };
+        return (vec![(None, event)], None);
+    };
+    let replayed_terminal = events.iter().any(|published| published.event.is_terminal());
+    let mut replay: Vec<JobEventItem> = events
+        .into_iter()
+        .map(|published| (Some(published.id), published.event))
+        .collect();
+    if replayed_terminal {
+        return (replay, None);
+    }
+    if job.status.is_terminal() {
+        // The terminal event is no longer remembered, e.g. after a restart, so it is rebuilt
+        // from the job. A client that already saw it gets it again rather than hang.
+        replay.extend(terminal_event(&job).map(|event| (None, event)));
+        return (replay, None);
+    }
+    let live = job_events(job_store.clone(), job_id, rx).boxed();
+    (replay, Some(live))
+}
+
+/// Live events of one job, ending with its terminal event.
+fn job_events(
+    job_store: JobStore,
+    job_id: Uuid,
+    mut rx: Receiver<PublishedEvent>,
+) -> impl Stream<Item = JobEventItem> {
+    stream! {
+        loop {
+            match rx.recv().await {
+                Ok(published) => {
+                    let terminal = published.event.is_terminal();
+                    yield (Some(published.id), published.event);
+                    if terminal {
+                        info!("Job {} completed or failed, stopping its events.", job_id);
+                        break;
+                    }
+                }
+                // A slow subscriber does not get the events it missed, it gets the current state
+                // of the job instead and carries on from there
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Subscriber for job {} missed {} events", job_id, skipped);
+                    match job_store.get_job(job_id).await {
+                        Some(job) if job.status.is_terminal() => {
+                            if let Some(event) = terminal_event(&job) {
+                                yield (None, event);
+                            }
                             break;
                         }
-                    } else {
-                        info!("Ignoring message for job {} as it doesn't match requested job IDs ", received_job_id);
+                        Some(job) => {
+                            yield (None, JobEvent::Stage { job_id, status: job.status });
+                        }
+                        None => break,
                     }
                 }
-                Err(e) => println!("Failed to deserialize job status: {}", e),
+                // The job was evicted or discarded
+                Err(RecvError::Closed) => break,
             }
         }
-    };
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    }
 }
@@ -0,0 +1,157 @@
+use std::{fs::File, io::BufReader, path::Path, str::FromStr, sync::Arc};
+
+use common::prover_input::Layout;
+use serde::Deserialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::errors::ProverError;
+
+/// Memory the prover needs regardless of the trace length, in MiB.
+const BASE_MEMORY_MIB: u64 = 256;
+
+/// Rough prover memory use per step for each layout, in KiB. Layouts with more builtins
+/// have wider traces, unknown layouts are treated like the heaviest one.
+fn kib_per_step(layout: Option<Layout>) -> u64 {
+    match layout {
+        Some(Layout::Small) => 3,
+        Some(Layout::Dex) => 4,
+        Some(Layout::Recursive) | Some(Layout::RecursiveWithPoseidon) => 6,
+        Some(Layout::Starknet) => 12,
+        Some(Layout::StarknetWithKeccak) | None => 24,
+    }
+}
+
+pub fn estimate_memory_mib(layout: &str, n_steps: u64) -> u64 {
+    let layout = Layout::from_str(layout).ok();
+    BASE_MEMORY_MIB + n_steps * kib_per_step(layout) / 1024
+}
+
+#[derive(Debug, Deserialize)]
+struct PublicInputSize {
+    layout: String,
+    n_steps: u64,
+}
+
+/// Limits the total estimated memory of the proofs that run at the same time.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBudget {
+    semaphore: Option<Arc<Semaphore>>,
+    total_mib: u32,
+}
+
+impl MemoryBudget {
+    /// Without a budget every proof is admitted right away.
+    pub fn new(total_mib: Option<u32>) -> Self {
+        Self {
+            semaphore: total_mib.map(|mib| Arc::new(Semaphore::new(mib as usize))),
+            total_mib: total_mib.unwrap_or_default(),
+        }
+    }
+
+    /// Waits until the proof described by `public_input_file` fits in the budget, the returned
+    /// permit gives its share back when dropped. Proofs larger than the whole budget run alone.
+    pub async fn admit(
+        &self,
+        public_input_file: &Path,
+        job_id: Uuid,
+    ) -> Result<Option<OwnedSemaphorePermit>, ProverError> {
+        let Some(semaphore) = &self.semaphore else {
+            return Ok(None);
+        };
+        let public_input: PublicInputSize =
+            serde_json::from_reader(BufReader::new(File::open(public_input_file)?))?;
+        let estimate = estimate_memory_mib(&public_input.layout, public_input.n_steps);
+        let permits = estimate.min(self.total_mib as u64) as u32;
+        info!(
+            "Job {} needs an estimated {} MiB for {} steps on layout {}, {} MiB available",
+            job_id,
+            estimate,
+            public_input.n_steps,
+            public_input.layout,
+            semaphore.available_permits()
+        );
+        let permit = semaphore
+            .clone()
+            .acquire_many_owned(permits)
+            .await
+            .map_err(|e| ProverError::InternalServerError(e.to_string()))?;
+        Ok(Some(permit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    fn public_input(dir: &TempDir, name: &str, layout: &str, n_steps: u64) -> PathBuf {
+        let path = dir.path().join(name);
+        let contents = serde_json::json!({ "layout": layout, "n_steps": n_steps });
+        std::fs::write(&path, contents.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_estimate_grows_with_steps_and_layout() {
+        assert!(
+            estimate_memory_mib("recursive", 1 << 20) > estimate_memory_mib("recursive", 1 << 16)
+        );
+        assert!(
+            estimate_memory_mib("starknet_with_keccak", 1 << 20)
+                > estimate_memory_mib("small", 1 << 20)
+        );
+        assert_eq!(
+            estimate_memory_mib("unknown", 1 << 20),
+            estimate_memory_mib("starknet_with_keccak", 1 << 20)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_small_proofs_share_the_budget() {
+        let dir = tempdir().unwrap();
+        let small = public_input(&dir, "small.json", "small", 1 << 10);
+        let budget = MemoryBudget::new(Some(1024));
+
+        let first = budget.admit(&small, Uuid::new_v4()).await.unwrap();
+        let second = budget.admit(&small, Uuid::new_v4()).await.unwrap();
+        assert!(first.is_some() && second.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_large_proof_waits_for_budget() {
+        let dir = tempdir().unwrap();
+        let large = public_input(&dir, "large.json", "starknet_with_keccak", 1 << 24);
+        let budget = MemoryBudget::new(Some(1024));
+
+        // Larger than the whole budget, so it is admitted alone
+        let permit = budget.admit(&large, Uuid::new_v4()).await.unwrap();
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(50),
+            budget.admit(&large, Uuid::new_v4()),
+        );
+        assert!(waiting.await.is_err());
+
+        drop(permit);
+        assert!(budget
+            .admit(&large, Uuid::new_v4())
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_no_budget_admits_everything() {
+        let budget = MemoryBudget::default();
+        let missing = PathBuf::from("does-not-exist.json");
+        assert!(budget
+            .admit(&missing, Uuid::new_v4())
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
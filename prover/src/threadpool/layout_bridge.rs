@@ -1,13 +1,26 @@
-use common::prover_input::{Cairo0ProverInput, Layout};
+use common::{
+    models::JobKind,
+    prover_input::{Cairo0ProverInput, Layout},
+};
 use tokio::fs;
 
 use crate::errors::ProverError;
 
-use super::{prove::prove, task::TaskCommon, CairoVersionedInput};
+use super::{
+    admission::MemoryBudget,
+    prove::trace,
+    task::{HandOver, TaskCommon},
+    CairoVersionedInput, PoolHandle,
+};
 
 const LAYOUT_BRIDGE_PATH: &str = "layout_bridge.json";
 
-pub async fn layout_bridge(common: &TaskCommon, proof: Vec<u8>) -> Result<(), ProverError> {
+pub async fn layout_bridge(
+    common: &TaskCommon,
+    proof: Vec<u8>,
+    memory_budget: MemoryBudget,
+    proving_pool: &PoolHandle,
+) -> Result<HandOver, ProverError> {
     let program = fs::read(LAYOUT_BRIDGE_PATH).await?;
 
     let input = Cairo0ProverInput {
@@ -17,13 +30,17 @@ pub async fn layout_bridge(common: &TaskCommon, proof: Vec<u8>) -> Result<(), Pr
         n_queries: None,
         pow_bits: None,
         run_mode: common::prover_input::RunMode::Bootload,
+        timeout: None,
+        priority: None,
+        public_result: false,
     };
     let input = CairoVersionedInput::Cairo0(input);
-    prove(
-        common.job_id,
-        common.job_store.clone(),
+    trace(
+        common,
+        JobKind::LayoutBridge,
         input,
-        common.sse_tx.clone(),
+        memory_budget,
+        proving_pool,
     )
     .await
 }
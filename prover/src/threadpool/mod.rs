@@ -1,65 +1,96 @@
-use crate::errors::ProverError;
+use crate::{errors::ProverError, metrics::METRICS};
+use uuid::Uuid;
 
-use std::sync::Arc;
+use common::models::JobError;
+use queue::{QueueConfig, QueueError, TaskQueue};
+use retry::RetryPolicy;
+use std::{sync::Arc, time::Duration};
 use task::Task;
 use tokio::{
-    spawn,
-    sync::{mpsc, Mutex},
+    select, spawn,
     task::JoinHandle,
+    time::{sleep, timeout, Instant},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+pub mod admission;
 pub mod layout_bridge;
 pub mod prove;
+pub mod queue;
+pub mod retry;
 pub mod run;
 pub mod snos;
 pub mod task;
 pub mod utlis;
 pub use run::CairoVersionedInput;
 
-type ReceiverType = Arc<Mutex<mpsc::Receiver<Task>>>;
-type SenderType = Option<mpsc::Sender<Task>>;
-
 pub struct ThreadPool {
     workers: Vec<Worker>,
-    sender: SenderType,
+    queue: Arc<TaskQueue>,
+    retry_after: Duration,
 }
 
 impl ThreadPool {
-    pub fn new(size: usize) -> ThreadPool {
+    pub fn new(
+        name: &'static str,
+        size: usize,
+        config: QueueConfig,
+        retry_policy: RetryPolicy,
+    ) -> ThreadPool {
         assert!(size > 0);
 
-        let (sender, receiver) = mpsc::channel(100);
-
-        let receiver = Arc::new(Mutex::new(receiver));
+        let queue = Arc::new(TaskQueue::new(config.aging_interval, config.capacity));
 
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, name, Arc::clone(&queue), retry_policy));
         }
 
         ThreadPool {
             workers,
-            sender: Some(sender),
+            queue,
+            retry_after: config.retry_after,
         }
     }
 
     pub async fn execute(&self, task: Task) -> Result<(), ProverError> {
-        self.sender
-            .as_ref()
-            .ok_or(ProverError::CustomError(
-                "Thread pool is shutdown".to_string(),
-            ))?
-            .send(task)
-            .await?;
+        let (job_id, job_store) = task.extract_common();
+        let (job_id, job_store) = (*job_id, job_store.clone());
+        if let Err(e) = self.queue.push(task).await {
+            // The job never reached a worker, so its id must not outlive the rejected request
+            job_store.discard_job(job_id).await;
+            return Err(rejected(e, self.retry_after));
+        }
         Ok(())
     }
 
-    pub async fn shutdown(&mut self) -> Result<(), ProverError> {
-        if let Some(sender) = self.sender.take() {
-            drop(sender); // Dropping the sender signals that no more messages will be sent
+    pub fn handle(&self) -> PoolHandle {
+        PoolHandle {
+            queue: self.queue.clone(),
+            retry_after: self.retry_after,
         }
+    }
+
+    /// Number of tasks waiting for a worker.
+    pub async fn queue_depth(&self) -> usize {
+        self.queue.depth().await
+    }
+
+    /// Position of a job that is still waiting for a worker, `0` being the next to run.
+    pub async fn queue_position(&self, job_id: Uuid) -> Option<usize> {
+        self.queue.position(job_id).await
+    }
+
+    /// Drops the task of a cancelled job that is still waiting for a worker, returns whether it
+    /// was queued.
+    pub async fn remove_queued(&self, job_id: Uuid) -> bool {
+        self.queue.remove(job_id).await.is_some()
+    }
+
+    pub async fn shutdown(&mut self) -> Result<(), ProverError> {
+        // Closing the queue signals that no more tasks will be submitted
+        self.queue.close().await;
 
         // Wait for each worker to finish its current task
         for worker in &mut self.workers {
@@ -75,44 +106,100 @@ impl ThreadPool {
     }
 }
 
+/// Lets a pipeline stage hand its job over to the next pool without locking the `ThreadPool`.
+#[derive(Clone)]
+pub struct PoolHandle {
+    queue: Arc<TaskQueue>,
+    retry_after: Duration,
+}
+
+impl PoolHandle {
+    pub async fn hand_over(&self, task: Task) -> Result<(), ProverError> {
+        self.queue
+            .push_wait(task)
+            .await
+            .map_err(|_| ProverError::ShuttingDown(self.retry_after.as_secs()))
+    }
+
+    /// Queues the tasks of a batch in order, the whole batch is rejected when it does not fit in
+    /// the queue.
+    pub async fn feed(&self, tasks: Vec<Task>) -> Result<(), ProverError> {
+        self.queue
+            .push_all(tasks)
+            .await
+            .map_err(|e| rejected(e, self.retry_after))
+    }
+}
+
+fn rejected(error: QueueError, retry_after: Duration) -> ProverError {
+    let retry_after = retry_after.as_secs();
+    match error {
+        QueueError::Full => ProverError::QueueFull(retry_after),
+        QueueError::Closed => ProverError::ShuttingDown(retry_after),
+    }
+}
+
 struct Worker {
     _id: usize,
     thread: Option<JoinHandle<()>>,
 }
 
 impl Worker {
-    fn new(id: usize, receiver: ReceiverType) -> Worker {
+    fn new(
+        id: usize,
+        pool: &'static str,
+        queue: Arc<TaskQueue>,
+        retry_policy: RetryPolicy,
+    ) -> Worker {
+        let active_workers = METRICS.active_workers.with_label_values(&[pool]);
         let thread = spawn(async move {
             loop {
-                let message = receiver.lock().await.recv().await;
+                let message = queue.pop().await;
                 match message {
                     Some(task) => {
+                        let (job_id, job_store) = task.extract_common();
+                        let (job_id, job_store) = (*job_id, job_store.clone());
+
+                        let Some(cancellation_token) = job_store.cancellation_token(job_id).await
+                        else {
+                            info!("Worker {id} skipping cancelled job {job_id}.");
+                            continue;
+                        };
                         info!("Worker {id} got a job; executing.");
 
-                        let (job_id, job_store, sse_tx) = task.extract_common();
-
-                        let job_result = task.execute().await;
-                        if let Err(e) = job_result {
-                            job_store
-                                .update_job_status(
-                                    *job_id,
-                                    common::models::JobStatus::Failed,
-                                    Some(e.to_string()),
-                                )
-                                .await;
-
-                            let sender = sse_tx.clone();
-                            let sender = sender.lock().await;
-                            if sender.receiver_count() > 0 {
-                                let _ = sender.send(
-                                    serde_json::to_string(&(
-                                        common::models::JobStatus::Failed,
-                                        job_id,
-                                    ))
-                                    .unwrap(),
-                                );
-                            }
+                        // Dropping the task future on cancellation or timeout kills its child
+                        // processes and removes its working directory
+                        let task_timeout = task.timeout();
+                        active_workers.inc();
+                        let started = Instant::now();
+                        let job_result = select! {
+                            result = timeout(task_timeout, task.execute()) => result
+                                .unwrap_or(Err(ProverError::TimedOut(task_timeout.as_secs()))),
+                            () = cancellation_token.cancelled() => Err(ProverError::Cancelled),
+                        };
+                        active_workers.dec();
+                        // Failed and cancelled attempts used the workers too
+                        job_store.record_usage(job_id, started.elapsed()).await;
+                        // Waiting for room in the next pool is not part of the stage's timeout
+                        let job_result = match job_result {
+                            Ok(Some(next)) => select! {
+                                result = next.pool.hand_over(next.task) => result,
+                                () = cancellation_token.cancelled() => Err(ProverError::Cancelled),
+                            },
+                            Ok(None) => Ok(()),
+                            Err(e) => Err(e),
+                        };
+                        // The job store counts finished jobs, on the transition that finished them
+                        let kind = task.kind();
+                        if let Err(ProverError::Cancelled) = job_result {
+                            info!("Worker {id} stopped job {job_id}, it was cancelled.");
+                        } else if let Err(e) = job_result {
                             error!("Worker {id} encountered an error in job {job_id}: {:?}", e);
+                            let error = e.into_job_error();
+                            if retry_or_fail(&queue, &retry_policy, task, error).await {
+                                METRICS.job_retried(kind);
+                                continue;
+                            }
                         }
 
                         info!("Worker {id} finished the job.");
@@ -131,3 +218,168 @@ impl Worker {
         }
     }
 }
+
+// Schedules the task again if its error is transient and its job has attempts left, otherwise
+// fails the job. Returns whether the task will be attempted again.
+async fn retry_or_fail(
+    queue: &Arc<TaskQueue>,
+    retry_policy: &RetryPolicy,
+    task: Task,
+    error: JobError,
+) -> bool {
+    let (job_id, job_store) = task.extract_common();
+    let (job_id, job_store) = (*job_id, job_store.clone());
+    let attempt = job_store.attempt(job_id).await;
+    if !task.retryable(error.code) || !retry_policy.allows_retry(attempt) {
+        job_store.fail_job(job_id, error).await;
+        return false;
+    }
+    let backoff = retry_policy.backoff(attempt);
+    // A job that finished meanwhile, e.g. was cancelled, is not retried
+    if job_store
+        .retry_job(job_id, error.clone(), backoff)
+        .await
+        .is_none()
+    {
+        return false;
+    }
+    info!(
+        "Job {job_id} is retried in {:?}, attempt {attempt} failed.",
+        backoff
+    );
+    schedule_retry(queue.clone(), task, error, backoff);
+    true
+}
+
+// Queues the task again once its backoff has passed. The job keeps its cancellation token
+// meanwhile, so a job cancelled during the backoff is skipped when a worker picks it up.
+fn schedule_retry(queue: Arc<TaskQueue>, task: Task, error: JobError, backoff: Duration) {
+    spawn(async move {
+        sleep(backoff).await;
+        let (job_id, job_store) = task.extract_common();
+        let (job_id, job_store) = (*job_id, job_store.clone());
+        if queue.push_wait(task).await.is_err() {
+            warn!("Job {job_id} could not be retried, the server is shutting down.");
+            job_store.fail_job(job_id, error).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use common::models::{JobErrorCode, JobKind, JobStatus};
+
+    use super::*;
+    use crate::{
+        auth::Signer,
+        threadpool::{
+            admission::MemoryBudget,
+            task::{LayoutBridgeParams, TaskCommon},
+        },
+        utils::job::JobStore,
+    };
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    async fn task(job_store: &JobStore) -> Task {
+        let job_id = job_store
+            .create_job(&Signer::default(), JobKind::LayoutBridge, false)
+            .await
+            .unwrap();
+        Task::LayoutBridge(LayoutBridgeParams {
+            common: TaskCommon {
+                job_id,
+                job_store: job_store.clone(),
+                timeout: Duration::from_secs(60),
+                priority: Default::default(),
+            },
+            proof: Vec::new(),
+            memory_budget: MemoryBudget::default(),
+            proving_pool: PoolHandle {
+                queue: Arc::new(TaskQueue::new(Duration::from_secs(60), 1)),
+                retry_after: Duration::ZERO,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried_until_attempts_run_out() {
+        let queue = Arc::new(TaskQueue::new(Duration::from_secs(60), 10));
+        let job_store = JobStore::default();
+        let task = task(&job_store).await;
+        let job_id = *task.extract_common().0;
+        let error = JobError::new(JobErrorCode::OutOfResources, "Runner was killed");
+
+        assert!(retry_or_fail(&queue, &policy(), task, error.clone()).await);
+        let job = job_store.get_job(job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.metrics.retries.len(), 1);
+
+        let task = queue.pop().await.unwrap();
+        assert_eq!(*task.extract_common().0, job_id);
+        assert!(!retry_or_fail(&queue, &policy(), task, error).await);
+        let job = job_store.get_job(job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.metrics.retries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_failures_are_not_retried() {
+        let queue = Arc::new(TaskQueue::new(Duration::from_secs(60), 10));
+        let job_store = JobStore::default();
+        let task = task(&job_store).await;
+        let job_id = *task.extract_common().0;
+        let error = JobError::new(JobErrorCode::HintFailure, "Cairo program failed in a hint");
+
+        assert!(!retry_or_fail(&queue, &policy(), task, error).await);
+        let job = job_store.get_job(job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.metrics.retries.is_empty());
+        assert_eq!(queue.depth().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_jobs_are_counted_once() {
+        let cancelled = || {
+            METRICS
+                .jobs_finished
+                .with_label_values(&["layout_bridge", "Cancelled"])
+                .get()
+        };
+        let before = cancelled();
+        let mut pool = ThreadPool::new(
+            "test",
+            1,
+            QueueConfig {
+                aging_interval: Duration::from_secs(60),
+                capacity: 1,
+                retry_after: Duration::ZERO,
+            },
+            policy(),
+        );
+        let job_store = JobStore::default();
+        let task = task(&job_store).await;
+        let job_id = *task.extract_common().0;
+
+        // Cancelled once it was submitted, the worker skips its task
+        job_store.cancel_job(job_id, None).await.unwrap();
+        pool.execute(task).await.unwrap();
+        pool.shutdown().await.unwrap();
+        // The task reporting back after the cancellation does not finish the job again
+        job_store
+            .fail_job(job_id, JobError::new(JobErrorCode::ProverCrash, "Killed"))
+            .await;
+
+        assert_eq!(
+            job_store.get_job(job_id).await.unwrap().status,
+            JobStatus::Cancelled
+        );
+        assert_eq!(cancelled(), before + 1);
+    }
+}
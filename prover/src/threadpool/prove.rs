@@ -1,94 +1,181 @@
-use super::CairoVersionedInput;
+use super::task::{HandOver, ProveParams, Task, TaskCommon};
+use super::{admission::MemoryBudget, CairoVersionedInput, PoolHandle};
 use crate::errors::ProverError;
-use crate::threadpool::utlis::{ProvePaths, RunPaths};
-use crate::utils::{config::Template, job::JobStore};
+use crate::threadpool::utlis::{
+    command_run, prover_failure, verifier_failure, ProvePaths, RunPaths,
+};
+use crate::utils::config::{ProgramPublicInputAsNSteps, ProgramPublicInputOutput, Template};
 use cairo_proof_parser::json_parser::proof_from_annotations;
 use cairo_proof_parser::output::ExtractOutputResult;
 use cairo_proof_parser::program::{CairoVersion, ExtractProgramResult};
 use cairo_proof_parser::{self, ProofJSON};
-use common::models::{JobStatus, ProverResult};
+use common::models::{JobError, JobErrorCode, JobKind, JobStatus, ProverResult, StageMetrics};
 use serde_json::Value;
+use starknet_crypto::Felt;
 use std::fs;
 
-use std::sync::Arc;
 use tempfile::tempdir;
-use tokio::sync::broadcast::Sender;
-use tokio::sync::Mutex;
 use tracing::{info, trace};
 
-#[allow(clippy::too_many_arguments)]
-pub async fn prove(
-    job_id: u64,
-    job_store: JobStore,
+/// First stage of a proof, runs on the running pool. Generates the trace and the prover
+/// parameters, and returns the proving stage that takes the working directory over.
+pub async fn trace(
+    common: &TaskCommon,
+    kind: JobKind,
     program_input: CairoVersionedInput,
-    sse_tx: Arc<Mutex<Sender<String>>>,
-) -> Result<(), ProverError> {
-    let dir = tempdir()?;
-    job_store
-        .update_job_status(job_id, JobStatus::Running, None)
-        .await;
+    memory_budget: MemoryBudget,
+    proving_pool: &PoolHandle,
+) -> Result<HandOver, ProverError> {
+    let job_id = common.job_id;
+    let started = tokio::time::Instant::now();
+    set_stage(common, JobStatus::Tracing).await;
 
-    let paths = ProvePaths::new(dir);
+    let paths = ProvePaths::new(tempdir()?);
     let (n_queries, pow_bits, run_mode) = program_input.get_parameters();
     if matches!(run_mode, common::prover_input::RunMode::Pie) {
         Err(ProverError::InvalidRunMode(
             "Pie mode is not supported in proving".to_string(),
         ))?;
     }
-    program_input
-        .prepare_and_run(&RunPaths::from(&paths), run_mode.clone(), job_id)
+    let stages = program_input
+        .prepare_and_run(
+            &RunPaths::from(&paths),
+            run_mode.clone(),
+            &common.job_store.log(job_id).await,
+        )
         .await?;
+    let n_steps = ProgramPublicInputAsNSteps::read_from_file(&paths.public_input_file)?.n_steps;
+    common
+        .job_store
+        .update_metrics(job_id, |metrics| {
+            stages
+                .into_iter()
+                .for_each(|stage| metrics.record_stage(stage));
+            metrics.n_steps = Some(n_steps as u64);
+        })
+        .await;
 
     Template::generate_from_public_input_file(&paths.public_input_file, n_queries, pow_bits)?
         .save_to_file(&paths.params_file)?;
 
+    info!(
+        "Trace generated for job {}, handing it over for proving",
+        job_id
+    );
+    Ok(HandOver {
+        pool: proving_pool.clone(),
+        task: Task::Prove(ProveParams {
+            common: common.next_stage(started.elapsed()),
+            kind,
+            program_input,
+            paths,
+            memory_budget,
+        }),
+    })
+}
+
+/// Second stage of a proof, runs on the proving pool with the artifacts of the trace stage.
+pub(super) async fn prove(
+    common: &TaskCommon,
+    program_input: &CairoVersionedInput,
+    paths: &ProvePaths,
+    memory_budget: &MemoryBudget,
+) -> Result<(), ProverError> {
+    let job_id = common.job_id;
+    let job_store = &common.job_store;
+    set_stage(common, JobStatus::Proving).await;
+
+    // Held until the prover exits so concurrent proofs stay within the memory budget
+    let _admission = memory_budget
+        .admit(&paths.public_input_file, job_id)
+        .await?;
     trace!("Running prover");
     let start = tokio::time::Instant::now();
-    let prove_status = paths.prove_command().spawn()?.wait().await?;
+    let peak_rss_kib = command_run(
+        paths.prove_command(),
+        &job_store.log(job_id).await,
+        prover_failure,
+    )
+    .await?;
     let elapsed = start.elapsed();
     info!(
         "Prover finished in {:?} ms for job: {}",
         elapsed.as_millis(),
         job_id
     );
+    // A proof that does not verify is never handed out
+    let start = tokio::time::Instant::now();
+    command_run(
+        paths.verify_command(),
+        &job_store.log(job_id).await,
+        verifier_failure,
+    )
+    .await?;
+    let verify_elapsed = start.elapsed();
     let result = fs::read_to_string(&paths.proof_path)?;
     let proof: Value = serde_json::from_str(&result)?;
     let final_result = serde_json::to_string_pretty(&proof)?;
-    let sender = sse_tx.lock().await;
+    job_store
+        .update_metrics(job_id, |metrics| {
+            metrics.record_stage(StageMetrics::new("prove", elapsed, peak_rss_kib));
+            metrics.record_stage(StageMetrics::new("verify", verify_elapsed, None));
+            metrics.proof_size = Some(final_result.len());
+        })
+        .await;
 
-    if prove_status.success() {
-        let cairo_version = match program_input {
-            CairoVersionedInput::Cairo(_) => CairoVersion::Cairo,
-            CairoVersionedInput::Cairo0(_) => CairoVersion::Cairo0,
-        };
-        let bootload = matches!(run_mode, common::prover_input::RunMode::Bootload);
-        let prover_result = prover_result(&final_result, cairo_version, bootload)?;
-
-        job_store
-            .update_job_status(
-                job_id,
-                JobStatus::Completed,
-                serde_json::to_string_pretty(&prover_result).ok(),
-            )
-            .await;
-        if sender.receiver_count() > 0 {
-            sender
-                .send(serde_json::to_string(&(JobStatus::Completed, job_id))?)
-                .unwrap();
-        }
-    } else {
-        job_store
-            .update_job_status(job_id, JobStatus::Failed, Some(final_result))
-            .await;
-        if sender.receiver_count() > 0 {
-            sender
-                .send(serde_json::to_string(&(JobStatus::Failed, job_id))?)
-                .unwrap();
-        }
-    }
+    set_stage(common, JobStatus::Parsing).await;
+    let (_, _, run_mode) = program_input.get_parameters();
+    let cairo_version = match program_input {
+        CairoVersionedInput::Cairo(_) => CairoVersion::Cairo,
+        CairoVersionedInput::Cairo0(_) => CairoVersion::Cairo0,
+    };
+    let bootload = matches!(run_mode, common::prover_input::RunMode::Bootload);
+    let start = tokio::time::Instant::now();
+    let prover_result = prover_result(&final_result, cairo_version, bootload)?;
+    check_output(
+        &prover_result.program_output,
+        &ProgramPublicInputOutput::read_from_file(&paths.public_input_file)?.output(),
+    )?;
+    let elapsed = start.elapsed();
+    job_store
+        .update_metrics(job_id, |metrics| {
+            metrics.record_stage(StageMetrics::new("parse", elapsed, None))
+        })
+        .await;
+
+    job_store
+        .update_job_status(
+            job_id,
+            JobStatus::Completed,
+            serde_json::to_string_pretty(&prover_result).ok(),
+        )
+        .await;
     Ok(())
 }
 
+async fn set_stage(common: &TaskCommon, status: JobStatus) {
+    common
+        .job_store
+        .update_job_status(common.job_id, status, None)
+        .await;
+}
+
+// The proof must commit to the output the trace run wrote
+fn check_output(proven: &[Felt], expected: &[Felt]) -> Result<(), ProverError> {
+    if proven == expected {
+        return Ok(());
+    }
+    Err(JobError::new(
+        JobErrorCode::VerifierMismatch,
+        format!(
+            "Proof commits to {} output cells, the program wrote {}",
+            proven.len(),
+            expected.len()
+        ),
+    )
+    .into())
+}
+
 fn prover_result(
     proof: &str,
     cairo_version: CairoVersion,
@@ -116,3 +203,46 @@ fn prover_result(
     };
     Ok(prover_result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proofs_of_another_output_are_rejected() {
+        let output = [Felt::from(1u64), Felt::from(55u64)];
+        assert!(check_output(&output, &output).is_ok());
+
+        for expected in [
+            &[Felt::from(1u64), Felt::from(89u64)][..],
+            &output[..1],
+            &[],
+        ] {
+            let error = check_output(&output, expected)
+                .unwrap_err()
+                .into_job_error();
+            assert_eq!(error.code, JobErrorCode::VerifierMismatch);
+            assert!(!error.code.is_retryable());
+        }
+    }
+
+    #[test]
+    fn test_output_is_read_from_the_public_memory() {
+        let public_input: ProgramPublicInputOutput = serde_json::from_value(serde_json::json!({
+            "memory_segments": {
+                "program": {"begin_addr": 1, "stop_ptr": 5},
+                "output": {"begin_addr": 10, "stop_ptr": 12}
+            },
+            "public_memory": [
+                {"address": 1, "value": "0x40780017fff7fff", "page": 0},
+                {"address": 11, "value": "0x37", "page": 0},
+                {"address": 10, "value": "0x1", "page": 0}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            public_input.output(),
+            vec![Felt::from(1u64), Felt::from(55u64)]
+        );
+    }
+}
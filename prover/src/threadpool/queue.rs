@@ -0,0 +1,355 @@
+use std::{pin::pin, time::Duration};
+
+use common::prover_input::Priority;
+use tokio::{
+    sync::{Mutex, Notify},
+    time::Instant,
+};
+use uuid::Uuid;
+
+use crate::Args;
+
+use super::task::Task;
+
+/// Scheduling and admission settings of a `ThreadPool` queue.
+#[derive(Debug, Clone, Copy)]
+pub struct QueueConfig {
+    pub aging_interval: Duration,
+    pub capacity: usize,
+    pub retry_after: Duration,
+}
+
+impl From<&Args> for QueueConfig {
+    fn from(args: &Args) -> Self {
+        Self {
+            aging_interval: Duration::from_secs(args.queue_aging_interval),
+            capacity: args.max_queue_depth,
+            retry_after: Duration::from_secs(args.queue_retry_after),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum QueueError {
+    Full,
+    Closed,
+}
+
+struct QueuedTask {
+    task: Task,
+    priority: Priority,
+    enqueued: Instant,
+}
+
+impl QueuedTask {
+    // Every `aging_interval` spent waiting is worth one priority level, so low priority
+    // tasks are eventually scheduled even under a constant stream of urgent ones
+    fn score(&self, now: Instant, aging_interval: Duration) -> u128 {
+        self.priority as u128 * aging_interval.as_millis()
+            + now.duration_since(self.enqueued).as_millis()
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    tasks: Vec<QueuedTask>,
+    closed: bool,
+}
+
+impl QueueState {
+    // Indices of the queued tasks, the next one to run first. Ties keep submission order.
+    fn schedule(&self, aging_interval: Duration) -> Vec<usize> {
+        let now = Instant::now();
+        let mut order: Vec<usize> = (0..self.tasks.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.tasks[i].score(now, aging_interval)));
+        order
+    }
+}
+
+/// Priority queue shared by the workers of a `ThreadPool`.
+pub struct TaskQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+    space: Notify,
+    aging_interval: Duration,
+    capacity: usize,
+}
+
+impl TaskQueue {
+    pub fn new(aging_interval: Duration, capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+            notify: Notify::new(),
+            space: Notify::new(),
+            aging_interval,
+            capacity,
+        }
+    }
+
+    fn insert(&self, state: &mut QueueState, task: Task) {
+        state.tasks.push(QueuedTask {
+            priority: task.priority(),
+            task,
+            enqueued: Instant::now(),
+        });
+        self.notify.notify_one();
+    }
+
+    /// Never waits for room, a full or closed queue rejects the task right away.
+    pub async fn push(&self, task: Task) -> Result<(), QueueError> {
+        let mut state = self.state.lock().await;
+        if state.closed {
+            return Err(QueueError::Closed);
+        }
+        if state.tasks.len() >= self.capacity {
+            return Err(QueueError::Full);
+        }
+        self.insert(&mut state, task);
+        Ok(())
+    }
+
+    /// Queues all the tasks or none of them, tasks that do not fit in the queue together are
+    /// rejected right away.
+    pub async fn push_all(&self, tasks: Vec<Task>) -> Result<(), QueueError> {
+        let mut state = self.state.lock().await;
+        if state.closed {
+            return Err(QueueError::Closed);
+        }
+        if state.tasks.len() + tasks.len() > self.capacity {
+            return Err(QueueError::Full);
+        }
+        for task in tasks {
+            self.insert(&mut state, task);
+        }
+        Ok(())
+    }
+
+    /// Waits for room instead of rejecting the task, used to hand a job over between pipeline
+    /// stages so a saturated pool slows down the previous stage rather than failing the job.
+    pub async fn push_wait(&self, task: Task) -> Result<(), QueueError> {
+        loop {
+            let mut notified = pin!(self.space.notified());
+            notified.as_mut().enable();
+            {
+                let mut state = self.state.lock().await;
+                if state.closed {
+                    return Err(QueueError::Closed);
+                }
+                if state.tasks.len() < self.capacity {
+                    self.insert(&mut state, task);
+                    return Ok(());
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Waits for the most urgent task, returns `None` once the queue is closed and drained.
+    pub async fn pop(&self) -> Option<Task> {
+        loop {
+            // Register interest before checking the state so a `close` in between is not missed
+            let mut notified = pin!(self.notify.notified());
+            notified.as_mut().enable();
+            {
+                let mut state = self.state.lock().await;
+                if let Some(&next) = state.schedule(self.aging_interval).first() {
+                    self.space.notify_one();
+                    return Some(state.tasks.swap_remove(next).task);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Number of tasks waiting for a worker.
+    pub async fn depth(&self) -> usize {
+        self.state.lock().await.tasks.len()
+    }
+
+    /// Zero-based position of the job among the queued tasks.
+    pub async fn position(&self, job_id: Uuid) -> Option<usize> {
+        let state = self.state.lock().await;
+        state
+            .schedule(self.aging_interval)
+            .into_iter()
+            .position(|i| *state.tasks[i].task.extract_common().0 == job_id)
+    }
+
+    /// Takes the task of the job out of the queue, e.g. once the job is cancelled, so it no longer
+    /// holds a slot.
+    pub async fn remove(&self, job_id: Uuid) -> Option<Task> {
+        let mut state = self.state.lock().await;
+        let position = state
+            .tasks
+            .iter()
+            .position(|queued| *queued.task.extract_common().0 == job_id)?;
+        self.space.notify_one();
+        Some(state.tasks.remove(position).task)
+    }
+
+    pub async fn close(&self) {
+        self.state.lock().await.closed = true;
+        self.notify.notify_waiters();
+        self.space.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        threadpool::{
+            admission::MemoryBudget,
+            task::{LayoutBridgeParams, TaskCommon},
+            PoolHandle,
+        },
+        utils::job::JobStore,
+    };
+
+    fn task(job_id: Uuid, priority: Priority) -> Task {
+        Task::LayoutBridge(LayoutBridgeParams {
+            common: TaskCommon {
+                job_id,
+                job_store: JobStore::default(),
+                timeout: Duration::from_secs(60),
+                priority,
+            },
+            proof: Vec::new(),
+            memory_budget: MemoryBudget::default(),
+            proving_pool: PoolHandle {
+                queue: Arc::new(TaskQueue::new(Duration::from_secs(60), 1)),
+                retry_after: Duration::ZERO,
+            },
+        })
+    }
+
+    fn job_ids() -> Vec<Uuid> {
+        (0..4).map(|_| Uuid::new_v4()).collect()
+    }
+
+    async fn pop_id(queue: &TaskQueue) -> Uuid {
+        *queue.pop().await.unwrap().extract_common().0
+    }
+
+    #[tokio::test]
+    async fn test_higher_priority_runs_first() {
+        let ids = job_ids();
+        let queue = TaskQueue::new(Duration::from_secs(3600), 10);
+        for (id, priority) in [
+            (ids[0], Priority::Low),
+            (ids[1], Priority::Normal),
+            (ids[2], Priority::High),
+            (ids[3], Priority::Normal),
+        ] {
+            assert!(queue.push(task(id, priority)).await.is_ok());
+        }
+
+        assert_eq!(queue.position(ids[3]).await, Some(2));
+        assert_eq!(pop_id(&queue).await, ids[2]);
+        assert_eq!(pop_id(&queue).await, ids[1]);
+        assert_eq!(pop_id(&queue).await, ids[3]);
+        assert_eq!(pop_id(&queue).await, ids[0]);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_tasks_age_past_newer_urgent_ones() {
+        let ids = job_ids();
+        let queue = TaskQueue::new(Duration::from_millis(1), 10);
+        assert!(queue.push(task(ids[0], Priority::Low)).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queue.push(task(ids[1], Priority::High)).await.is_ok());
+
+        assert_eq!(pop_id(&queue).await, ids[0]);
+        assert_eq!(pop_id(&queue).await, ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_closed_queue_drains_then_stops() {
+        let ids = job_ids();
+        let queue = TaskQueue::new(Duration::from_secs(60), 10);
+        assert!(queue.push(task(ids[0], Priority::Normal)).await.is_ok());
+        queue.close().await;
+
+        assert_eq!(
+            queue.push(task(ids[1], Priority::Normal)).await,
+            Err(QueueError::Closed)
+        );
+        assert_eq!(pop_id(&queue).await, ids[0]);
+        assert!(queue.pop().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_rejects_tasks() {
+        let ids = job_ids();
+        let queue = TaskQueue::new(Duration::from_secs(60), 1);
+        assert!(queue.push(task(ids[0], Priority::Normal)).await.is_ok());
+        assert_eq!(
+            queue.push(task(ids[1], Priority::High)).await,
+            Err(QueueError::Full)
+        );
+
+        assert_eq!(pop_id(&queue).await, ids[0]);
+        assert!(queue.push(task(ids[1], Priority::High)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tasks_that_do_not_fit_together_are_all_rejected() {
+        let ids = job_ids();
+        let queue = TaskQueue::new(Duration::from_secs(60), 3);
+        assert!(queue.push(task(ids[0], Priority::Normal)).await.is_ok());
+        let batch = ids[1..]
+            .iter()
+            .map(|&id| task(id, Priority::Normal))
+            .collect();
+        assert_eq!(queue.push_all(batch).await, Err(QueueError::Full));
+        assert_eq!(queue.depth().await, 1);
+
+        let batch = ids[1..3]
+            .iter()
+            .map(|&id| task(id, Priority::Normal))
+            .collect();
+        assert!(queue.push_all(batch).await.is_ok());
+        assert_eq!(queue.depth().await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_removed_tasks_free_their_slot() {
+        let ids = job_ids();
+        let queue = TaskQueue::new(Duration::from_secs(60), 2);
+        assert!(queue.push(task(ids[0], Priority::Normal)).await.is_ok());
+        assert!(queue.push(task(ids[1], Priority::Normal)).await.is_ok());
+        assert_eq!(
+            queue.push(task(ids[2], Priority::Normal)).await,
+            Err(QueueError::Full)
+        );
+
+        assert!(queue.remove(ids[0]).await.is_some());
+        assert!(queue.remove(ids[0]).await.is_none());
+        assert_eq!(queue.position(ids[1]).await, Some(0));
+        assert!(queue.push(task(ids[2], Priority::Normal)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hand_over_waits_for_room() {
+        let ids = job_ids();
+        let queue = Arc::new(TaskQueue::new(Duration::from_secs(60), 1));
+        assert!(queue.push(task(ids[0], Priority::Normal)).await.is_ok());
+
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            let job_id = ids[1];
+            async move { queue.push_wait(task(job_id, Priority::Normal)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        assert_eq!(pop_id(&queue).await, ids[0]);
+        assert_eq!(waiting.await.unwrap(), Ok(()));
+        assert_eq!(pop_id(&queue).await, ids[1]);
+    }
+}
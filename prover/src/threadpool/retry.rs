@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use crate::Args;
+
+/// How often and how soon a task that failed with a transient error is attempted again. Which
+/// errors are transient depends on the kind of task, see `Task::retryable`.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts a job gets in total, `1` disables retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl From<&Args> for RetryPolicy {
+    fn from(args: &Args) -> Self {
+        Self {
+            max_attempts: args.max_attempts,
+            initial_backoff: Duration::from_secs(args.retry_initial_backoff),
+            max_backoff: Duration::from_secs(args.retry_max_backoff),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a job whose attempt number `attempt` failed may be attempted again.
+    pub fn allows_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// Delay before the attempt following attempt number `attempt`, doubling after every failure.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let policy = policy();
+        assert_eq!(policy.backoff(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff(3), Duration::from_secs(40));
+        assert_eq!(policy.backoff(4), Duration::from_secs(60));
+        assert_eq!(policy.backoff(100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_attempts_are_bounded() {
+        let policy = policy();
+        assert!(policy.allows_retry(1));
+        assert!(policy.allows_retry(2));
+        assert!(!policy.allows_retry(3));
+    }
+}
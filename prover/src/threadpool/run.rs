@@ -1,25 +1,24 @@
 use std::{
     fs,
     path::{Path, PathBuf},
-    sync::Arc,
 };
 
 use common::{
-    models::{JobResult, JobStatus, RunResult, TraceFiles},
-    prover_input::{Cairo0ProverInput, CairoProverInput, Layout, RunMode},
+    models::{JobResult, JobStatus, RunResult, StageMetrics, TraceFiles},
+    prover_input::{BatchInput, Cairo0ProverInput, CairoProverInput, Layout, RunMode},
 };
 use tempfile::tempdir;
-use tokio::{
-    process::Command,
-    sync::{broadcast::Sender, Mutex},
-    time::Instant,
-};
+use tokio::{process::Command, time::Instant};
 use tracing::info;
+use uuid::Uuid;
 
 use crate::{
     errors::ProverError,
-    threadpool::utlis::{command_run, create_template, ProvePaths},
-    utils::job::JobStore,
+    threadpool::utlis::{command_run, create_template, runner_failure, ProvePaths},
+    utils::{
+        config::ProgramPublicInputAsNSteps,
+        job::{JobLog, JobStore},
+    },
 };
 
 use super::utlis::{prepare_input, RunPaths};
@@ -40,6 +39,26 @@ impl CairoVersionedInput {
             }
         }
     }
+    pub fn public_result(&self) -> bool {
+        match self {
+            CairoVersionedInput::Cairo(input) => input.public_result,
+            CairoVersionedInput::Cairo0(input) => input.public_result,
+        }
+    }
+    pub fn layout(&self) -> &Layout {
+        match self {
+            CairoVersionedInput::Cairo(input) => &input.layout,
+            CairoVersionedInput::Cairo0(input) => &input.layout,
+        }
+    }
+}
+impl From<BatchInput> for CairoVersionedInput {
+    fn from(input: BatchInput) -> Self {
+        match input {
+            BatchInput::Cairo(input) => CairoVersionedInput::Cairo(input),
+            BatchInput::Cairo0(input) => CairoVersionedInput::Cairo0(input),
+        }
+    }
 }
 pub trait BootloaderPath {
     fn path(&self) -> Result<PathBuf, ProverError>;
@@ -60,10 +79,9 @@ impl BootloaderPath for Layout {
 }
 
 pub async fn run(
-    job_id: u64,
+    job_id: Uuid,
     job_store: JobStore,
     program_input: CairoVersionedInput,
-    sse_tx: Arc<Mutex<Sender<String>>>,
 ) -> Result<(), ProverError> {
     let dir = tempdir()?;
     job_store
@@ -72,17 +90,38 @@ pub async fn run(
 
     let paths = ProvePaths::new(dir);
     let (_, _, run_mode) = program_input.get_parameters();
-    program_input
-        .prepare_and_run(&RunPaths::from(&paths), run_mode.clone(), job_id)
+    let stages = program_input
+        .prepare_and_run(
+            &RunPaths::from(&paths),
+            run_mode.clone(),
+            &job_store.log(job_id).await,
+        )
         .await
-        .map_err(|e| {
-            ProverError::TraceGenerationError(format!(
+        .map_err(|e| match e {
+            // Failures of the runner itself are already classified
+            ProverError::Job(_) => e,
+            e => ProverError::TraceGenerationError(format!(
                 "Failed to generate trace, cairo-run error: {}",
                 e
-            ))
+            )),
         })?;
     info!("Trace generated for job {}", job_id);
-    let sender = sse_tx.lock().await;
+    let n_steps = match run_mode {
+        RunMode::Bootload | RunMode::Trace => {
+            ProgramPublicInputAsNSteps::read_from_file(&paths.public_input_file)
+                .ok()
+                .map(|public_input| public_input.n_steps as u64)
+        }
+        RunMode::Pie => None,
+    };
+    job_store
+        .update_metrics(job_id, |metrics| {
+            stages
+                .into_iter()
+                .for_each(|stage| metrics.record_stage(stage));
+            metrics.n_steps = n_steps;
+        })
+        .await;
 
     let runner_result = match run_mode {
         RunMode::Bootload | RunMode::Trace => {
@@ -106,11 +145,6 @@ pub async fn run(
             serde_json::to_string(&JobResult::Run(runner_result)).ok(),
         )
         .await;
-    if sender.receiver_count() > 0 {
-        sender
-            .send(serde_json::to_string(&(JobStatus::Completed, job_id))?)
-            .unwrap();
-    }
     Ok(())
 }
 
@@ -119,10 +153,10 @@ impl CairoVersionedInput {
         &self,
         paths: &'_ RunPaths<'_>,
         run_mode: RunMode,
-        job_id: u64,
-    ) -> Result<(), ProverError> {
+        log: &JobLog,
+    ) -> Result<Vec<StageMetrics>, ProverError> {
         self.prepare(paths)?;
-        self.run_internal(paths, run_mode, job_id).await
+        self.run_internal(paths, run_mode, log).await
     }
     fn prepare(&self, paths: &RunPaths<'_>) -> Result<(), ProverError> {
         match self {
@@ -143,61 +177,65 @@ impl CairoVersionedInput {
         &self,
         paths: &RunPaths<'_>,
         run_mode: RunMode,
-        job_id: u64,
-    ) -> Result<(), ProverError> {
+        log: &JobLog,
+    ) -> Result<Vec<StageMetrics>, ProverError> {
         match self {
             CairoVersionedInput::Cairo(input) => {
                 let layout_str = input.layout.to_string();
                 match run_mode {
                     RunMode::Bootload => {
-                        generate_pie(
+                        let pie = generate_pie(
                             paths.pie_output,
                             paths.program_input_path,
                             paths.cairo1_pie_command(&layout_str),
-                            job_id,
+                            log,
                         )
                         .await?;
-                        run_cairo(paths.cairo0_run_command(&input.layout, true)?, job_id).await
+                        let trace =
+                            run_cairo(paths.cairo0_run_command(&input.layout, true)?, log).await?;
+                        Ok(vec![pie, trace])
                     }
-                    RunMode::Pie => {
+                    RunMode::Pie => Ok(vec![
                         generate_pie(
                             paths.pie_output,
                             paths.program_input_path,
                             paths.cairo1_pie_command(&layout_str),
-                            job_id,
+                            log,
                         )
-                        .await
-                    }
-                    RunMode::Trace => {
-                        run_cairo(paths.cairo1_run_command(&layout_str), job_id).await
-                    }
+                        .await?,
+                    ]),
+                    RunMode::Trace => Ok(vec![
+                        run_cairo(paths.cairo1_run_command(&layout_str), log).await?,
+                    ]),
                 }
             }
             CairoVersionedInput::Cairo0(input) => {
                 let layout_str = input.layout.to_string();
                 match run_mode {
                     RunMode::Bootload => {
-                        generate_pie(
+                        let pie = generate_pie(
                             paths.pie_output,
                             paths.program_input_path,
                             paths.cairo0_pie_command(&layout_str),
-                            job_id,
+                            log,
                         )
                         .await?;
-                        run_cairo(paths.cairo0_run_command(&input.layout, true)?, job_id).await
+                        let trace =
+                            run_cairo(paths.cairo0_run_command(&input.layout, true)?, log).await?;
+                        Ok(vec![pie, trace])
                     }
-                    RunMode::Pie => {
+                    RunMode::Pie => Ok(vec![
                         generate_pie(
                             paths.pie_output,
                             paths.program_input_path,
                             paths.cairo0_pie_command(&layout_str),
-                            job_id,
+                            log,
                         )
-                        .await
-                    }
-                    RunMode::Trace => {
-                        run_cairo(paths.cairo0_run_command(&input.layout, false)?, job_id).await
-                    }
+                        .await?,
+                    ]),
+                    RunMode::Trace => Ok(vec![
+                        run_cairo(paths.cairo0_run_command(&input.layout, false)?, log).await?,
+                    ]),
                 }
             }
         }
@@ -208,36 +246,38 @@ async fn generate_pie(
     pie_output: &Path,
     program_input_path: &Path,
     command: Command,
-    job_id: u64,
-) -> Result<(), ProverError> {
-    info!("Generating PIE for job {}", job_id);
+    log: &JobLog,
+) -> Result<StageMetrics, ProverError> {
+    info!("Generating PIE for job {}", log.job_id);
     let start = Instant::now();
-    command_run(command).await?;
+    let peak_rss_kib = command_run(command, log, runner_failure).await?;
+    let elapsed = start.elapsed();
     info!(
         "PIE generated in {:?}ms, for job {}",
-        start.elapsed().as_millis(),
-        job_id
+        elapsed.as_millis(),
+        log.job_id
     );
     create_template(
         pie_output.to_str().unwrap(),
         program_input_path.to_str().unwrap(),
     )?;
-    Ok(())
+    Ok(StageMetrics::new("pie", elapsed, peak_rss_kib))
 }
 
-async fn run_cairo(command: Command, job_id: u64) -> Result<(), ProverError> {
+async fn run_cairo(command: Command, log: &JobLog) -> Result<StageMetrics, ProverError> {
     info!(
         "Running cairo-run to generate trace from PIE for job {}",
-        job_id
+        log.job_id
     );
     let start = Instant::now();
-    command_run(command).await?;
+    let peak_rss_kib = command_run(command, log, runner_failure).await?;
+    let elapsed = start.elapsed();
     info!(
         "Trace generated in {:?}ms, for job {}",
-        start.elapsed().as_millis(),
-        job_id
+        elapsed.as_millis(),
+        log.job_id
     );
-    Ok(())
+    Ok(StageMetrics::new("trace", elapsed, peak_rss_kib))
 }
 
 impl RunPaths<'_> {
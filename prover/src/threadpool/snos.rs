@@ -1,29 +1,24 @@
-use std::sync::Arc;
-
 use cairo_vm::{vm::runners::cairo_pie::CairoPie, Felt252};
 use common::{
-    models::{JobResult, JobStatus, SnosPieOutput},
+    models::{JobError, JobErrorCode, JobResult, JobStatus, SnosPieOutput, StageMetrics},
     snos_input::SnosPieInput,
 };
 use prove_block::get_memory_segment;
-use prove_block::prove_block;
+use prove_block::{prove_block, ProveBlockError};
 use tempfile::tempdir;
-use tokio::{
-    fs,
-    sync::{broadcast::Sender, Mutex},
-};
+use tokio::fs;
 use tracing::info;
+use uuid::Uuid;
 
 use crate::{errors::ProverError, utils::job::JobStore};
 
 pub async fn snos_pie_gen(
-    job_id: u64,
+    job_id: Uuid,
     job_store: JobStore,
     program_input: SnosPieInput,
-    sse_tx: Arc<Mutex<Sender<String>>>,
 ) -> Result<(), ProverError> {
     let dir = tempdir()?;
-    let snos_pie_path = dir.into_path().join("snos_pie.zip");
+    let snos_pie_path = dir.path().join("snos_pie.zip");
     job_store
         .update_job_status(job_id, JobStatus::Running, None)
         .await;
@@ -37,12 +32,24 @@ pub async fn snos_pie_gen(
         program_input.full_output,
     )
     .await
-    .map_err(|e| ProverError::CustomError(e.to_string()))?;
+    .map_err(|e| {
+        // Only a failing RPC provider may answer differently next time
+        let code = match e {
+            ProveBlockError::RpcError(_) => JobErrorCode::RpcFailure,
+            _ => JobErrorCode::Internal,
+        };
+        JobError::new(code, "Failed to generate the SNOS pie").with_details(e.to_string())
+    })?;
 
     let elapsed = start.elapsed();
     info!("Snos pie generation for job {} took {:?}", job_id, elapsed);
-
-    let sender = sse_tx.lock().await;
+    let n_steps = result.0.extract_steps();
+    job_store
+        .update_metrics(job_id, |metrics| {
+            metrics.record_stage(StageMetrics::new("snos", elapsed, None));
+            metrics.n_steps = Some(n_steps as u64);
+        })
+        .await;
 
     if result.0.run_validity_checks().is_ok() {
         let pie = result.0.clone();
@@ -63,21 +70,9 @@ pub async fn snos_pie_gen(
                 Some(serde_json::to_string(&JobResult::Snos(snos_pie))?),
             )
             .await;
-        if sender.receiver_count() > 0 {
-            sender
-                .send(serde_json::to_string(&(JobStatus::Completed, job_id))?)
-                .unwrap();
-        }
     } else {
         info!("Failed to generate snos pie for job {}", job_id);
-        job_store
-            .update_job_status(job_id, JobStatus::Failed, None)
-            .await;
-        if sender.receiver_count() > 0 {
-            sender
-                .send(serde_json::to_string(&(JobStatus::Failed, job_id))?)
-                .unwrap();
-        }
+        return Err(JobError::internal("SNOS pie failed its validity checks").into());
     }
     Ok(())
 }
@@ -1,31 +1,73 @@
 use crate::{
     errors::ProverError,
-    threadpool::{layout_bridge::layout_bridge, prove::prove, snos::snos_pie_gen},
+    threadpool::{
+        layout_bridge::layout_bridge,
+        prove::{prove, trace},
+        snos::snos_pie_gen,
+    },
     utils::job::JobStore,
 };
+use uuid::Uuid;
 
-use std::sync::Arc;
+use std::time::Duration;
 
-use common::snos_input::SnosPieInput;
-use tokio::sync::{broadcast::Sender, Mutex};
+use common::{
+    models::{JobErrorCode, JobKind},
+    prover_input::Priority,
+    snos_input::SnosPieInput,
+};
 
-use super::{run::run, CairoVersionedInput};
+use super::{
+    admission::MemoryBudget, run::run, utlis::ProvePaths, CairoVersionedInput, PoolHandle,
+};
 use tracing::info;
 
+#[derive(Clone)]
 pub struct TaskCommon {
-    pub job_id: u64,
+    pub job_id: Uuid,
     pub job_store: JobStore,
-    pub sse_tx: Arc<Mutex<Sender<String>>>,
+    /// How long the task may execute before the worker gives up on it. The stages of a proof
+    /// share the timeout of the job, each stage gets what the previous ones left of it.
+    pub timeout: Duration,
+    pub priority: Priority,
 }
 impl TaskCommon {
-    pub fn as_tuple(&self) -> (&u64, &JobStore, &Arc<Mutex<Sender<String>>>) {
-        (&self.job_id, &self.job_store, &self.sse_tx)
+    pub fn as_tuple(&self) -> (&Uuid, &JobStore) {
+        (&self.job_id, &self.job_store)
+    }
+
+    /// Common part of the next stage of the job, limited to the time this stage left.
+    pub fn next_stage(&self, elapsed: Duration) -> TaskCommon {
+        TaskCommon {
+            timeout: self.timeout.saturating_sub(elapsed),
+            ..self.clone()
+        }
     }
 }
 
+/// Next stage of a job, queued on its pool by the worker once the current stage finished so that
+/// waiting for room there does not count against the timeout of the stage.
+pub struct HandOver {
+    pub pool: PoolHandle,
+    pub task: Task,
+}
+
+/// Trace stage of a proof, submitted to the running pool.
+pub struct TraceParams {
+    pub common: TaskCommon,
+    pub program_input: CairoVersionedInput,
+    pub memory_budget: MemoryBudget,
+    pub proving_pool: PoolHandle,
+}
+
+/// Proving stage of a proof, handed to the proving pool once its trace is generated.
 pub struct ProveParams {
     pub common: TaskCommon,
+    /// Kind of the job whose trace was handed over, a layout bridge is proven like a proof.
+    pub kind: JobKind,
     pub program_input: CairoVersionedInput,
+    pub(super) paths: ProvePaths,
+    pub memory_budget: MemoryBudget,
 }
 
 pub struct RunParams {
@@ -36,6 +78,8 @@ pub struct RunParams {
 pub struct LayoutBridgeParams {
     pub common: TaskCommon,
     pub proof: Vec<u8>,
+    pub memory_budget: MemoryBudget,
+    pub proving_pool: PoolHandle,
 }
 pub struct SnosParams {
     pub common: TaskCommon,
@@ -43,32 +87,91 @@ pub struct SnosParams {
 }
 pub enum Task {
     Run(RunParams),
+    Trace(TraceParams),
     Prove(ProveParams),
     LayoutBridge(LayoutBridgeParams),
     Snos(SnosParams),
 }
 
 impl Task {
-    pub fn extract_common(&self) -> (&u64, &JobStore, &Arc<Mutex<Sender<String>>>) {
+    fn common(&self) -> &TaskCommon {
+        match self {
+            Task::Trace(params) => &params.common,
+            Task::Prove(params) => &params.common,
+            Task::Run(params) => &params.common,
+            Task::LayoutBridge(params) => &params.common,
+            Task::Snos(params) => &params.common,
+        }
+    }
+
+    pub fn extract_common(&self) -> (&Uuid, &JobStore) {
+        self.common().as_tuple()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.common().timeout
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.common().priority
+    }
+
+    /// Kind of job the task belongs to, the proving stage reports the kind of its trace.
+    pub fn kind(&self) -> &'static str {
         match self {
-            Task::Prove(params) => params.common.as_tuple(),
-            Task::Run(params) => params.common.as_tuple(),
-            Task::LayoutBridge(params) => params.common.as_tuple(),
-            Task::Snos(params) => params.common.as_tuple(),
+            Task::Run(_) => JobKind::Run,
+            Task::Trace(_) => JobKind::Prove,
+            Task::Prove(params) => params.kind,
+            Task::LayoutBridge(_) => JobKind::LayoutBridge,
+            Task::Snos(_) => JobKind::Snos,
         }
+        .as_str()
     }
 
-    pub async fn execute(&self) -> Result<(), ProverError> {
+    /// Whether a failure of this task is transient, so that attempting it again may succeed.
+    pub fn retryable(&self, code: JobErrorCode) -> bool {
         match self {
+            // The Cairo runner is deterministic, only the resources it had can change
+            Task::Run(_) | Task::Trace(_) | Task::LayoutBridge(_) => {
+                code == JobErrorCode::OutOfResources
+            }
+            Task::Prove(_) => matches!(
+                code,
+                JobErrorCode::OutOfResources | JobErrorCode::ProverCrash
+            ),
+            // Generating the pie is deterministic for a block, only fetching it may fail transiently
+            Task::Snos(_) => matches!(
+                code,
+                JobErrorCode::OutOfResources | JobErrorCode::RpcFailure
+            ),
+        }
+    }
+
+    /// Runs the task, returning the next stage of the job if it has one.
+    pub async fn execute(&self) -> Result<Option<HandOver>, ProverError> {
+        match self {
+            Task::Trace(params) => {
+                info!("Executing trace task for job {}", params.common.job_id);
+                trace(
+                    &params.common,
+                    JobKind::Prove,
+                    params.program_input.clone(),
+                    params.memory_budget.clone(),
+                    &params.proving_pool,
+                )
+                .await
+                .map(Some)
+            }
             Task::Prove(params) => {
                 info!("Executing Prove task for job {}", params.common.job_id);
                 prove(
-                    params.common.job_id,
-                    params.common.job_store.clone(),
-                    params.program_input.clone(),
-                    params.common.sse_tx.clone(),
+                    &params.common,
+                    &params.program_input,
+                    &params.paths,
+                    &params.memory_budget,
                 )
                 .await
+                .map(|()| None)
             }
             Task::Run(params) => {
                 info!("Executing run task for job {}", params.common.job_id);
@@ -76,13 +179,20 @@ impl Task {
                     params.common.job_id,
                     params.common.job_store.clone(),
                     params.program_input.clone(),
-                    params.common.sse_tx.clone(),
                 )
                 .await
+                .map(|()| None)
             }
             Task::LayoutBridge(params) => {
                 info!("Executing layout bridge for job {}", params.common.job_id);
-                layout_bridge(&params.common, params.proof.clone()).await
+                layout_bridge(
+                    &params.common,
+                    params.proof.clone(),
+                    params.memory_budget.clone(),
+                    &params.proving_pool,
+                )
+                .await
+                .map(Some)
             }
             Task::Snos(params) => {
                 let program_input = params.input.clone();
@@ -91,10 +201,36 @@ impl Task {
                     params.common.job_id,
                     params.common.job_store.clone(),
                     program_input,
-                    params.common.sse_tx.clone(),
                 )
                 .await
+                .map(|()| None)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn common(timeout: Duration) -> TaskCommon {
+        TaskCommon {
+            job_id: Uuid::new_v4(),
+            job_store: JobStore::default(),
+            timeout,
+            priority: Priority::Normal,
+        }
+    }
+
+    #[test]
+    fn test_next_stage_gets_what_is_left_of_the_timeout() {
+        let trace = common(Duration::from_secs(60));
+        let prove = trace.next_stage(Duration::from_secs(45));
+        assert_eq!(prove.timeout, Duration::from_secs(15));
+        assert_eq!(prove.job_id, trace.job_id);
+        assert_eq!(
+            trace.next_stage(Duration::from_secs(90)).timeout,
+            Duration::ZERO
+        );
+    }
+}
@@ -1,14 +1,26 @@
+use common::models::{JobError, JobErrorCode};
 use starknet_crypto::Felt;
+use std::collections::VecDeque;
 use std::fs;
 use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
 use std::str::FromStr;
+use std::time::Duration;
 use tempfile::TempDir;
-use tokio::process::Command;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::select;
 
 use crate::errors::ProverError;
+use crate::utils::{job::JobLog, logs::MAX_LINE_BYTES};
 
-#[derive(Debug, Clone)]
+/// Last stderr lines reported with the error of a failed command, the whole output is in the job log.
+const ERROR_TAIL_LINES: usize = 100;
+
+#[derive(Debug)]
 pub(super) struct ProvePaths {
+    // Keeps the working directory alive, it is removed once the paths are dropped
+    _dir: TempDir,
     pub(super) program_input: PathBuf,
     pub(super) program: PathBuf,
     pub(super) proof_path: PathBuf,
@@ -23,7 +35,7 @@ pub(super) struct ProvePaths {
 
 impl ProvePaths {
     pub fn new(base_dir: TempDir) -> Self {
-        let path = base_dir.into_path();
+        let path = base_dir.path().to_path_buf();
         Self {
             program_input: path.join("program_input.json"),
             program: path.join("program.json"),
@@ -35,8 +47,15 @@ impl ProvePaths {
             params_file: path.join("cpu_air_params.json"),
             config_file: PathBuf::from_str("config/cpu_air_prover_config.json").unwrap(),
             pie_output: path.join("program_pie_output.zip"),
+            _dir: base_dir,
         }
     }
+    pub fn verify_command(&self) -> Command {
+        let mut command = Command::new("cpu_air_verifier");
+        command.arg("--in_file").arg(&self.proof_path);
+        command
+    }
+
     pub fn prove_command(&self) -> Command {
         let mut command = Command::new("cpu_air_prover");
         command
@@ -112,19 +131,260 @@ impl<'a> From<&'a ProvePaths> for RunPaths<'a> {
     }
 }
 
-pub async fn command_run(mut command: Command) -> Result<(), ProverError> {
-    command
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
+/// Kills the whole process group of a spawned child unless it is disarmed after the child exits.
+/// Dropping the future that awaits a child (job cancellation) therefore also stops any processes
+/// the child itself started, e.g. the python interpreter behind `cairo-run`.
+struct ProcessGroupGuard(Option<u32>);
+
+impl ProcessGroupGuard {
+    fn new(child: &Child) -> Self {
+        Self(child.id())
+    }
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.0 {
+            kill_process_group(pgid);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_group(pgid: u32) {
+    // SAFETY: killpg has no memory safety requirements, a stale group id only results in ESRCH
+    unsafe {
+        libc::killpg(pgid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pgid: u32) {}
+
+#[cfg(unix)]
+fn spawn_in_process_group(command: &mut Command) -> std::io::Result<Child> {
+    command.process_group(0).spawn()
+}
+
+#[cfg(not(unix))]
+fn spawn_in_process_group(command: &mut Command) -> std::io::Result<Child> {
+    command.spawn()
+}
+
+/// Resident memory of the process group of a command, in KiB. The group holds the command and
+/// every process it spawned, e.g. the prover started by a wrapper script. This is the larger of
+/// the peak (`VmHWM`) of the command itself and the current total (`VmRSS`) of the group.
+#[cfg(target_os = "linux")]
+fn read_group_rss(pgid: u32) -> Option<u64> {
+    let mut total = None;
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        if entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+            .is_none()
+        {
+            continue;
+        }
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        if process_group(&stat) != Some(pgid) {
+            continue;
+        }
+        let rss = fs::read_to_string(entry.path().join("status"))
+            .ok()
+            .and_then(|status| status_kib(&status, "VmRSS"));
+        if let Some(rss) = rss {
+            total = Some(total.unwrap_or(0) + rss);
+        }
+    }
+    let peak = fs::read_to_string(format!("/proc/{}/status", pgid))
+        .ok()
+        .and_then(|status| status_kib(&status, "VmHWM"));
+    total.max(peak)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_group_rss(_pgid: u32) -> Option<u64> {
+    None
+}
 
-    let child = command.spawn()?;
-    let output = child.wait_with_output().await?;
+// Value of a field of `/proc/<pid>/status` given in kB, e.g. `VmHWM`
+#[cfg(target_os = "linux")]
+fn status_kib(status: &str, field: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(ProverError::CustomError(stderr.into()));
+// Process group in `/proc/<pid>/stat`, the third field after the command name. The name is in
+// parentheses and may itself contain spaces and parentheses.
+#[cfg(target_os = "linux")]
+fn process_group(stat: &str) -> Option<u32> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(2)?.parse().ok()
+}
+
+// Memory can only be read while the processes are alive, so it is sampled until the child exits.
+// The child leads its own process group, see `spawn_in_process_group`.
+async fn wait_with_peak_rss(child: &mut Child) -> std::io::Result<(ExitStatus, Option<u64>)> {
+    let pgid = child.id();
+    let mut peak_rss = None;
+    let mut interval = tokio::time::interval(Duration::from_millis(250));
+    loop {
+        select! {
+            status = child.wait() => return Ok((status?, peak_rss)),
+            _ = interval.tick() => {
+                peak_rss = peak_rss.max(pgid.and_then(read_group_rss));
+            }
+        }
     }
-    Ok(())
+}
+
+// Forwards every line of the pipe to the job log, keeping the last `keep` lines. Lines are cut
+// like the log does, the rest of an over-long line is skipped rather than read.
+async fn capture_lines(
+    pipe: Option<impl AsyncRead + Unpin>,
+    log: &JobLog,
+    keep: usize,
+) -> std::io::Result<VecDeque<String>> {
+    let mut tail = VecDeque::new();
+    let Some(pipe) = pipe else {
+        return Ok(tail);
+    };
+    let mut reader = BufReader::new(pipe);
+    let mut buffer = Vec::with_capacity(MAX_LINE_BYTES);
+    loop {
+        buffer.clear();
+        let read = (&mut reader)
+            .take(MAX_LINE_BYTES as u64)
+            .read_until(b'\n', &mut buffer)
+            .await?;
+        if read == 0 {
+            break;
+        }
+        if buffer.last() != Some(&b'\n') {
+            skip_line(&mut reader).await?;
+        }
+        let line = String::from_utf8_lossy(&buffer).trim_end().to_string();
+        if keep > 0 {
+            if tail.len() == keep {
+                tail.pop_front();
+            }
+            tail.push_back(line.clone());
+        }
+        log.append(line).await;
+    }
+    Ok(tail)
+}
+
+// Drops the rest of the current line without buffering it
+async fn skip_line(reader: &mut (impl AsyncBufRead + Unpin)) -> std::io::Result<()> {
+    loop {
+        let chunk = reader.fill_buf().await?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        match chunk.iter().position(|&byte| byte == b'\n') {
+            Some(end) => {
+                reader.consume(end + 1);
+                return Ok(());
+            }
+            None => {
+                let len = chunk.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Runs the command to completion with its output captured into the job log. A failure is turned
+/// into a job error by `on_failure`, from the exit status and the end of the stderr of the command.
+/// Returns the peak memory of the child and the processes it spawned in KiB.
+pub async fn command_run(
+    mut command: Command,
+    log: &JobLog,
+    on_failure: fn(&ExitStatus, String) -> JobError,
+) -> Result<Option<u64>, ProverError> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = spawn_in_process_group(&mut command)?;
+    let guard = ProcessGroupGuard::new(&child);
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    // The pipes are drained while waiting so a chatty child never blocks on a full pipe
+    let (result, stdout, stderr) = tokio::join!(
+        wait_with_peak_rss(&mut child),
+        capture_lines(stdout, log, 0),
+        capture_lines(stderr, log, ERROR_TAIL_LINES)
+    );
+    let (status, peak_rss) = result?;
+    guard.disarm();
+    stdout?;
+
+    if !status.success() {
+        let stderr = Vec::from(stderr?).join("\n");
+        return Err(on_failure(&status, stderr).into());
+    }
+    Ok(peak_rss)
+}
+
+#[cfg(unix)]
+fn killed_by_oom(status: &ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    // The OOM killer sends SIGKILL, cancelled jobs never get here as their task is dropped
+    status.signal() == Some(libc::SIGKILL)
+}
+
+#[cfg(not(unix))]
+fn killed_by_oom(_status: &ExitStatus) -> bool {
+    false
+}
+
+/// Classifies a failed `cairo-run` or `cairo1-run`, hint errors are reported by both runners with
+/// the word "hint" in them.
+pub fn runner_failure(status: &ExitStatus, stderr: String) -> JobError {
+    let error = if killed_by_oom(status) {
+        JobError::new(JobErrorCode::OutOfResources, "Cairo runner was killed")
+    } else if stderr.to_lowercase().contains("hint") {
+        JobError::new(JobErrorCode::HintFailure, "Cairo program failed in a hint")
+    } else {
+        JobError::new(
+            JobErrorCode::InvalidProgram,
+            "Cairo runner rejected the program",
+        )
+    };
+    error.with_details(stderr)
+}
+
+/// Classifies a failed `cpu_air_prover`.
+pub fn prover_failure(status: &ExitStatus, stderr: String) -> JobError {
+    let error = if killed_by_oom(status) {
+        JobError::new(JobErrorCode::OutOfResources, "Prover was killed")
+    } else {
+        JobError::new(
+            JobErrorCode::ProverCrash,
+            format!("Prover exited with {}", status),
+        )
+    };
+    error.with_details(stderr)
+}
+
+/// Classifies a failed `cpu_air_verifier`, a proof it rejects is a mismatch.
+pub fn verifier_failure(status: &ExitStatus, stderr: String) -> JobError {
+    let error = if killed_by_oom(status) {
+        JobError::new(JobErrorCode::OutOfResources, "Verifier was killed")
+    } else {
+        JobError::new(
+            JobErrorCode::VerifierMismatch,
+            "Proof does not verify against the program",
+        )
+    };
+    error.with_details(stderr)
 }
 
 pub fn prepare_input(felts: &[Felt]) -> String {
@@ -165,3 +425,90 @@ pub fn create_template(pie_path: &str, file_path: &str) -> std::io::Result<()> {
     // Write the JSON string to the file
     fs::write(file_path, json)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::Signer, utils::job::JobStore};
+    use common::models::JobKind;
+
+    #[tokio::test]
+    async fn test_long_lines_are_cut() {
+        let job_store = JobStore::default();
+        let job_id = job_store
+            .create_job(&Signer::default(), JobKind::Run, false)
+            .await
+            .unwrap();
+        let output = format!(
+            "{}\nnext\n{}",
+            "a".repeat(3 * MAX_LINE_BYTES),
+            "b".repeat(10)
+        );
+
+        let tail = capture_lines(Some(output.as_bytes()), &job_store.log(job_id).await, 3)
+            .await
+            .unwrap();
+        let lines: Vec<String> = tail.into_iter().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "a".repeat(MAX_LINE_BYTES),
+                "next".to_string(),
+                "b".repeat(10)
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failure_classification() {
+        use std::os::unix::process::ExitStatusExt;
+        let exited = ExitStatus::from_raw(1 << 8);
+        let killed = ExitStatus::from_raw(libc::SIGKILL);
+        let hint = "Error: Got an exception while executing a hint.".to_string();
+        assert_eq!(
+            runner_failure(&exited, hint).code,
+            JobErrorCode::HintFailure
+        );
+        assert_eq!(
+            runner_failure(&exited, "Unknown value for memory cell".to_string()).code,
+            JobErrorCode::InvalidProgram
+        );
+        assert_eq!(
+            runner_failure(&killed, String::new()).code,
+            JobErrorCode::OutOfResources
+        );
+        assert_eq!(
+            prover_failure(&exited, String::new()).code,
+            JobErrorCode::ProverCrash
+        );
+        assert_eq!(
+            verifier_failure(&exited, String::new()).code,
+            JobErrorCode::VerifierMismatch
+        );
+        assert_eq!(
+            verifier_failure(&killed, String::new()).code,
+            JobErrorCode::OutOfResources
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_memory_fields_are_parsed() {
+        let status = "Name:\tcpu_air_prover\nVmPeak:\t  912340 kB\nVmHWM:\t  524288 kB\nVmRSS:\t  262144 kB\n";
+        assert_eq!(status_kib(status, "VmHWM"), Some(524288));
+        assert_eq!(status_kib(status, "VmRSS"), Some(262144));
+        assert_eq!(status_kib(status, "VmSwap"), None);
+
+        let stat = "4242 (cpu air (prover)) S 4200 4201 4201 0 -1 4194560";
+        assert_eq!(process_group(stat), Some(4201));
+        assert_eq!(process_group("4242 (broken"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_group_memory_includes_the_leader() {
+        // Whether or not the test process leads a group, its own peak is read
+        assert!(read_group_rss(std::process::id()).is_some_and(|kib| kib > 0));
+    }
+}
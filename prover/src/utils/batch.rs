@@ -0,0 +1,291 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use common::{
+    models::{BatchJobStatus, BatchResponse, BatchStatusResponse, JobKind, JobStatus},
+    prover_input::BatchInput,
+};
+use ed25519_dalek::VerifyingKey;
+use tokio::sync::Mutex;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    auth::{auth_errors::AuthError, quota::check_submission, Signer},
+    errors::ProverError,
+    server::AppState,
+    threadpool::{task::Task, PoolHandle},
+};
+
+use super::job::JobStore;
+
+/// Jobs submitted together by one request.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub id: Uuid,
+    pub owner: Option<VerifyingKey>,
+    pub job_ids: Vec<Uuid>,
+    pub created: DateTime<Utc>,
+}
+
+/// Batches are only kept in memory, their jobs outlive them across restarts. A batch is forgotten
+/// once all of its jobs were evicted.
+#[derive(Clone, Default)]
+pub struct BatchStore {
+    inner: Arc<Mutex<BatchStoreInner>>,
+}
+
+#[derive(Default)]
+struct BatchStoreInner {
+    batches: HashMap<Uuid, Batch>,
+}
+
+impl BatchStore {
+    /// Records the batch under a random id, so batch ids do not reveal how many were submitted.
+    pub async fn create(&self, owner: Option<VerifyingKey>, job_ids: Vec<Uuid>) -> Uuid {
+        let mut inner = self.inner.lock().await;
+        let id = Uuid::new_v4();
+        inner.batches.insert(
+            id,
+            Batch {
+                id,
+                owner,
+                job_ids,
+                created: Utc::now(),
+            },
+        );
+        id
+    }
+
+    /// Status of every job of the batch, only the key that submitted it may read it.
+    pub async fn status(
+        &self,
+        id: Uuid,
+        requester: Option<&VerifyingKey>,
+        job_store: &JobStore,
+    ) -> Result<BatchStatusResponse, ProverError> {
+        let batch = self
+            .inner
+            .lock()
+            .await
+            .batches
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ProverError::CustomError("Batch not found".to_string()))?;
+        if batch.owner.is_some() && batch.owner.as_ref() != requester {
+            return Err(ProverError::Auth(AuthError::Forbidden));
+        }
+        let mut response = BatchStatusResponse {
+            batch_id: batch.id,
+            created: batch.created,
+            total: batch.job_ids.len(),
+            pending: 0,
+            running: 0,
+            completed: 0,
+            failed: 0,
+            jobs: Vec::with_capacity(batch.job_ids.len()),
+        };
+        for job_id in batch.job_ids {
+            let status = job_store
+                .get_job(job_id)
+                .await
+                .map_or(JobStatus::Unknown, |job| job.status);
+            match status {
+                JobStatus::Pending => response.pending += 1,
+                JobStatus::Completed => response.completed += 1,
+                JobStatus::Failed
+                | JobStatus::Cancelled
+                | JobStatus::TimedOut
+                | JobStatus::Unknown => response.failed += 1,
+                _ => response.running += 1,
+            }
+            response.jobs.push(BatchJobStatus { job_id, status });
+        }
+        Ok(response)
+    }
+
+    /// Forgets the batches none of whose jobs are left, returning how many were removed.
+    pub async fn prune(&self, job_store: &JobStore) -> usize {
+        let batches: Vec<(Uuid, Vec<Uuid>)> = self
+            .inner
+            .lock()
+            .await
+            .batches
+            .values()
+            .map(|batch| (batch.id, batch.job_ids.clone()))
+            .collect();
+        let mut gone = Vec::new();
+        'batches: for (id, job_ids) in batches {
+            for job_id in job_ids {
+                if job_store.get_job(job_id).await.is_some() {
+                    continue 'batches;
+                }
+            }
+            gone.push(id);
+        }
+        let mut inner = self.inner.lock().await;
+        for id in gone.iter() {
+            inner.batches.remove(id);
+        }
+        gone.len()
+    }
+}
+
+/// Jobs of a batch being submitted, whose tasks are only queued once every job was created.
+pub struct PendingBatch {
+    pub job_ids: Vec<Uuid>,
+    pub tasks: Vec<Task>,
+}
+
+impl PendingBatch {
+    /// Rejects empty batches, batches larger than the server accepts, batches with a job the key of
+    /// the signer may not submit and batches that do not fit in its quota as a whole.
+    pub async fn new(
+        app_state: &AppState,
+        signer: &Signer,
+        kind: JobKind,
+        jobs: &[BatchInput],
+    ) -> Result<Self, ProverError> {
+        let size = jobs.len();
+        if size == 0 {
+            return Err(ProverError::CustomError("Batch has no jobs".to_string()));
+        }
+        if size > app_state.max_batch_size {
+            return Err(ProverError::BatchTooLarge(size, app_state.max_batch_size));
+        }
+        for input in jobs {
+            signer.check_allowed(kind, Some(input.layout()))?;
+        }
+        check_submission(app_state, signer, size as u64).await?;
+        Ok(Self {
+            job_ids: Vec::with_capacity(size),
+            tasks: Vec::with_capacity(size),
+        })
+    }
+
+    /// Feeds the tasks to the pool and records the batch. A batch that does not fit in the queue
+    /// is rejected as a whole and its jobs are discarded, so it is never accepted only in part.
+    pub async fn submit(
+        self,
+        app_state: &AppState,
+        signer: &Signer,
+        pool: PoolHandle,
+    ) -> Result<BatchResponse, ProverError> {
+        // Jobs shared with other submissions or served from the cache have no task here and are
+        // left alone
+        let queued: Vec<Uuid> = self
+            .tasks
+            .iter()
+            .map(|task| *task.extract_common().0)
+            .collect();
+        if let Err(e) = pool.feed(self.tasks).await {
+            for job_id in queued {
+                app_state.job_store.discard_job(job_id).await;
+            }
+            return Err(e);
+        }
+        let batch_id = app_state
+            .batches
+            .create(signer.0, self.job_ids.clone())
+            .await;
+        info!(
+            "Batch {} submitted with {} jobs",
+            batch_id,
+            self.job_ids.len()
+        );
+        Ok(BatchResponse {
+            batch_id,
+            job_ids: self.job_ids,
+        })
+    }
+
+    /// Forgets the jobs created so far, when the batch is rejected before being queued.
+    pub async fn abandon(self) {
+        for task in self.tasks {
+            let (job_id, job_store) = task.extract_common();
+            job_store.discard_job(*job_id).await;
+        }
+    }
+}
+
+pub async fn get_batch(
+    Path(id): Path<Uuid>,
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+) -> Result<impl IntoResponse, ProverError> {
+    let status = app_state
+        .batches
+        .status(id, signer.0.as_ref(), &app_state.job_store)
+        .await?;
+    Ok(Json(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::models::JobKind;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_status_counts_jobs() {
+        let job_store = JobStore::default();
+        let batches = BatchStore::default();
+        let pending = job_store
+            .create_job(&Signer::default(), JobKind::Run, false)
+            .await
+            .unwrap();
+        let running = job_store
+            .create_job(&Signer::default(), JobKind::Run, false)
+            .await
+            .unwrap();
+        let completed = job_store
+            .create_job(&Signer::default(), JobKind::Run, false)
+            .await
+            .unwrap();
+        job_store
+            .update_job_status(running, JobStatus::Proving, None)
+            .await;
+        job_store
+            .update_job_status(completed, JobStatus::Completed, Some("{}".to_string()))
+            .await;
+        let id = batches
+            .create(None, vec![pending, running, completed, Uuid::new_v4()])
+            .await;
+
+        let status = batches.status(id, None, &job_store).await.unwrap();
+        assert_eq!(status.total, 4);
+        assert_eq!(
+            (
+                status.pending,
+                status.running,
+                status.completed,
+                status.failed
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(status.jobs[3].status, JobStatus::Unknown);
+        assert!(!status.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_batches_without_jobs_are_pruned() {
+        let job_store = JobStore::default();
+        let batches = BatchStore::default();
+        let job_id = job_store
+            .create_job(&Signer::default(), JobKind::Run, false)
+            .await
+            .unwrap();
+        let evicted = Uuid::new_v4();
+        let kept = batches.create(None, vec![job_id, evicted]).await;
+        let gone = batches.create(None, vec![evicted]).await;
+
+        assert_eq!(batches.prune(&job_store).await, 1);
+        assert!(batches.status(kept, None, &job_store).await.is_ok());
+        assert!(batches.status(gone, None, &job_store).await.is_err());
+    }
+}
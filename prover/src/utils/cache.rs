@@ -0,0 +1,416 @@
+use std::{collections::HashMap, future::Future, path::PathBuf, sync::Arc, time::SystemTime};
+
+use common::{
+    canonical_json,
+    models::{JobKind, JobStatus},
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs, spawn,
+    sync::{Mutex, OnceCell},
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    auth::Signer, errors::ProverError, server::AppState, threadpool::CairoVersionedInput, Args,
+};
+
+use super::job::JobStore;
+
+/// Settings of the proof result cache, caching is disabled without a path.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub path: Option<PathBuf>,
+    pub max_entries: usize,
+}
+
+impl From<&Args> for CacheConfig {
+    fn from(args: &Args) -> Self {
+        Self {
+            path: args.result_cache_path.clone(),
+            max_entries: args.result_cache_max_entries,
+        }
+    }
+}
+
+/// Content address of a prove request: a hash of everything that determines its proof. Settings
+/// that only affect how the job is scheduled, like its timeout or priority, are left out.
+pub fn cache_key(input: &CairoVersionedInput) -> Result<String, ProverError> {
+    let request = match input {
+        CairoVersionedInput::Cairo(input) => json!({
+            "version": "cairo",
+            "program": input.program,
+            "program_input": input.program_input,
+            "layout": input.layout,
+            "n_queries": input.n_queries,
+            "pow_bits": input.pow_bits,
+            "run_mode": input.run_mode,
+        }),
+        CairoVersionedInput::Cairo0(input) => json!({
+            "version": "cairo0",
+            "program": program_value(&input.program),
+            "program_input": input.program_input,
+            "layout": input.layout,
+            "n_queries": input.n_queries,
+            "pow_bits": input.pow_bits,
+            "run_mode": input.run_mode,
+        }),
+    };
+    // Keys are sorted explicitly, so the bytes depend neither on field order nor on whether
+    // `serde_json` preserves insertion order
+    let bytes = canonical_json(&request)?;
+    Ok(hex::encode(Sha256::digest(bytes)))
+}
+
+// A Cairo 0 program arrives as the bytes of its JSON, it is parsed so that its formatting and
+// field order do not change the hash. Bytes that are not JSON are hashed as they are.
+fn program_value(program: &[u8]) -> Value {
+    serde_json::from_slice(program).unwrap_or_else(|_| Value::from(program))
+}
+
+/// Results of finished proofs stored on disk by the hash of their request, along with the jobs
+/// currently proving a request so identical submissions can share them.
+#[derive(Clone, Default)]
+pub struct ResultCache {
+    dir: Option<PathBuf>,
+    inner: Arc<Mutex<CacheInner>>,
+}
+
+/// Index of the cache. Files are read and written without holding it.
+#[derive(Default)]
+struct CacheInner {
+    max_entries: usize,
+    /// Cached keys and when they were last used, the least recently used one is evicted first.
+    entries: HashMap<String, u64>,
+    clock: u64,
+    /// Unfinished jobs by signing key and request hash, filled by the first of the identical
+    /// submissions. Jobs are only shared between submissions of the same key, so every job keeps
+    /// a single owner.
+    in_flight: HashMap<InFlightKey, Arc<OnceCell<Uuid>>>,
+}
+
+/// Signing key, whether the result is public, and request hash of an unfinished job.
+type InFlightKey = (Option<[u8; 32]>, bool, String);
+
+impl ResultCache {
+    /// Opens the cache, indexing the results already stored in its directory.
+    pub async fn new(config: CacheConfig) -> Result<Self, ProverError> {
+        let mut inner = CacheInner {
+            max_entries: config.max_entries,
+            ..Default::default()
+        };
+        if let Some(dir) = &config.path {
+            fs::create_dir_all(dir).await?;
+            let mut stored = Vec::new();
+            let mut entries = fs::read_dir(dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                    continue;
+                }
+                let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                let modified = entry
+                    .metadata()
+                    .await?
+                    .modified()
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                stored.push((modified, key.to_string()));
+            }
+            // Results keep their recency across restarts through their modification time
+            stored.sort();
+            for (_, key) in stored {
+                inner.touch(key);
+            }
+            info!("Indexed {} cached results", inner.entries.len());
+        }
+        let evicted = inner.evict();
+        let cache = Self {
+            dir: config.path,
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        cache.remove(evicted).await;
+        Ok(cache)
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", key)))
+    }
+
+    /// Returns the cached result of the request with this key, if any.
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let path = self.path(key)?;
+        if !self.inner.lock().await.entries.contains_key(key) {
+            return None;
+        }
+        match fs::read_to_string(&path).await {
+            Ok(result) => {
+                self.inner.lock().await.touch(key.to_string());
+                Some(result)
+            }
+            Err(e) => {
+                warn!(
+                    "Dropping unreadable cached result {}: {}",
+                    path.display(),
+                    e
+                );
+                self.inner.lock().await.entries.remove(key);
+                None
+            }
+        }
+    }
+
+    /// Stores the result of a request, evicting the least recently used results beyond the limit.
+    pub async fn insert(&self, key: String, result: String) {
+        let Some(path) = self.path(&key) else {
+            return;
+        };
+        // Write to a temporary file first so a crash never leaves a truncated result behind
+        let tmp_path = path.with_extension("json.tmp");
+        let written = match fs::write(&tmp_path, result).await {
+            Ok(()) => fs::rename(&tmp_path, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            error!("Failed to cache result {}: {}", key, e);
+            return;
+        }
+        let evicted = {
+            let mut inner = self.inner.lock().await;
+            inner.touch(key);
+            inner.evict()
+        };
+        self.remove(evicted).await;
+    }
+
+    async fn remove(&self, keys: Vec<String>) {
+        for path in keys.iter().filter_map(|key| self.path(key)) {
+            if let Err(e) = fs::remove_file(&path).await {
+                warn!("Failed to evict cached result {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl CacheInner {
+    fn touch(&mut self, key: String) {
+        self.clock += 1;
+        self.entries.insert(key, self.clock);
+    }
+    // Forgets the least recently used keys beyond the limit, returning them so their files can be
+    // removed
+    fn evict(&mut self) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.entries.len() > self.max_entries {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, used)| **used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            evicted.push(oldest);
+        }
+        evicted
+    }
+    // Forgets the in-flight job of the submission unless it was replaced meanwhile
+    fn finish(&mut self, in_flight_key: &InFlightKey, cell: &Arc<OnceCell<Uuid>>) {
+        if self
+            .in_flight
+            .get(in_flight_key)
+            .is_some_and(|current| Arc::ptr_eq(current, cell))
+        {
+            self.in_flight.remove(in_flight_key);
+        }
+    }
+}
+
+/// Answers a prove request from the cache when possible. A cached result becomes a new job that is
+/// already completed, and a request the same key is already proving gets that job back. Otherwise
+/// `start` creates and queues the job, whose result is cached once it completes.
+pub async fn submit_cached<F, Fut>(
+    app_state: &AppState,
+    signer: &Signer,
+    input: &CairoVersionedInput,
+    start: F,
+) -> Result<Uuid, ProverError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Uuid, ProverError>>,
+{
+    let cache = &app_state.result_cache;
+    let job_store = &app_state.job_store;
+    let key = cache_key(input)?;
+    if let Some(result) = cache.get(&key).await {
+        let job_id = job_store
+            .create_completed_job(signer.0, JobKind::Prove, input.public_result(), result)
+            .await;
+        info!("Served job {} from the result cache", job_id);
+        return Ok(job_id);
+    }
+
+    let in_flight_key = (
+        signer.0.map(|key| key.to_bytes()),
+        input.public_result(),
+        key,
+    );
+    let cell = cache
+        .inner
+        .lock()
+        .await
+        .in_flight
+        .entry(in_flight_key.clone())
+        .or_default()
+        .clone();
+    if let Some(job_id) = cell.get() {
+        info!("Sharing job {} with an identical submission", job_id);
+        return Ok(*job_id);
+    }
+    // Concurrent identical submissions wait for the first one to start its job, and start their
+    // own only if it failed
+    let started = cell
+        .get_or_try_init(|| async {
+            let job_id = start().await?;
+            spawn(remember_result(
+                cache.clone(),
+                job_store.clone(),
+                in_flight_key.clone(),
+                cell.clone(),
+                job_id,
+            ));
+            Ok::<_, ProverError>(job_id)
+        })
+        .await;
+    match started {
+        Ok(job_id) => Ok(*job_id),
+        Err(e) => {
+            if !cell.initialized() {
+                cache.inner.lock().await.finish(&in_flight_key, &cell);
+            }
+            Err(e)
+        }
+    }
+}
+
+// Caches the result of the job once it completes. The job stays shared until then, so identical
+// submissions find either the job or its result.
+async fn remember_result(
+    cache: ResultCache,
+    job_store: JobStore,
+    in_flight_key: InFlightKey,
+    cell: Arc<OnceCell<Uuid>>,
+    job_id: Uuid,
+) {
+    let job = job_store.wait_finished(job_id).await;
+    if let Some(job) = job {
+        if job.status == JobStatus::Completed {
+            if let Some(result) = job.result {
+                cache.insert(in_flight_key.2.clone(), result).await;
+            }
+        }
+    }
+    cache.inner.lock().await.finish(&in_flight_key, &cell);
+}
+
+#[cfg(test)]
+mod tests {
+    use common::prover_input::{Cairo0ProverInput, Layout, Priority, RunMode};
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn input(layout: Layout) -> Cairo0ProverInput {
+        Cairo0ProverInput {
+            program: b"{\"data\": []}".to_vec(),
+            program_input: b"{}".to_vec(),
+            layout,
+            n_queries: Some(16),
+            pow_bits: None,
+            run_mode: RunMode::Trace,
+            timeout: None,
+            priority: None,
+            public_result: false,
+        }
+    }
+
+    #[test]
+    fn test_key_ignores_scheduling_settings() {
+        let mut scheduled = input(Layout::Recursive);
+        scheduled.timeout = Some(60);
+        scheduled.priority = Some(Priority::High);
+        scheduled.public_result = true;
+        let key = cache_key(&CairoVersionedInput::Cairo0(input(Layout::Recursive))).unwrap();
+        assert_eq!(
+            key,
+            cache_key(&CairoVersionedInput::Cairo0(scheduled)).unwrap()
+        );
+        assert_ne!(
+            key,
+            cache_key(&CairoVersionedInput::Cairo0(input(Layout::Starknet))).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_key_ignores_program_formatting() {
+        let mut compact = input(Layout::Recursive);
+        compact.program = br#"{"data":[],"hints":{}}"#.to_vec();
+        let mut reordered = input(Layout::Recursive);
+        reordered.program = b"{\n  \"hints\": {},\n  \"data\": []\n}".to_vec();
+        assert_eq!(
+            cache_key(&CairoVersionedInput::Cairo0(compact)).unwrap(),
+            cache_key(&CairoVersionedInput::Cairo0(reordered)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_keys_are_hashed_in_sorted_order() {
+        let mut object = serde_json::Map::new();
+        object.insert("b".to_string(), json!([{"z": 1, "y": 2}]));
+        object.insert("a".to_string(), json!(null));
+        assert_eq!(
+            canonical_json(&Value::Object(object)).unwrap(),
+            br#"{"a":null,"b":[{"y":2,"z":1}]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_result_is_evicted() {
+        let dir = tempdir().unwrap();
+        let config = CacheConfig {
+            path: Some(dir.path().to_path_buf()),
+            max_entries: 2,
+        };
+        let cache = ResultCache::new(config.clone()).await.unwrap();
+        cache.insert("a".to_string(), "1".to_string()).await;
+        cache.insert("b".to_string(), "2".to_string()).await;
+        assert_eq!(cache.get("a").await.as_deref(), Some("1"));
+        cache.insert("c".to_string(), "3".to_string()).await;
+
+        assert_eq!(cache.get("b").await, None);
+        assert!(!dir.path().join("b.json").exists());
+
+        // Stored results survive a restart
+        let reopened = ResultCache::new(config).await.unwrap();
+        assert_eq!(reopened.get("a").await.as_deref(), Some("1"));
+        assert_eq!(reopened.get("c").await.as_deref(), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_cache_stores_nothing() {
+        let cache = ResultCache::new(CacheConfig {
+            path: None,
+            max_entries: 10,
+        })
+        .await
+        .unwrap();
+        cache.insert("a".to_string(), "1".to_string()).await;
+        assert_eq!(cache.get("a").await, None);
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use starknet_crypto::Felt;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Write};
 use std::path::PathBuf;
@@ -97,8 +99,8 @@ impl core::default::Default for Template {
 }
 
 #[derive(Debug, Deserialize)]
-struct ProgramPublicInputAsNSteps {
-    n_steps: u32,
+pub struct ProgramPublicInputAsNSteps {
+    pub n_steps: u32,
 }
 
 impl ProgramPublicInputAsNSteps {
@@ -115,3 +117,41 @@ impl ProgramPublicInputAsNSteps {
         steps
     }
 }
+
+#[derive(Debug, Deserialize)]
+struct MemorySegment {
+    begin_addr: u64,
+    stop_ptr: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PublicMemoryCell {
+    address: u64,
+    value: Felt,
+}
+
+/// Output the Cairo runner wrote, read from the public memory of the public input.
+#[derive(Debug, Deserialize)]
+pub struct ProgramPublicInputOutput {
+    memory_segments: HashMap<String, MemorySegment>,
+    public_memory: Vec<PublicMemoryCell>,
+}
+
+impl ProgramPublicInputOutput {
+    pub fn read_from_file(input_file: &PathBuf) -> Result<Self, ProverError> {
+        serde_json::from_reader(BufReader::new(File::open(input_file)?)).map_err(ProverError::from)
+    }
+    /// Cells of the output segment in order, empty for a layout without one.
+    pub fn output(&self) -> Vec<Felt> {
+        let Some(segment) = self.memory_segments.get("output") else {
+            return Vec::new();
+        };
+        let mut cells: Vec<&PublicMemoryCell> = self
+            .public_memory
+            .iter()
+            .filter(|cell| (segment.begin_addr..segment.stop_ptr).contains(&cell.address))
+            .collect();
+        cells.sort_by_key(|cell| cell.address);
+        cells.iter().map(|cell| cell.value).collect()
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use common::models::{JobEvent, JobStatus};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use uuid::Uuid;
+
+use super::job::Job;
+
+/// Events kept per job so late subscribers can catch up.
+const EVENT_HISTORY: usize = 64;
+/// Events a subscriber may fall behind before it lags and has to re-read the job.
+const CHANNEL_CAPACITY: usize = 128;
+
+/// An event together with its id, ids grow across all jobs so they can be used as `Last-Event-ID`.
+#[derive(Debug, Clone)]
+pub struct PublishedEvent {
+    pub id: u64,
+    pub event: JobEvent,
+}
+
+struct JobChannel {
+    history: VecDeque<PublishedEvent>,
+    tx: Sender<PublishedEvent>,
+}
+
+impl Default for JobChannel {
+    fn default() -> Self {
+        Self {
+            history: VecDeque::new(),
+            tx: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+/// Publishes job events to the subscribers of each job and remembers the recent ones. Every job
+/// has its own channel, so subscribers only ever wake up for the jobs they follow.
+pub struct EventLog {
+    next_id: Arc<AtomicU64>,
+    channels: HashMap<Uuid, JobChannel>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            next_id: Arc::new(AtomicU64::new(1)),
+            channels: HashMap::new(),
+        }
+    }
+}
+
+impl EventLog {
+    pub fn publish(&mut self, event: JobEvent) {
+        let published = PublishedEvent {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            event,
+        };
+        let channel = self.channels.entry(published.event.job_id()).or_default();
+        // Log lines would quickly push the status changes out of the history, they are served
+        // from the log buffer of the job instead
+        if !matches!(published.event, JobEvent::Log { .. }) {
+            if channel.history.len() == EVENT_HISTORY {
+                channel.history.pop_front();
+            }
+            channel.history.push_back(published.clone());
+        }
+        // Nobody listening is not an error, the event stays in the history
+        let _ = channel.tx.send(published);
+    }
+
+    /// Returns the remembered events of the job newer than `last_event_id` and a receiver for the
+    /// ones that follow, nothing is lost or repeated in between.
+    pub fn subscribe(
+        &mut self,
+        job_id: Uuid,
+        last_event_id: Option<u64>,
+    ) -> (Vec<PublishedEvent>, Receiver<PublishedEvent>) {
+        let channel = self.channels.entry(job_id).or_default();
+        let replay = channel
+            .history
+            .iter()
+            .filter(|published| last_event_id.is_none_or(|last| published.id > last))
+            .cloned()
+            .collect();
+        (replay, channel.tx.subscribe())
+    }
+
+    /// Drops the channel of a job that is gone, its subscribers see the channel close.
+    pub fn forget(&mut self, job_id: Uuid) {
+        self.channels.remove(&job_id);
+    }
+
+    /// Sender for the events of the job that is used without the `EventLog`, e.g. by its log.
+    pub fn sender(&mut self, job_id: Uuid) -> EventSender {
+        EventSender {
+            next_id: self.next_id.clone(),
+            tx: self.channels.entry(job_id).or_default().tx.clone(),
+        }
+    }
+}
+
+/// Sends the events of one job to its subscribers without remembering them, which only suits
+/// events that are not replayed, i.e. log lines.
+#[derive(Clone)]
+pub struct EventSender {
+    next_id: Arc<AtomicU64>,
+    tx: Sender<PublishedEvent>,
+}
+
+impl EventSender {
+    pub fn send(&self, event: JobEvent) {
+        let published = PublishedEvent {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            event,
+        };
+        let _ = self.tx.send(published);
+    }
+}
+
+/// Terminal event matching the status of a finished job, for jobs whose history is gone,
+/// e.g. after a restart.
+pub fn terminal_event(job: &Job) -> Option<JobEvent> {
+    match job.status {
+        JobStatus::Completed => Some(JobEvent::Completed { job_id: job.id }),
+        JobStatus::Failed | JobStatus::TimedOut | JobStatus::Cancelled => Some(JobEvent::Failed {
+            job_id: job.id,
+            status: job.status.clone(),
+            error: Some(job.job_error()),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay_after_last_event_id() {
+        let mut events = EventLog::default();
+        let (job, other) = (Uuid::new_v4(), Uuid::new_v4());
+        events.publish(JobEvent::Queued { job_id: job });
+        events.publish(JobEvent::Queued { job_id: other });
+        events.publish(JobEvent::Started {
+            job_id: job,
+            status: JobStatus::Running,
+        });
+
+        let (replay, _) = events.subscribe(job, None);
+        let ids: Vec<u64> = replay.iter().map(|published| published.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+
+        let (replay, mut rx) = events.subscribe(job, Some(1));
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].event.name(), "started");
+
+        events.publish(JobEvent::Queued { job_id: other });
+        events.publish(JobEvent::Completed { job_id: job });
+        let next = rx.recv().await.unwrap();
+        assert_eq!(next.id, 5);
+        assert!(next.event.is_terminal());
+    }
+
+    #[tokio::test]
+    async fn test_forgotten_job_closes_subscribers() {
+        let mut events = EventLog::default();
+        let job = Uuid::new_v4();
+        events.publish(JobEvent::Queued { job_id: job });
+        let (_, mut rx) = events.subscribe(job, None);
+
+        events.forget(job);
+        assert!(matches!(
+            rx.recv().await,
+            Err(broadcast::error::RecvError::Closed)
+        ));
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut events = EventLog::default();
+        let job = Uuid::new_v4();
+        for _ in 0..EVENT_HISTORY + 10 {
+            events.publish(JobEvent::Stage {
+                job_id: job,
+                status: JobStatus::Running,
+            });
+        }
+        let (replay, _) = events.subscribe(job, None);
+        assert_eq!(replay.len(), EVENT_HISTORY);
+        assert_eq!(replay[0].id, 11);
+
+        events.forget(job);
+        assert!(events.subscribe(job, None).0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_log_lines_are_not_remembered() {
+        let mut events = EventLog::default();
+        let job = Uuid::new_v4();
+        let (_, mut rx) = events.subscribe(job, None);
+        events.publish(JobEvent::Log {
+            job_id: job,
+            line: "hint output".to_string(),
+        });
+        assert!(events.subscribe(job, None).0.is_empty());
+        assert_eq!(rx.recv().await.unwrap().event.name(), "log");
+    }
+
+    #[tokio::test]
+    async fn test_sender_shares_event_ids() {
+        let mut events = EventLog::default();
+        let job = Uuid::new_v4();
+        let (_, mut rx) = events.subscribe(job, None);
+        let sender = events.sender(job);
+        events.publish(JobEvent::Queued { job_id: job });
+        sender.send(JobEvent::Log {
+            job_id: job,
+            line: "hint output".to_string(),
+        });
+        assert_eq!(rx.recv().await.unwrap().id, 1);
+        assert_eq!(rx.recv().await.unwrap().id, 2);
+        assert_eq!(events.subscribe(job, None).0.len(), 1);
+    }
+}
@@ -2,24 +2,129 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
-use common::models::{JobResponse, JobStatus};
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use common::models::{
+    JobAttempt, JobError, JobErrorCode, JobEvent, JobKind, JobListResponse, JobLogsResponse,
+    JobMetrics, JobQuery, JobResponse, JobStatus, JobSummary, KeyQuota,
+};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::BTreeMap,
+    collections::{HashMap, HashSet},
     sync::Arc,
-    time::{Duration, Instant},
+    time::Duration,
+};
+use tokio::sync::{
+    broadcast::{error::RecvError, Receiver},
+    Mutex,
 };
-use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+use uuid::Uuid;
 
-use crate::{errors::ProverError, server::AppState};
+use crate::{
+    auth::{
+        auth_errors::AuthError,
+        quota::{check_jobs, KeyUsage, ProvingUsage},
+        Signer,
+    },
+    errors::ProverError,
+    metrics::METRICS,
+    server::AppState,
+};
 
-#[derive(Clone)]
+use super::{
+    events::{EventLog, EventSender, PublishedEvent},
+    logs::LogBuffer,
+    retention::RetentionPolicy,
+    storage::{JobBackend, JobStorage},
+};
+
+/// Jobs listed by `/jobs` when the request does not pick a page size.
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Job {
-    pub id: u64,
+    pub id: Uuid,
     pub status: JobStatus,
     pub result: Option<String>,
-    pub created: Instant,
+    pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub finished: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub owner: Option<VerifyingKey>,
+    #[serde(default)]
+    pub metrics: JobMetrics,
+    /// Why the job failed, jobs stored before errors were typed only have a message in `result`.
+    #[serde(default)]
+    pub error: Option<JobError>,
+    /// Jobs stored before kinds were recorded have none.
+    #[serde(default)]
+    pub kind: Option<JobKind>,
+    /// Anyone may read the job, see [`Job::readable_by`].
+    #[serde(default)]
+    pub public: bool,
+}
+
+impl Job {
+    pub fn summary(&self) -> JobSummary {
+        JobSummary {
+            job_id: self.id,
+            kind: self.kind,
+            status: self.status.clone(),
+            owner: self.owner,
+            created: self.created,
+            finished: self.finished,
+            error: matches!(
+                self.status,
+                JobStatus::Failed | JobStatus::Cancelled | JobStatus::TimedOut
+            )
+            .then(|| self.job_error()),
+        }
+    }
+    /// Public jobs and jobs without an owner may be read by anyone, other jobs only by their
+    /// owner.
+    pub fn readable_by(&self, requester: Option<&VerifyingKey>) -> bool {
+        self.public || self.owner.is_none() || self.owner.as_ref() == requester
+    }
+    /// When the next attempt of a job waiting out its retry backoff starts.
+    pub fn retry_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.status != JobStatus::Pending {
+            return None;
+        }
+        self.metrics
+            .retries
+            .last()?
+            .retry_at
+            .filter(|retry_at| *retry_at > now)
+    }
+    pub fn result_size(&self) -> usize {
+        self.result.as_ref().map_or(0, String::len)
+    }
+    /// Error of a failed job, falling back to the message of jobs without a typed error.
+    pub fn job_error(&self) -> JobError {
+        if let Some(error) = &self.error {
+            return error.clone();
+        }
+        match self.status {
+            JobStatus::TimedOut => JobError::new(
+                JobErrorCode::Timeout,
+                self.result
+                    .clone()
+                    .unwrap_or_else(|| "Job timed out".to_string()),
+            ),
+            JobStatus::Cancelled => JobError::new(JobErrorCode::Cancelled, "Job was cancelled"),
+            _ => JobError::internal(
+                self.result
+                    .clone()
+                    .unwrap_or_else(|| "Unknown error".to_string()),
+            ),
+        }
+    }
 }
 
 #[derive(Default, Clone)]
@@ -28,102 +133,1073 @@ pub struct JobStore {
 }
 
 impl JobStore {
-    pub async fn create_job(&self) -> u64 {
-        self.inner.lock().await.create_job()
+    /// Creates a store on top of `backend`, restoring any jobs it already holds.
+    pub async fn new(backend: JobBackend) -> Result<Self, ProverError> {
+        let mut inner = JobStoreInner {
+            backend,
+            ..Default::default()
+        };
+        inner.restore().await?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+        })
     }
-    pub async fn update_job_status(&self, job_id: u64, status: JobStatus, result: Option<String>) {
+    /// Creates a pending job of the signer. Its job limits are checked under the lock the job is
+    /// created with, so concurrent submissions cannot go over them together.
+    pub async fn create_job(
+        &self,
+        signer: &Signer,
+        kind: JobKind,
+        public: bool,
+    ) -> Result<Uuid, ProverError> {
+        let mut inner = self.inner.lock().await;
+        let quota = &signer.1.quota;
+        if let Some(key) = signer.0.as_ref().filter(|_| *quota != KeyQuota::default()) {
+            check_jobs(quota, &inner.usage(key), 1)?;
+        }
+        Ok(inner.create_job(signer.0, kind, public).await)
+    }
+    /// Creates a job that is already completed with `result`, e.g. one served from the cache.
+    pub async fn create_completed_job(
+        &self,
+        owner: Option<VerifyingKey>,
+        kind: JobKind,
+        public: bool,
+        result: String,
+    ) -> Uuid {
+        let mut inner = self.inner.lock().await;
+        let job_id = inner.create_job(owner, kind, public).await;
+        if let Some(job) = inner.jobs.get_mut(&job_id) {
+            job.metrics.cache_hit = true;
+        }
+        inner
+            .update_job_status(job_id, JobStatus::Completed, Some(result))
+            .await;
+        job_id
+    }
+    pub async fn update_job_status(&self, job_id: Uuid, status: JobStatus, result: Option<String>) {
         self.inner
             .lock()
             .await
-            .update_job_status(job_id, status, result);
+            .update_job_status(job_id, status, result)
+            .await;
     }
-    pub async fn get_job(&self, id: u64) -> Option<Job> {
+    /// Marks the job as failed with `error`, or as timed out for a timeout.
+    pub async fn fail_job(&self, job_id: Uuid, error: JobError) {
+        self.inner
+            .lock()
+            .await
+            .set_status(job_id, error.code.job_status(), None, Some(error))
+            .await;
+    }
+    /// Records the failed attempt of the job and puts it back to `Pending` until it is retried
+    /// after `retry_in`. Returns the number of the failed attempt, `None` if the job already
+    /// finished, e.g. it was cancelled meanwhile.
+    pub async fn retry_job(
+        &self,
+        job_id: Uuid,
+        error: JobError,
+        retry_in: Duration,
+    ) -> Option<u32> {
+        self.inner
+            .lock()
+            .await
+            .retry_job(job_id, error, retry_in)
+            .await
+    }
+    /// Number of the attempt the job is on, starting at 1.
+    pub async fn attempt(&self, job_id: Uuid) -> u32 {
+        self.inner
+            .lock()
+            .await
+            .jobs
+            .get(&job_id)
+            .map_or(1, |job| job.metrics.retries.len() as u32 + 1)
+    }
+    pub async fn get_job(&self, id: Uuid) -> Option<Job> {
         self.inner.lock().await.get_job(id)
     }
+    /// Unfinished jobs of the key and the time spent on its jobs today.
+    pub async fn usage(&self, key: &VerifyingKey) -> KeyUsage {
+        self.inner.lock().await.usage(key)
+    }
+    /// A page of the jobs matching `query`, newest first. Without `requester` every job is
+    /// listed, otherwise only the jobs it submitted.
+    pub async fn list(
+        &self,
+        query: &JobQuery,
+        requester: Option<&VerifyingKey>,
+    ) -> JobListResponse {
+        self.inner.lock().await.list(query, requester)
+    }
+    /// Snapshot of the job with its remembered events newer than `last_event_id`, and a receiver
+    /// for the events published after the snapshot. `None` if there is no such job.
+    pub async fn subscribe(
+        &self,
+        id: Uuid,
+        last_event_id: Option<u64>,
+    ) -> Option<(Job, Vec<PublishedEvent>, Receiver<PublishedEvent>)> {
+        let mut inner = self.inner.lock().await;
+        let job = inner.get_job(id)?;
+        let (replay, rx) = inner.events.subscribe(id, last_event_id);
+        Some((job, replay, rx))
+    }
+    /// Waits until the job finishes and returns it, `None` if it is evicted or discarded first.
+    pub async fn wait_finished(&self, id: Uuid) -> Option<Job> {
+        let (mut job, _, mut rx) = self.subscribe(id, None).await?;
+        while !job.status.is_terminal() {
+            match rx.recv().await {
+                Ok(published) if !published.event.is_terminal() => continue,
+                Err(RecvError::Closed) => return None,
+                // A lagging receiver may have missed the terminal event, so the job is read again
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+            }
+            job = self.get_job(id).await?;
+        }
+        Some(job)
+    }
+    /// Applies `update` to the metrics of the job, e.g. to record a finished stage.
+    pub async fn update_metrics(&self, job_id: Uuid, update: impl FnOnce(&mut JobMetrics)) {
+        self.inner.lock().await.update_metrics(job_id, update).await;
+    }
+    /// Counts the time a worker spent on an attempt of the job against the daily proving time of
+    /// its owner, whether the attempt succeeded or not.
+    pub async fn record_usage(&self, job_id: Uuid, elapsed: Duration) {
+        let mut inner = self.inner.lock().await;
+        let Some(owner) = inner.jobs.get(&job_id).and_then(|job| job.owner) else {
+            return;
+        };
+        inner
+            .proving_usage
+            .record(&owner, elapsed.as_millis() as u64);
+    }
+    /// Handle the tasks of the job append the output of their processes to. Appending only
+    /// locks the log of the job, not the store.
+    pub async fn log(&self, job_id: Uuid) -> JobLog {
+        let mut inner = self.inner.lock().await;
+        // The output of a job that is gone is not kept
+        let buffer = if inner.jobs.contains_key(&job_id) {
+            inner.logs.entry(job_id).or_default().clone()
+        } else {
+            Default::default()
+        };
+        JobLog {
+            job_id,
+            buffer,
+            events: inner.events.sender(job_id),
+        }
+    }
+    /// Captured output of the job, only its owner can read it.
+    pub async fn logs(
+        &self,
+        id: Uuid,
+        requester: Option<&VerifyingKey>,
+    ) -> Result<JobLogsResponse, ProverError> {
+        let buffer = self.inner.lock().await.logs(id, requester)?;
+        let Some(buffer) = buffer else {
+            return Ok(JobLogsResponse {
+                job_id: id,
+                lines: Vec::new(),
+                dropped: 0,
+            });
+        };
+        let buffer = buffer.lock().await;
+        Ok(JobLogsResponse {
+            job_id: id,
+            lines: buffer.lines(),
+            dropped: buffer.dropped(),
+        })
+    }
+    /// Returns the token a worker should watch while executing the job,
+    /// or `None` if the job was cancelled before it started.
+    pub async fn cancellation_token(&self, id: Uuid) -> Option<CancellationToken> {
+        self.inner
+            .lock()
+            .await
+            .cancellation_tokens
+            .get(&id)
+            .cloned()
+    }
+    /// Marks the job as cancelled and stops its task, only the owner of the job can cancel it.
+    pub async fn cancel_job(
+        &self,
+        id: Uuid,
+        requester: Option<&VerifyingKey>,
+    ) -> Result<(), ProverError> {
+        self.inner.lock().await.cancel_job(id, requester).await
+    }
+    /// Forgets a job that was never handed to a worker.
+    pub async fn discard_job(&self, id: Uuid) {
+        self.inner.lock().await.discard_job(id).await;
+    }
+    /// Drops finished jobs according to `policy`, returning how many were evicted.
+    pub async fn evict_expired(&self, policy: &RetentionPolicy) -> usize {
+        self.inner.lock().await.evict_expired(policy).await
+    }
 }
 
 #[derive(Default)]
 struct JobStoreInner {
-    jobs: BTreeMap<u64, Job>,
-    counter: u64,
+    jobs: HashMap<Uuid, Job>,
+    backend: JobBackend,
+    cancellation_tokens: HashMap<Uuid, CancellationToken>,
+    events: EventLog,
+    logs: HashMap<Uuid, Arc<Mutex<LogBuffer>>>,
+    proving_usage: ProvingUsage,
 }
 
 impl JobStoreInner {
-    pub fn create_job(&mut self) -> u64 {
-        let job_id = self.counter;
-        self.counter += 1;
+    // Jobs that were in flight when the server went down have lost their task, so they can only fail
+    async fn restore(&mut self) -> Result<(), ProverError> {
+        for mut job in self.backend.load().await? {
+            if !job.status.is_terminal() {
+                job.status = JobStatus::Failed;
+                job.error = Some(JobError::internal("Job interrupted by server restart"));
+                job.finished = Some(Utc::now());
+                self.backend.save(&job).await?;
+            } else if job.finished.is_none() {
+                job.finished = Some(job.created);
+            }
+            self.jobs.insert(job.id, job);
+        }
+        info!("Restored {} jobs from storage", self.jobs.len());
+        Ok(())
+    }
+    pub async fn create_job(
+        &mut self,
+        owner: Option<VerifyingKey>,
+        kind: JobKind,
+        public: bool,
+    ) -> Uuid {
+        // Random ids, so that the jobs of other keys cannot be found by counting
+        let job_id = Uuid::new_v4();
         let new_job = Job {
             id: job_id,
             status: JobStatus::Pending,
             result: None,
-            created: Instant::now(),
+            created: Utc::now(),
+            finished: None,
+            owner,
+            metrics: JobMetrics {
+                queued_at: Some(Utc::now()),
+                ..Default::default()
+            },
+            error: None,
+            kind: Some(kind),
+            public,
         };
+        if let Err(e) = self.backend.save(&new_job).await {
+            error!("Failed to persist job {}: {}", job_id, e);
+        }
         self.jobs.insert(job_id, new_job);
-        self.clear_old_jobs();
+        self.cancellation_tokens
+            .insert(job_id, CancellationToken::new());
+        self.events.publish(JobEvent::Queued { job_id });
         job_id
     }
-    pub fn update_job_status(&mut self, job_id: u64, status: JobStatus, result: Option<String>) {
+    pub async fn update_job_status(
+        &mut self,
+        job_id: Uuid,
+        status: JobStatus,
+        result: Option<String>,
+    ) {
+        self.set_status(job_id, status, result, None).await;
+    }
+    async fn set_status(
+        &mut self,
+        job_id: Uuid,
+        status: JobStatus,
+        result: Option<String>,
+        error: Option<JobError>,
+    ) {
         if let Some(job) = self.jobs.get_mut(&job_id) {
+            // A cancelled job keeps its status even if its task still reports back
+            if job.status == JobStatus::Cancelled {
+                return;
+            }
+            if status.is_terminal() {
+                self.cancellation_tokens.remove(&job_id);
+            }
+            job.finished = status.is_terminal().then(Utc::now);
+            if status != JobStatus::Pending && job.metrics.started_at.is_none() {
+                job.metrics.started_at = Some(Utc::now());
+            }
+            job.metrics.finished_at = job.finished;
+            let event = status_event(job_id, &job.status, &status, &error);
+            let finishes = !job.status.is_terminal() && status.is_terminal();
             job.status = status;
             job.result = result;
+            job.error = error;
+            if finishes {
+                count_finished(job);
+            }
+            if let Err(e) = self.backend.save(job).await {
+                error!("Failed to persist job {}: {}", job_id, e);
+            }
+            if let Some(event) = event {
+                self.events.publish(event);
+            }
+        }
+    }
+    pub fn get_job(&self, id: Uuid) -> Option<Job> {
+        self.jobs.get(&id).cloned()
+    }
+    fn usage(&self, key: &VerifyingKey) -> KeyUsage {
+        let mut usage = KeyUsage {
+            proving_seconds: self.proving_usage.seconds_today(key),
+            ..Default::default()
+        };
+        for job in self
+            .jobs
+            .values()
+            .filter(|job| job.owner.as_ref() == Some(key))
+        {
+            match job.status {
+                JobStatus::Pending => usage.queued_jobs += 1,
+                JobStatus::Running
+                | JobStatus::Tracing
+                | JobStatus::Proving
+                | JobStatus::Parsing => usage.running_jobs += 1,
+                _ => {}
+            }
+        }
+        usage
+    }
+    fn list(&self, query: &JobQuery, requester: Option<&VerifyingKey>) -> JobListResponse {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let mut matching: Vec<&Job> = self
+            .jobs
+            .values()
+            .filter(|job| requester.is_none() || job.owner.as_ref() == requester)
+            .filter(|job| query.status.is_empty() || query.status.contains(&job.status))
+            .filter(|job| {
+                query.kind.is_empty() || job.kind.is_some_and(|kind| query.kind.contains(&kind))
+            })
+            .filter(|job| {
+                query
+                    .created_after
+                    .map_or(true, |after| job.created >= after)
+            })
+            .filter(|job| {
+                query
+                    .created_before
+                    .map_or(true, |before| job.created < before)
+            })
+            .collect();
+        matching.sort_by(|a, b| b.created.cmp(&a.created).then(b.id.cmp(&a.id)));
+        JobListResponse {
+            jobs: matching
+                .iter()
+                .skip(query.offset)
+                .take(limit)
+                .map(|job| job.summary())
+                .collect(),
+            total: matching.len(),
+            offset: query.offset,
+            limit,
+        }
+    }
+    async fn retry_job(
+        &mut self,
+        job_id: Uuid,
+        error: JobError,
+        retry_in: Duration,
+    ) -> Option<u32> {
+        let job = self.jobs.get_mut(&job_id)?;
+        if job.status.is_terminal() {
+            return None;
+        }
+        let attempt = job.metrics.retries.len() as u32 + 1;
+        let failed_at = Utc::now();
+        job.metrics.retries.push(JobAttempt {
+            attempt,
+            failed_at,
+            retry_at: chrono::Duration::from_std(retry_in)
+                .ok()
+                .and_then(|backoff| failed_at.checked_add_signed(backoff)),
+            error: error.clone(),
+        });
+        job.status = JobStatus::Pending;
+        if let Err(e) = self.backend.save(job).await {
+            error!("Failed to persist job {}: {}", job_id, e);
+        }
+        self.events.publish(JobEvent::Retrying {
+            job_id,
+            attempt,
+            error,
+            retry_in_ms: retry_in.as_millis() as u64,
+        });
+        Some(attempt)
+    }
+    async fn update_metrics(&mut self, job_id: Uuid, update: impl FnOnce(&mut JobMetrics)) {
+        if let Some(job) = self.jobs.get_mut(&job_id) {
+            let recorded = job.metrics.stages.len();
+            update(&mut job.metrics);
+            job.metrics.stages[recorded..]
+                .iter()
+                .for_each(|stage| METRICS.observe_stage(stage));
+            if let Err(e) = self.backend.save(job).await {
+                error!("Failed to persist job {}: {}", job_id, e);
+            }
+        }
+    }
+    fn logs(
+        &self,
+        id: Uuid,
+        requester: Option<&VerifyingKey>,
+    ) -> Result<Option<Arc<Mutex<LogBuffer>>>, ProverError> {
+        let job = self
+            .jobs
+            .get(&id)
+            .ok_or_else(|| ProverError::CustomError("Job not found".to_string()))?;
+        if job.owner.is_some() && job.owner.as_ref() != requester {
+            return Err(ProverError::Auth(AuthError::Forbidden));
+        }
+        Ok(self.logs.get(&id).cloned())
+    }
+    pub async fn cancel_job(
+        &mut self,
+        id: Uuid,
+        requester: Option<&VerifyingKey>,
+    ) -> Result<(), ProverError> {
+        let job = self
+            .jobs
+            .get_mut(&id)
+            .ok_or_else(|| ProverError::CustomError("Job not found".to_string()))?;
+        if job.owner.is_some() && job.owner.as_ref() != requester {
+            return Err(ProverError::Auth(AuthError::Forbidden));
+        }
+        if job.status.is_terminal() {
+            return Err(ProverError::JobAlreadyFinished(id));
+        }
+        let error = JobError::new(JobErrorCode::Cancelled, "Job was cancelled");
+        job.status = JobStatus::Cancelled;
+        job.error = Some(error.clone());
+        job.finished = Some(Utc::now());
+        job.metrics.finished_at = job.finished;
+        count_finished(job);
+        if let Err(e) = self.backend.save(job).await {
+            error!("Failed to persist job {}: {}", id, e);
+        }
+        if let Some(token) = self.cancellation_tokens.remove(&id) {
+            token.cancel();
         }
-        self.clear_old_jobs()
+        self.events.publish(JobEvent::Failed {
+            job_id: id,
+            status: JobStatus::Cancelled,
+            error: Some(error),
+        });
+        Ok(())
     }
-    pub fn get_job(&mut self, id: u64) -> Option<Job> {
-        let job = self.jobs.get(&id).cloned();
-        self.clear_old_jobs();
-        job
+    async fn discard_job(&mut self, id: Uuid) {
+        self.jobs.remove(&id);
+        self.cancellation_tokens.remove(&id);
+        self.events.forget(id);
+        self.logs.remove(&id);
+        if let Err(e) = self.backend.remove(id).await {
+            error!("Failed to remove job {} from storage: {}", id, e);
+        }
     }
-    // Clear old jobs so that the memory doesn't go balistic if the server runs for a long time
-    fn clear_old_jobs(&mut self) {
-        let expiry_duration = Duration::from_secs(60 * 60); // 1 hour //TODO: make this configurable
-        while let Some((id, job)) = self.jobs.pop_first() {
-            if job.created.elapsed() < expiry_duration {
-                self.jobs.insert(id, job);
-                break;
+    // Evicts jobs past their TTL, then the oldest finished jobs while results exceed the size budget
+    async fn evict_expired(&mut self, policy: &RetentionPolicy) -> usize {
+        let now = Utc::now();
+        let mut evicted: HashSet<Uuid> = self
+            .jobs
+            .values()
+            .filter(|job| policy.is_expired(job, now))
+            .map(|job| job.id)
+            .collect();
+
+        // Once results exceed the size budget, evict the oldest finished jobs first
+        if let Some(max_result_bytes) = policy.max_result_bytes {
+            let mut total_size: usize = self
+                .jobs
+                .values()
+                .filter(|job| !evicted.contains(&job.id))
+                .map(Job::result_size)
+                .sum();
+            let mut finished: Vec<&Job> = self
+                .jobs
+                .values()
+                .filter(|job| job.finished.is_some() && !evicted.contains(&job.id))
+                .collect();
+            finished.sort_by_key(|job| job.finished);
+            for job in finished {
+                if total_size <= max_result_bytes {
+                    break;
+                }
+                total_size -= job.result_size();
+                evicted.insert(job.id);
             }
         }
+
+        for id in evicted.iter() {
+            self.jobs.remove(id);
+            self.events.forget(*id);
+            self.logs.remove(id);
+            if let Err(e) = self.backend.remove(*id).await {
+                error!("Failed to remove job {} from storage: {}", id, e);
+            }
+        }
+        evicted.len()
+    }
+}
+
+/// Appends the output of the processes of a job to its log, each line is also published as a
+/// `log` event.
+#[derive(Clone)]
+pub struct JobLog {
+    pub job_id: Uuid,
+    buffer: Arc<Mutex<LogBuffer>>,
+    events: EventSender,
+}
+
+impl JobLog {
+    pub async fn append(&self, line: String) {
+        let line = self.buffer.lock().await.push(line);
+        self.events.send(JobEvent::Log {
+            job_id: self.job_id,
+            line,
+        });
+    }
+}
+
+// Counts a job once, on the transition that finished it. Jobs restored from storage that predate
+// job kinds are not counted.
+fn count_finished(job: &Job) {
+    if let Some(kind) = job.kind {
+        METRICS.job_finished(kind.as_str(), &job.status);
+    }
+}
+
+// Event announcing a status change, leaving `Pending` means a worker started the job
+fn status_event(
+    job_id: Uuid,
+    previous: &JobStatus,
+    status: &JobStatus,
+    error: &Option<JobError>,
+) -> Option<JobEvent> {
+    match status {
+        JobStatus::Completed => Some(JobEvent::Completed { job_id }),
+        JobStatus::Failed | JobStatus::TimedOut | JobStatus::Cancelled => Some(JobEvent::Failed {
+            job_id,
+            status: status.clone(),
+            error: error.clone(),
+        }),
+        _ if status == previous => None,
+        _ if *previous == JobStatus::Pending => Some(JobEvent::Started {
+            job_id,
+            status: status.clone(),
+        }),
+        _ => Some(JobEvent::Stage {
+            job_id,
+            status: status.clone(),
+        }),
+    }
+}
+
+// A proof waits in the running pool for its trace, then in the proving pool for the prover
+async fn queue_position(app_state: &AppState, job: &Job) -> Option<usize> {
+    let position = app_state
+        .proving_thread_pool
+        .lock()
+        .await
+        .queue_position(job.id)
+        .await;
+    match position {
+        Some(position) => Some(position),
+        None => {
+            app_state
+                .running_thread_pool
+                .lock()
+                .await
+                .queue_position(job.id)
+                .await
+        }
     }
 }
 
+/// Whether `signer` may read the job, admins may read every job.
+pub fn may_read(app_state: &AppState, signer: &Signer, job: &Job) -> bool {
+    job.readable_by(signer.0.as_ref()) || app_state.is_admin(signer)
+}
+
+/// The job, if `signer` may read it.
+pub async fn readable_job(
+    app_state: &AppState,
+    signer: &Signer,
+    id: Uuid,
+) -> Result<Job, ProverError> {
+    let job = app_state
+        .job_store
+        .get_job(id)
+        .await
+        .ok_or_else(|| ProverError::CustomError("Job not found".to_string()))?;
+    if !may_read(app_state, signer, &job) {
+        return Err(ProverError::Auth(AuthError::Forbidden));
+    }
+    Ok(job)
+}
+
 pub async fn get_job(
-    Path(id): Path<u64>,
+    Path(id): Path<Uuid>,
     State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
 ) -> Result<impl IntoResponse, ProverError> {
-    if let Some(job) = app_state.job_store.get_job(id).await {
-        let (status, response) = match job.status {
-            JobStatus::Pending | JobStatus::Running => (
-                StatusCode::OK,
-                Json(JobResponse::InProgress {
-                    id: job.id,
-                    status: job.status.clone(),
-                }),
-            ),
-            JobStatus::Completed => (
-                StatusCode::OK,
-                Json(JobResponse::Completed {
-                    status: job.status.clone(),
-                    result: serde_json::from_str(&job.result.clone().unwrap_or_default())?,
-                }),
-            ),
-            JobStatus::Failed => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JobResponse::Failed {
-                    error: job
-                        .result
-                        .clone()
-                        .unwrap_or_else(|| "Unknown error".to_string()),
-                }),
-            ),
-            JobStatus::Unknown => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JobResponse::Failed {
-                    error: "Unknown error".to_string(),
-                }),
-            ),
+    let job = readable_job(&app_state, &signer, id).await?;
+    let (status, response) = job_response(&app_state, &job).await?;
+    Ok((status, Json(response)).into_response())
+}
+
+/// Serves jobs submitted with a public result without a signature, other jobs are not found.
+pub async fn get_public_job(
+    Path(id): Path<Uuid>,
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ProverError> {
+    match app_state.job_store.get_job(id).await {
+        Some(job) if job.public => {
+            let (status, response) = job_response(&app_state, &job).await?;
+            Ok((status, Json(response)).into_response())
+        }
+        _ => Err(ProverError::CustomError("Job not found".to_string())),
+    }
+}
+
+/// Response describing the job and the HTTP status it is served with.
+pub async fn job_response(
+    app_state: &AppState,
+    job: &Job,
+) -> Result<(StatusCode, JobResponse), ProverError> {
+    let response = match job.status {
+        JobStatus::Pending
+        | JobStatus::Running
+        | JobStatus::Tracing
+        | JobStatus::Proving
+        | JobStatus::Parsing => (
+            StatusCode::OK,
+            JobResponse::InProgress {
+                id: job.id,
+                status: job.status.clone(),
+                queue_position: queue_position(app_state, job).await,
+                retry_at: job.retry_at(Utc::now()),
+                metrics: job.metrics.clone(),
+            },
+        ),
+        JobStatus::Completed => (
+            StatusCode::OK,
+            JobResponse::Completed {
+                status: job.status.clone(),
+                result: serde_json::from_str(&job.result.clone().unwrap_or_default())?,
+                metrics: job.metrics.clone(),
+            },
+        ),
+        JobStatus::TimedOut => (
+            StatusCode::GATEWAY_TIMEOUT,
+            JobResponse::TimedOut {
+                status: job.status.clone(),
+                error: job.job_error(),
+                metrics: job.metrics.clone(),
+            },
+        ),
+        JobStatus::Failed | JobStatus::Cancelled | JobStatus::Unknown => {
+            let error = job.job_error();
+            (
+                StatusCode::from_u16(error.code.http_status())
+                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+                JobResponse::Failed {
+                    error,
+                    metrics: job.metrics.clone(),
+                },
+            )
+        }
+    };
+    Ok(response)
+}
+
+pub async fn cancel_job(
+    Path(id): Path<Uuid>,
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+) -> Result<impl IntoResponse, ProverError> {
+    app_state
+        .job_store
+        .cancel_job(id, signer.0.as_ref())
+        .await?;
+    // A job still waiting for a worker gives its queue slot back right away
+    for pool in [
+        &app_state.running_thread_pool,
+        &app_state.proving_thread_pool,
+    ] {
+        if pool.lock().await.remove_queued(id).await {
+            break;
+        }
+    }
+    info!("Job {} cancelled", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_job_logs(
+    Path(id): Path<Uuid>,
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+) -> Result<impl IntoResponse, ProverError> {
+    let logs = app_state.job_store.logs(id, signer.0.as_ref()).await?;
+    Ok(Json(logs))
+}
+
+/// Lists the jobs of the signing key, admins and open servers list every job.
+pub async fn list_jobs(
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+    Query(query): Query<JobQuery>,
+) -> Result<impl IntoResponse, ProverError> {
+    let requester = signer.0.filter(|_| !app_state.is_admin(&signer));
+    let jobs = app_state.job_store.list(&query, requester.as_ref()).await;
+    Ok(Json(jobs))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::models::StageMetrics;
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+
+    fn key(seed: u8) -> VerifyingKey {
+        SigningKey::from_bytes(&[seed; 32]).verifying_key()
+    }
+
+    fn signer(key: VerifyingKey) -> Signer {
+        Signer(Some(key), Default::default())
+    }
+
+    // Jobs are listed by creation time, so consecutive jobs must not share a timestamp
+    async fn tick() {
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+
+    #[tokio::test]
+    async fn test_only_owner_reads_private_jobs() {
+        let job_store = JobStore::default();
+        let (alice, bob) = (key(1), key(2));
+        let private = job_store
+            .create_job(&signer(alice), JobKind::Prove, false)
+            .await
+            .unwrap();
+        let public = job_store
+            .create_job(&signer(alice), JobKind::Prove, true)
+            .await
+            .unwrap();
+        let open = job_store
+            .create_job(&Signer::default(), JobKind::Run, false)
+            .await
+            .unwrap();
+
+        let private = job_store.get_job(private).await.unwrap();
+        assert!(private.readable_by(Some(&alice)));
+        assert!(!private.readable_by(Some(&bob)));
+        assert!(!private.readable_by(None));
+        let public = job_store.get_job(public).await.unwrap();
+        assert!(public.readable_by(Some(&bob)));
+        let open = job_store.get_job(open).await.unwrap();
+        assert!(open.readable_by(Some(&bob)));
+    }
+
+    #[tokio::test]
+    async fn test_jobs_over_the_limit_are_not_created() {
+        let job_store = JobStore::default();
+        let mut limited = signer(key(1));
+        limited.1.quota.max_concurrent_jobs = Some(1);
+        let job_id = job_store
+            .create_job(&limited, JobKind::Run, false)
+            .await
+            .unwrap();
+        assert!(matches!(
+            job_store.create_job(&limited, JobKind::Run, false).await,
+            Err(ProverError::QuotaExceeded(..))
+        ));
+        assert_eq!(job_store.list(&JobQuery::default(), None).await.total, 1);
+
+        job_store
+            .update_job_status(job_id, JobStatus::Completed, None)
+            .await;
+        assert!(job_store
+            .create_job(&limited, JobKind::Run, false)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_owner_cancels_a_pending_job() {
+        let job_store = JobStore::default();
+        let alice = key(1);
+        let job_id = job_store
+            .create_job(&signer(alice), JobKind::Prove, false)
+            .await
+            .unwrap();
+        let token = job_store.cancellation_token(job_id).await.unwrap();
+
+        job_store.cancel_job(job_id, Some(&alice)).await.unwrap();
+        assert!(token.is_cancelled());
+        assert!(job_store.cancellation_token(job_id).await.is_none());
+        let job = job_store.get_job(job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert_eq!(job.job_error().code, JobErrorCode::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_other_keys_cannot_cancel_a_job() {
+        let job_store = JobStore::default();
+        let job_id = job_store
+            .create_job(&signer(key(1)), JobKind::Prove, false)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            job_store.cancel_job(job_id, Some(&key(2))).await,
+            Err(ProverError::Auth(AuthError::Forbidden))
+        ));
+        let job = job_store.get_job(job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_finished_jobs_cannot_be_cancelled() {
+        let job_store = JobStore::default();
+        let alice = key(1);
+        let job_id = job_store
+            .create_job(&signer(alice), JobKind::Run, false)
+            .await
+            .unwrap();
+        job_store
+            .update_job_status(job_id, JobStatus::Completed, Some("{}".to_string()))
+            .await;
+
+        assert!(matches!(
+            job_store.cancel_job(job_id, Some(&alice)).await,
+            Err(ProverError::JobAlreadyFinished(id)) if id == job_id
+        ));
+        let job = job_store.get_job(job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_retried_jobs_report_their_next_attempt() {
+        let job_store = JobStore::default();
+        let job_id = job_store
+            .create_job(&Signer::default(), JobKind::Run, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            job_store
+                .get_job(job_id)
+                .await
+                .unwrap()
+                .retry_at(Utc::now()),
+            None
+        );
+
+        job_store
+            .update_job_status(job_id, JobStatus::Running, None)
+            .await;
+        let error = JobError::new(JobErrorCode::OutOfResources, "Runner was killed");
+        let before = Utc::now();
+        job_store
+            .retry_job(job_id, error, Duration::from_secs(30))
+            .await
+            .unwrap();
+        let job = job_store.get_job(job_id).await.unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        let retry_at = job.retry_at(before).unwrap();
+        assert!(retry_at >= before + chrono::Duration::seconds(30));
+        // Once the backoff passed, or the next attempt started, there is nothing to wait for
+        assert_eq!(job.retry_at(retry_at), None);
+        job_store
+            .update_job_status(job_id, JobStatus::Running, None)
+            .await;
+        assert_eq!(
+            job_store.get_job(job_id).await.unwrap().retry_at(before),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jobs_are_counted_when_they_finish() {
+        let finished = |status: &str| {
+            METRICS
+                .jobs_finished
+                .with_label_values(&["snos", status])
+                .get()
+        };
+        let (completed, failed) = (finished("Completed"), finished("Failed"));
+        let job_store = JobStore::default();
+        let job_id = job_store
+            .create_job(&Signer::default(), JobKind::Snos, false)
+            .await
+            .unwrap();
+
+        job_store
+            .update_job_status(job_id, JobStatus::Running, None)
+            .await;
+        assert_eq!(finished("Completed"), completed);
+        job_store
+            .update_job_status(job_id, JobStatus::Completed, Some("{}".to_string()))
+            .await;
+        assert_eq!(finished("Completed"), completed + 1);
+        // A late report of the finished job is not counted again
+        job_store
+            .fail_job(
+                job_id,
+                JobError::new(JobErrorCode::Internal, "Late failure"),
+            )
+            .await;
+        assert_eq!(finished("Completed"), completed + 1);
+        assert_eq!(finished("Failed"), failed);
+    }
+
+    #[tokio::test]
+    async fn test_recorded_stages_keep_the_peak_memory() {
+        let job_store = JobStore::default();
+        let job_id = job_store
+            .create_job(&Signer::default(), JobKind::Prove, false)
+            .await
+            .unwrap();
+        let observed = || {
+            METRICS
+                .stage_duration
+                .with_label_values(&["test_trace"])
+                .get_sample_count()
+        };
+        let before = observed();
+
+        job_store
+            .update_metrics(job_id, |metrics| {
+                metrics.record_stage(StageMetrics::new(
+                    "test_trace",
+                    Duration::from_millis(1500),
+                    Some(2048),
+                ));
+            })
+            .await;
+        job_store
+            .update_metrics(job_id, |metrics| {
+                metrics.record_stage(StageMetrics::new(
+                    "test_prove",
+                    Duration::from_secs(3),
+                    Some(1024),
+                ));
+                metrics.record_stage(StageMetrics::new("test_parse", Duration::ZERO, None));
+            })
+            .await;
+
+        let metrics = job_store.get_job(job_id).await.unwrap().metrics;
+        let stages: Vec<(&str, u64)> = metrics
+            .stages
+            .iter()
+            .map(|stage| (stage.stage.as_str(), stage.duration_ms))
+            .collect();
+        assert_eq!(
+            stages,
+            vec![
+                ("test_trace", 1500),
+                ("test_prove", 3000),
+                ("test_parse", 0)
+            ]
+        );
+        assert_eq!(metrics.peak_rss_kib, Some(2048));
+        // Every stage is observed once, when it is recorded
+        assert_eq!(observed(), before + 1);
+    }
+
+    #[tokio::test]
+    async fn test_log_lines_are_kept_and_published() {
+        let job_store = JobStore::default();
+        let alice = key(1);
+        let job_id = job_store
+            .create_job(&signer(alice), JobKind::Run, false)
+            .await
+            .unwrap();
+        let (_, _, mut rx) = job_store.subscribe(job_id, None).await.unwrap();
+        let log = job_store.log(job_id).await;
+        log.append("first".to_string()).await;
+        log.append("second".to_string()).await;
+
+        let logs = job_store.logs(job_id, Some(&alice)).await.unwrap();
+        assert_eq!(logs.lines, vec!["first", "second"]);
+        assert!(job_store.logs(job_id, Some(&key(2))).await.is_err());
+        let published = rx.recv().await.unwrap();
+        assert!(matches!(published.event, JobEvent::Log { line, .. } if line == "first"));
+    }
+
+    #[tokio::test]
+    async fn test_list_only_returns_own_jobs() {
+        let job_store = JobStore::default();
+        let (alice, bob) = (key(1), key(2));
+        let proof = job_store
+            .create_job(&signer(alice), JobKind::Prove, false)
+            .await
+            .unwrap();
+        tick().await;
+        let run = job_store
+            .create_job(&signer(alice), JobKind::Run, false)
+            .await
+            .unwrap();
+        job_store
+            .create_job(&signer(bob), JobKind::Prove, false)
+            .await
+            .unwrap();
+        job_store
+            .fail_job(run, JobError::internal("Runner crashed"))
+            .await;
+
+        let all = job_store.list(&JobQuery::default(), None).await;
+        assert_eq!(all.total, 3);
+
+        let own = job_store.list(&JobQuery::default(), Some(&alice)).await;
+        let ids: Vec<Uuid> = own.jobs.iter().map(|job| job.job_id).collect();
+        assert_eq!(ids, vec![run, proof]);
+        assert!(own.jobs[0].error.is_some());
+
+        let query = JobQuery {
+            status: vec![JobStatus::Failed],
+            ..Default::default()
+        };
+        let failed = job_store.list(&query, Some(&alice)).await;
+        assert_eq!(failed.total, 1);
+        assert_eq!(failed.jobs[0].kind, Some(JobKind::Run));
+    }
+
+    #[tokio::test]
+    async fn test_list_pages_and_time_range() {
+        let job_store = JobStore::default();
+        let start = Utc::now();
+        let mut created = Vec::new();
+        for _ in 0..5 {
+            created.push(
+                job_store
+                    .create_job(&Signer::default(), JobKind::Prove, false)
+                    .await
+                    .unwrap(),
+            );
+            tick().await;
+        }
+        let query = JobQuery {
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = job_store.list(&query, None).await;
+        assert_eq!(page.total, 5);
+        let ids: Vec<Uuid> = page.jobs.iter().map(|job| job.job_id).collect();
+        assert_eq!(ids, vec![created[3], created[2]]);
+
+        let query = JobQuery {
+            created_before: Some(start),
+            ..Default::default()
         };
-        Ok((status, response).into_response())
-    } else {
-        Err(ProverError::CustomError("Job not found".to_string()))
+        assert_eq!(job_store.list(&query, None).await.total, 0);
     }
 }
@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+/// Lines kept per job, older lines are dropped once a job prints more.
+pub const MAX_LOG_LINES: usize = 1000;
+/// Longer lines are cut, so a child printing a huge blob cannot exhaust the buffer.
+pub const MAX_LINE_BYTES: usize = 4096;
+
+/// The most recent output of the processes of a job.
+#[derive(Debug, Default)]
+pub struct LogBuffer {
+    lines: VecDeque<String>,
+    dropped: usize,
+}
+
+impl LogBuffer {
+    /// Appends a line, returning it as it was stored.
+    pub fn push(&mut self, mut line: String) -> String {
+        truncate_line(&mut line);
+        if self.lines.len() == MAX_LOG_LINES {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(line.clone());
+        line
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.iter().cloned().collect()
+    }
+
+    /// Lines that no longer fit in the buffer.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+fn truncate_line(line: &mut String) {
+    if line.len() <= MAX_LINE_BYTES {
+        return;
+    }
+    let mut end = MAX_LINE_BYTES;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line.truncate(end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_keeps_latest_lines() {
+        let mut buffer = LogBuffer::default();
+        for i in 0..MAX_LOG_LINES + 5 {
+            buffer.push(i.to_string());
+        }
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), MAX_LOG_LINES);
+        assert_eq!(lines[0], "5");
+        assert_eq!(buffer.dropped(), 5);
+    }
+
+    #[test]
+    fn test_long_lines_are_truncated() {
+        let mut buffer = LogBuffer::default();
+        let stored = buffer.push("é".repeat(MAX_LINE_BYTES));
+        assert!(stored.len() <= MAX_LINE_BYTES);
+        assert!(stored.chars().all(|c| c == 'é'));
+    }
+}
@@ -1,3 +1,10 @@
+pub mod batch;
+pub mod cache;
 pub mod config;
+pub mod events;
 pub mod job;
+pub mod logs;
+pub mod retention;
 pub mod shutdown;
+pub mod storage;
+pub mod timeout;
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Duration, Utc};
+use common::models::JobStatus;
+use tokio::{spawn, task::JoinHandle};
+use tracing::info;
+
+use crate::Args;
+
+use super::{
+    batch::BatchStore,
+    job::{Job, JobStore},
+};
+
+/// Decides how long finished jobs and their results are kept around.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub completed_ttl: Duration,
+    pub failed_ttl: Duration,
+    pub max_result_bytes: Option<usize>,
+    pub sweep_interval: std::time::Duration,
+}
+
+impl From<&Args> for RetentionPolicy {
+    fn from(args: &Args) -> Self {
+        Self {
+            completed_ttl: Duration::seconds(args.completed_job_ttl as i64),
+            failed_ttl: Duration::seconds(args.failed_job_ttl as i64),
+            max_result_bytes: args.max_result_bytes,
+            sweep_interval: std::time::Duration::from_secs(args.retention_sweep_interval),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    fn ttl(&self, status: &JobStatus) -> Duration {
+        match status {
+            JobStatus::Failed | JobStatus::TimedOut => self.failed_ttl,
+            _ => self.completed_ttl,
+        }
+    }
+    /// Jobs are only ever expired once they are finished, the TTL is measured from completion.
+    pub fn is_expired(&self, job: &Job, now: DateTime<Utc>) -> bool {
+        job.finished
+            .is_some_and(|finished| now.signed_duration_since(finished) > self.ttl(&job.status))
+    }
+}
+
+pub fn spawn_sweeper(
+    job_store: JobStore,
+    batches: BatchStore,
+    policy: RetentionPolicy,
+) -> JoinHandle<()> {
+    spawn(async move {
+        let mut interval = tokio::time::interval(policy.sweep_interval);
+        loop {
+            interval.tick().await;
+            let evicted = job_store.evict_expired(&policy).await;
+            if evicted > 0 {
+                info!("Evicted {} expired jobs", evicted);
+            }
+            let pruned = batches.prune(&job_store).await;
+            if pruned > 0 {
+                info!("Forgot {} batches without jobs", pruned);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy {
+            completed_ttl: Duration::seconds(60),
+            failed_ttl: Duration::seconds(600),
+            max_result_bytes: None,
+            sweep_interval: std::time::Duration::from_secs(1),
+        }
+    }
+
+    fn job(status: JobStatus, finished: Option<DateTime<Utc>>) -> Job {
+        Job {
+            id: Uuid::nil(),
+            status,
+            result: None,
+            created: Utc::now() - Duration::days(1),
+            finished,
+            owner: None,
+            metrics: Default::default(),
+            error: None,
+            kind: None,
+            public: false,
+        }
+    }
+
+    #[test]
+    fn test_unfinished_jobs_never_expire() {
+        let job = job(JobStatus::Running, None);
+        assert!(!policy().is_expired(&job, Utc::now()));
+    }
+
+    #[test]
+    fn test_ttl_is_measured_from_completion() {
+        let now = Utc::now();
+        let job = job(JobStatus::Completed, Some(now - Duration::seconds(30)));
+        assert!(!policy().is_expired(&job, now));
+        assert!(policy().is_expired(&job, now + Duration::seconds(31)));
+    }
+
+    #[test]
+    fn test_failed_jobs_are_kept_longer() {
+        let now = Utc::now();
+        let job = job(JobStatus::Failed, Some(now - Duration::seconds(120)));
+        assert!(!policy().is_expired(&job, now));
+        assert!(policy().is_expired(&job, now + Duration::seconds(600)));
+    }
+}
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+use tokio::fs;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::errors::ProverError;
+
+use super::job::Job;
+
+pub(crate) trait JobStorage {
+    async fn load(&self) -> Result<Vec<Job>, ProverError>;
+    async fn save(&self, job: &Job) -> Result<(), ProverError>;
+    async fn remove(&self, id: Uuid) -> Result<(), ProverError>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum JobBackend {
+    #[default]
+    Memory,
+    Persistent(FileJobStorage),
+}
+
+impl JobStorage for JobBackend {
+    async fn load(&self) -> Result<Vec<Job>, ProverError> {
+        match self {
+            JobBackend::Memory => Ok(Vec::new()),
+            JobBackend::Persistent(storage) => storage.load().await,
+        }
+    }
+    async fn save(&self, job: &Job) -> Result<(), ProverError> {
+        match self {
+            JobBackend::Memory => Ok(()),
+            JobBackend::Persistent(storage) => storage.save(job).await,
+        }
+    }
+    async fn remove(&self, id: Uuid) -> Result<(), ProverError> {
+        match self {
+            JobBackend::Memory => Ok(()),
+            JobBackend::Persistent(storage) => storage.remove(id).await,
+        }
+    }
+}
+
+/// Stores every job as a separate JSON document inside a directory.
+#[derive(Debug, Clone)]
+pub struct FileJobStorage(PathBuf);
+
+impl FileJobStorage {
+    pub async fn new(path: PathBuf) -> Result<Self, ProverError> {
+        fs::create_dir_all(&path).await?;
+        Ok(Self(path))
+    }
+    fn job_path(&self, id: Uuid) -> PathBuf {
+        self.0.join(format!("{}.json", id))
+    }
+    // Jobs stored before job ids were UUIDs have a numeric id. They get a new id and are rewritten
+    // under it, keeping their owner, so they can still be read and are evicted like other jobs.
+    async fn migrate(&self, path: &Path, contents: &[u8]) -> Result<Option<Job>, ProverError> {
+        let Ok(mut record) = serde_json::from_slice::<Value>(contents) else {
+            return Ok(None);
+        };
+        let Some(old_id) = record.get("id").and_then(Value::as_u64) else {
+            return Ok(None);
+        };
+        let id = Uuid::new_v4();
+        record["id"] = json!(id);
+        let Ok(job) = serde_json::from_value::<Job>(record) else {
+            return Ok(None);
+        };
+        self.save(&job).await?;
+        fs::remove_file(path).await?;
+        info!("Migrated job {} stored with a numeric id to {}", old_id, id);
+        Ok(Some(job))
+    }
+}
+
+impl JobStorage for FileJobStorage {
+    async fn load(&self) -> Result<Vec<Job>, ProverError> {
+        // Listed up front, migrating a job writes a new record into the directory
+        let mut paths = Vec::new();
+        let mut entries = fs::read_dir(&self.0).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+                paths.push(path);
+            }
+        }
+        let mut jobs = Vec::new();
+        for path in paths {
+            let contents = fs::read(&path).await?;
+            match serde_json::from_slice::<Job>(&contents) {
+                Ok(job) => jobs.push(job),
+                Err(e) => match self.migrate(&path, &contents).await? {
+                    Some(job) => jobs.push(job),
+                    None => warn!("Skipping unreadable job record {}: {}", path.display(), e),
+                },
+            }
+        }
+        Ok(jobs)
+    }
+    async fn save(&self, job: &Job) -> Result<(), ProverError> {
+        let path = self.job_path(job.id);
+        // Write to a temporary file first so a crash never leaves a truncated record behind
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(job)?).await?;
+        fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+    async fn remove(&self, id: Uuid) -> Result<(), ProverError> {
+        match fs::remove_file(self.job_path(id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use common::models::JobStatus;
+    use ed25519_dalek::SigningKey;
+    use tempfile::tempdir;
+
+    fn job(id: Uuid, status: JobStatus) -> Job {
+        Job {
+            id,
+            status,
+            result: Some(format!("result {}", id)),
+            created: Utc::now(),
+            finished: None,
+            owner: None,
+            metrics: Default::default(),
+            error: None,
+            kind: None,
+            public: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_jobs() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileJobStorage::new(temp_dir.path().join("jobs"))
+            .await
+            .unwrap();
+
+        let (completed, running) = (Uuid::new_v4(), Uuid::new_v4());
+        storage
+            .save(&job(completed, JobStatus::Completed))
+            .await
+            .unwrap();
+        storage
+            .save(&job(running, JobStatus::Running))
+            .await
+            .unwrap();
+
+        let jobs = storage.load().await.unwrap();
+        assert_eq!(jobs.len(), 2);
+        let loaded = jobs.iter().find(|job| job.id == running).unwrap();
+        assert_eq!(loaded.status, JobStatus::Running);
+        assert_eq!(
+            loaded.result.as_deref(),
+            Some(format!("result {}", running).as_str())
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_jobs_with_numeric_ids_are_migrated() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileJobStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let owner = SigningKey::from_bytes(&[1; 32]).verifying_key();
+        let mut record = serde_json::to_value(Job {
+            owner: Some(owner),
+            ..job(Uuid::nil(), JobStatus::Completed)
+        })
+        .unwrap();
+        record["id"] = json!(7);
+        let legacy = temp_dir.path().join("7.json");
+        std::fs::write(&legacy, serde_json::to_vec(&record).unwrap()).unwrap();
+
+        let jobs = storage.load().await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_ne!(jobs[0].id, Uuid::nil());
+        assert_eq!(jobs[0].owner, Some(owner));
+        assert_eq!(jobs[0].status, JobStatus::Completed);
+        assert!(!legacy.exists());
+        assert!(storage.job_path(jobs[0].id).exists());
+        // The migrated record is loaded as it is on the next start
+        let reloaded = storage.load().await.unwrap();
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(reloaded[0].id, jobs[0].id);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_job() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileJobStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+
+        let id = Uuid::new_v4();
+        storage.save(&job(id, JobStatus::Failed)).await.unwrap();
+        storage.remove(id).await.unwrap();
+        // Removing a job that is already gone is not an error
+        storage.remove(id).await.unwrap();
+
+        assert!(storage.load().await.unwrap().is_empty());
+
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use crate::Args;
+
+/// Upper bounds on how long a task of each kind may execute before it is killed.
+#[derive(Debug, Clone, Copy)]
+pub struct TaskTimeouts {
+    pub run: Duration,
+    pub prove: Duration,
+    pub layout_bridge: Duration,
+    pub snos: Duration,
+}
+
+impl From<&Args> for TaskTimeouts {
+    fn from(args: &Args) -> Self {
+        Self {
+            run: Duration::from_secs(args.run_timeout),
+            prove: Duration::from_secs(args.prove_timeout),
+            layout_bridge: Duration::from_secs(args.layout_bridge_timeout),
+            snos: Duration::from_secs(args.snos_timeout),
+        }
+    }
+}
+
+impl TaskTimeouts {
+    /// Clients may ask for a shorter deadline than the server limit, but never a longer one.
+    pub fn capped(limit: Duration, requested: Option<u64>) -> Duration {
+        requested.map_or(limit, |secs| limit.min(Duration::from_secs(secs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_timeout_is_used_when_none_requested() {
+        let limit = Duration::from_secs(60);
+        assert_eq!(TaskTimeouts::capped(limit, None), limit);
+    }
+
+    #[test]
+    fn test_requested_timeout_is_capped_by_server() {
+        let limit = Duration::from_secs(60);
+        assert_eq!(
+            TaskTimeouts::capped(limit, Some(10)),
+            Duration::from_secs(10)
+        );
+        assert_eq!(TaskTimeouts::capped(limit, Some(600)), limit);
+    }
+}
@@ -0,0 +1,208 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::IntoResponse,
+    Extension,
+};
+use chrono::Utc;
+use common::ws::{WsRequest, WsResponse};
+use futures::{SinkExt, StreamExt};
+use std::pin::pin;
+use tokio::{
+    select,
+    sync::mpsc::{self, Sender},
+    task::JoinSet,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    auth::{auth_errors::AuthError, authorizer::AuthorizationProvider, key_revoked, Signer},
+    errors::ProverError,
+    layout_bridge, prove, run,
+    server::AppState,
+    sse::follow_job,
+    utils::job::{job_response, readable_job},
+};
+
+/// Responses waiting to be written to a slow client before its events are held back.
+const OUTGOING_CAPACITY: usize = 256;
+
+/// Lets an authenticated client submit jobs, follow their events and fetch their results over
+/// one connection. The upgrade request is signed like any other request without a body.
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(app_state): State<AppState>,
+    Extension(signer): Extension<Signer>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, app_state, signer))
+}
+
+async fn handle_socket(socket: WebSocket, app_state: AppState, signer: Signer) {
+    info!("WebSocket client connected");
+    let (mut sink, mut stream) = socket.split();
+    let (tx, mut rx) = mpsc::channel::<WsResponse>(OUTGOING_CAPACITY);
+
+    // Requests, submissions and event forwarding all answer through one writer
+    let writer = tokio::spawn(async move {
+        while let Some(response) = rx.recv().await {
+            let Ok(text) = serde_json::to_string(&response) else {
+                continue;
+            };
+            if sink.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscriptions = JoinSet::new();
+    let mut revoked = pin!(key_revoked(app_state.authorizer.clone(), signer.0));
+    loop {
+        let message = select! {
+            message = stream.next() => message,
+            () = &mut revoked => {
+                info!("Closing WebSocket connection, its key was revoked or expired");
+                let _ = tx
+                    .send(WsResponse::Error {
+                        request_id: None,
+                        error: AuthError::Unauthorized.to_string(),
+                    })
+                    .await;
+                break;
+            }
+        };
+        let Some(Ok(message)) = message else {
+            break;
+        };
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let (response, follow) = match serde_json::from_str::<WsRequest>(&text) {
+            Ok(request) => {
+                let request_id = request.request_id();
+                match handle_request(request, &app_state, &signer).await {
+                    Ok(handled) => handled,
+                    Err(e) => (
+                        WsResponse::Error {
+                            request_id: Some(request_id),
+                            error: e.to_string(),
+                        },
+                        None,
+                    ),
+                }
+            }
+            Err(e) => (
+                WsResponse::Error {
+                    request_id: None,
+                    error: e.to_string(),
+                },
+                None,
+            ),
+        };
+        if tx.send(response).await.is_err() {
+            break;
+        }
+        // Finished subscriptions are reaped so a long-lived connection does not pile them up
+        while subscriptions.try_join_next().is_some() {}
+        if let Some(job_id) = follow {
+            subscribe(&app_state, job_id, &tx, &mut subscriptions);
+        }
+    }
+
+    info!("WebSocket client disconnected");
+    subscriptions.abort_all();
+    drop(tx);
+    if let Err(e) = writer.await {
+        warn!("WebSocket writer failed: {}", e);
+    }
+}
+
+/// Answers the request, along with the job whose events the client should receive from now on.
+async fn handle_request(
+    request: WsRequest,
+    app_state: &AppState,
+    signer: &Signer,
+) -> Result<(WsResponse, Option<Uuid>), ProverError> {
+    // The key may have been revoked or given another policy since the connection was opened
+    let signer = match signer.0 {
+        Some(key) => match app_state.authorizer.policy(&key).await? {
+            Some(policy) if !policy.is_expired(Utc::now()) => Signer(Some(key), policy),
+            _ => return Err(ProverError::Auth(AuthError::Unauthorized)),
+        },
+        None => signer.clone(),
+    };
+    // Messages count against the request rate of the key like HTTP requests
+    if let Some(key) = &signer.0 {
+        app_state
+            .request_limiter
+            .check(key, &signer.1.quota)
+            .await?;
+    }
+    let (request_id, job_id) = match request {
+        WsRequest::ProveCairo { request_id, input } => (
+            request_id,
+            prove::cairo::submit(app_state, signer, input).await?,
+        ),
+        WsRequest::ProveCairo0 { request_id, input } => (
+            request_id,
+            prove::cairo0::submit(app_state, signer, input).await?,
+        ),
+        WsRequest::RunCairo { request_id, input } => (
+            request_id,
+            run::cairo::submit(app_state, signer, input).await?,
+        ),
+        WsRequest::RunCairo0 { request_id, input } => (
+            request_id,
+            run::cairo0::submit(app_state, signer, input).await?,
+        ),
+        WsRequest::LayoutBridge { request_id, input } => (
+            request_id,
+            layout_bridge::submit(app_state, signer, input).await?,
+        ),
+        WsRequest::Snos { request_id, input } => (
+            request_id,
+            run::snos::submit(app_state, signer, input).await?,
+        ),
+        WsRequest::Subscribe { request_id, job_id } => {
+            readable_job(app_state, &signer, job_id).await?;
+            return Ok((WsResponse::Subscribed { request_id, job_id }, Some(job_id)));
+        }
+        WsRequest::GetJob { request_id, job_id } => {
+            let job = readable_job(app_state, &signer, job_id).await?;
+            let (_, job) = job_response(app_state, &job).await?;
+            return Ok((WsResponse::Job { request_id, job }, None));
+        }
+    };
+    Ok((WsResponse::Accepted { request_id, job_id }, Some(job_id)))
+}
+
+// Forwards the events of the job until it finishes or the client goes away
+fn subscribe(
+    app_state: &AppState,
+    job_id: Uuid,
+    tx: &Sender<WsResponse>,
+    subscriptions: &mut JoinSet<()>,
+) {
+    let job_store = app_state.job_store.clone();
+    let tx = tx.clone();
+    subscriptions.spawn(async move {
+        let (replay, live) = follow_job(&job_store, job_id, None).await;
+        for (id, event) in replay {
+            if tx.send(WsResponse::Event { id, event }).await.is_err() {
+                return;
+            }
+        }
+        let Some(mut live) = live else {
+            return;
+        };
+        while let Some((id, event)) = live.next().await {
+            if tx.send(WsResponse::Event { id, event }).await.is_err() {
+                return;
+            }
+        }
+    });
+}